@@ -0,0 +1,142 @@
+//! Client for a master-password agent listening on a Unix domain socket
+//!
+//! Frames are a big-endian `u32` length followed by the body. A request body
+//! is `get <vault>`; the response is a one-byte status followed by a frame
+//! holding the secret when the status is [`STATUS_OK`].
+
+use crate::{Error, Result};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// The agent returned the secret
+pub const STATUS_OK: u8 = 0;
+/// The agent has no secret for the vault (or refused to share it)
+pub const STATUS_NOT_FOUND: u8 = 1;
+
+/// Largest frame accepted from an agent
+const MAX_FRAME_LEN: usize = 64 * 1024;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ask the agent at `socket` for the master password of `vault`
+///
+/// Returns `Ok(None)` when the agent doesn't hold one.
+pub fn request_master_password(socket: &Path, vault: &str) -> Result<Option<Zeroizing<String>>> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    write_request(&mut stream, vault)?;
+    let secret = match read_response(&mut stream)? {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+
+    let secret = String::from_utf8(secret.to_vec())
+        .map_err(|_| Error::InvalidInput("Agent returned a password that is not valid UTF-8".to_string()))?;
+    Ok(Some(Zeroizing::new(secret)))
+}
+
+/// Write a request for the secret of `vault`
+pub fn write_request<W: Write>(writer: &mut W, vault: &str) -> Result<()> {
+    write_frame(writer, format!("get {}", vault).as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the agent's response, zeroizing the secret once dropped
+pub fn read_response<R: Read>(reader: &mut R) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    let mut status = [0u8; 1];
+    reader.read_exact(&mut status)?;
+
+    match status[0] {
+        STATUS_OK => Ok(Some(read_frame(reader)?)),
+        STATUS_NOT_FOUND => Ok(None),
+        other => Err(Error::InvalidInput(format!("Agent sent unknown status {}", other))),
+    }
+}
+
+/// Write a length-prefixed frame
+pub fn write_frame<W: Write>(writer: &mut W, body: &[u8]) -> Result<()> {
+    if body.len() > MAX_FRAME_LEN {
+        return Err(Error::InvalidInput(format!("Agent frame of {} bytes is too large", body.len())));
+    }
+
+    writer.write_all(&(body.len() as u32).to_be_bytes())?;
+    writer.write_all(body)?;
+    Ok(())
+}
+
+/// Read a length-prefixed frame
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Zeroizing<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(Error::InvalidInput(format!("Agent frame of {} bytes is too large", len)));
+    }
+
+    let mut body = Zeroizing::new(vec![0u8; len]);
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_request_framing() {
+        let mut request = Vec::new();
+        write_request(&mut request, "/vaults/passman.db").unwrap();
+
+        assert_eq!(&request[..4], &22u32.to_be_bytes());
+        assert_eq!(&*read_frame(&mut Cursor::new(&request)).unwrap(), b"get /vaults/passman.db");
+    }
+
+    #[test]
+    fn test_response_framing() {
+        let mut response = vec![STATUS_OK];
+        write_frame(&mut response, b"hunter2").unwrap();
+        let secret = read_response(&mut Cursor::new(response)).unwrap().unwrap();
+        assert_eq!(&*secret, b"hunter2");
+
+        assert!(read_response(&mut Cursor::new(vec![STATUS_NOT_FOUND])).unwrap().is_none());
+        assert!(read_response(&mut Cursor::new(vec![7])).is_err());
+
+        let mut oversized = vec![STATUS_OK];
+        oversized.extend_from_slice(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes());
+        assert!(read_response(&mut Cursor::new(oversized)).is_err());
+    }
+
+    #[test]
+    fn test_request_against_mock_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let agent = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request = read_frame(&mut stream).unwrap();
+                if &*request == b"get work" {
+                    stream.write_all(&[STATUS_OK]).unwrap();
+                    write_frame(&mut stream, b"correct horse").unwrap();
+                } else {
+                    stream.write_all(&[STATUS_NOT_FOUND]).unwrap();
+                }
+            }
+        });
+
+        let secret = request_master_password(&socket, "work").unwrap().unwrap();
+        assert_eq!(secret.as_str(), "correct horse");
+        assert!(request_master_password(&socket, "personal").unwrap().is_none());
+        agent.join().unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::crypto::Cipher;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// What this build of passman supports, for frontends and packaging tests
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub ciphers: Vec<&'static str>,
+    pub kdfs: Vec<&'static str>,
+    pub import_formats: Vec<&'static str>,
+    pub export_formats: Vec<&'static str>,
+    /// Optional cargo features and whether they were compiled in
+    pub features: BTreeMap<&'static str, bool>,
+}
+
+/// Capabilities of the running build
+pub fn capabilities() -> Capabilities {
+    let features = BTreeMap::from([
+        ("agent", cfg!(feature = "agent")),
+        ("clipboard-support", cfg!(feature = "clipboard-support")),
+        ("native-crypto", cfg!(feature = "native-crypto")),
+        ("network", cfg!(feature = "network")),
+        ("rpc", cfg!(feature = "rpc")),
+        ("tui", cfg!(feature = "tui")),
+        ("web-ui", cfg!(feature = "web-ui")),
+        // Not implemented yet; listed so frontends can probe for them
+        ("keyring", false),
+    ]);
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        ciphers: Cipher::value_variants().iter().map(|cipher| cipher.as_str()).collect(),
+        kdfs: vec!["argon2id"],
+        import_formats: vec!["passman-encrypted"],
+        export_formats: vec!["passman-encrypted", "passman-json"],
+        features,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_json_reflects_build() {
+        let json = serde_json::to_value(capabilities()).unwrap();
+
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["ciphers"], serde_json::json!(["chacha20-poly1305", "aes-256-gcm"]));
+        assert_eq!(json["features"]["web-ui"], cfg!(feature = "web-ui"));
+        assert_eq!(json["features"]["rpc"], cfg!(feature = "rpc"));
+        assert_eq!(json["features"]["agent"], cfg!(feature = "agent"));
+        assert_eq!(json["features"]["clipboard-support"], cfg!(feature = "clipboard-support"));
+        assert_eq!(json["features"]["tui"], cfg!(feature = "tui"));
+        assert_eq!(json["features"]["keyring"], false);
+    }
+}
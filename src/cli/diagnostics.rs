@@ -0,0 +1,91 @@
+use crate::database::PasswordRepository;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+/// Non-secret facts about a vault, safe to paste into a bug report
+///
+/// Salts, password hashes and ciphertexts are deliberately never collected.
+#[derive(Debug, Clone)]
+pub struct VaultDiagnostics {
+    pub app_version: &'static str,
+    pub schema_version: u32,
+    pub format_version: Option<u32>,
+    pub cipher: &'static str,
+    pub kdf: String,
+    pub entry_count: u64,
+    pub file_size: Option<u64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_access: Option<DateTime<Utc>>,
+}
+
+impl VaultDiagnostics {
+    /// Gather diagnostics for the vault stored at `path`
+    pub fn collect(repo: &PasswordRepository, path: &Path) -> Result<Self> {
+        let metadata = if repo.is_initialized()? {
+            Some(repo.get_vault_metadata()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            app_version: env!("CARGO_PKG_VERSION"),
+            schema_version: repo.schema_version()?,
+            format_version: metadata.as_ref().map(|m| m.format_version),
+            cipher: repo.cipher()?.display_name(),
+            kdf: repo.password_manager()?.kdf_description(),
+            entry_count: repo.count_entries()?,
+            file_size: std::fs::metadata(path).ok().map(|m| m.len()),
+            created_at: metadata.as_ref().map(|m| m.created_at),
+            last_access: metadata.as_ref().map(|m| m.last_access),
+        })
+    }
+
+    /// Render as `key: value` lines
+    pub fn render(&self) -> String {
+        let unknown = || "unknown".to_string();
+        [
+            ("App version", self.app_version.to_string()),
+            ("Schema version", self.schema_version.to_string()),
+            ("Format version", self.format_version.map_or_else(unknown, |v| v.to_string())),
+            ("Cipher", self.cipher.to_string()),
+            ("KDF", self.kdf.clone()),
+            ("Entries", self.entry_count.to_string()),
+            ("Database size", self.file_size.map_or_else(unknown, |s| format!("{} bytes", s))),
+            ("Created", self.created_at.map_or_else(unknown, |t| t.to_rfc3339())),
+            ("Last access", self.last_access.map_or_else(unknown, |t| t.to_rfc3339())),
+        ]
+        .iter()
+        .map(|(key, value)| format!("{:<15} {}", format!("{}:", key), value))
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    #[test]
+    fn test_diagnostics_exclude_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passman.db");
+        let repo = PasswordRepository::new(&path).unwrap();
+        crate::cli::session::initialize_with_password(&repo, "master").unwrap();
+        let metadata = repo.get_vault_metadata().unwrap();
+
+        let output = VaultDiagnostics::collect(&repo, &path).unwrap().render();
+        assert!(output.contains(env!("CARGO_PKG_VERSION")));
+        assert!(output.contains("Argon2id"));
+        assert!(output.contains("ChaCha20-Poly1305"));
+
+        let hash = String::from_utf8(metadata.password_hash.clone()).unwrap();
+        let salt_hex: String = metadata.salt.iter().map(|b| format!("{:02x}", b)).collect();
+        let salt_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(&metadata.salt);
+        assert!(!output.contains(&hash));
+        assert!(!output.contains(hash.rsplit('$').next().unwrap()));
+        assert!(!output.contains(&salt_hex));
+        assert!(!output.contains(&salt_b64));
+    }
+}
@@ -0,0 +1,310 @@
+use crate::database::{FieldUpdate, PasswordEntry, UpdateSpec};
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Stands in for the password in `edit --editor`; leaving it in place keeps the password
+pub const PASSWORD_MASK: &str = "********";
+
+const ENTRY_HEADER: &str = "\
+# Edit the entry, then save and quit. Leave the password as ******** to keep it;
+# an empty url or notes removes the field.
+";
+
+/// Resolve the editor command: configured editor, then $VISUAL, then $EDITOR
+pub fn resolve_editor(configured: Option<&str>) -> String {
+    configured
+        .map(str::to_string)
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| default_editor().to_string())
+}
+
+fn default_editor() -> &'static str {
+    if cfg!(windows) {
+        "notepad"
+    } else {
+        "vi"
+    }
+}
+
+/// Open `initial` in an editor and return the saved text
+///
+/// The editor command may include arguments (e.g. `code --wait`). The text is
+/// staged in a temp file readable only by the current user, which is
+/// overwritten and removed once the editor exits.
+pub fn edit_text(initial: &str, editor: &str) -> Result<String> {
+    let mut parts = editor.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| Error::InvalidInput("Editor command is empty".to_string()))?;
+
+    let temp = SecureTempFile::create()?;
+    fs::write(temp.path(), initial)?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(temp.path())
+        .status()
+        .map_err(|e| Error::InvalidInput(format!("Failed to launch editor '{}': {}", program, e)))?;
+    if !status.success() {
+        return Err(Error::InvalidInput(format!("Editor '{}' exited with {}", program, status)));
+    }
+
+    Ok(fs::read_to_string(temp.path())?)
+}
+
+/// An entry's fields as written to the editor
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct EntryDocument {
+    title: String,
+    username: String,
+    password: String,
+    url: String,
+    tags: Vec<String>,
+    notes: String,
+}
+
+/// Changes made to an entry in the editor
+#[derive(Debug, Default)]
+pub struct EntryEdit {
+    pub spec: UpdateSpec,
+    pub password: Option<Zeroizing<String>>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+}
+
+impl EntryEdit {
+    /// Whether the edit changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.spec.is_empty() && self.password.is_none() && self.add_tags.is_empty() && self.remove_tags.is_empty()
+    }
+}
+
+/// Render an entry as TOML for editing, with the password masked
+pub fn render_entry(entry: &PasswordEntry) -> Result<String> {
+    let document = EntryDocument {
+        title: entry.title.clone(),
+        username: entry.username.clone(),
+        password: PASSWORD_MASK.to_string(),
+        url: entry.url.clone().unwrap_or_default(),
+        tags: entry.tags.clone(),
+        notes: entry.notes.clone().unwrap_or_default(),
+    };
+    let toml = toml::to_string(&document).map_err(|e| Error::InvalidInput(e.to_string()))?;
+    Ok(format!("{}{}", ENTRY_HEADER, toml))
+}
+
+/// Parse an edited entry, returning what changed compared to `entry`
+pub fn parse_entry(text: &str, entry: &PasswordEntry) -> Result<EntryEdit> {
+    let document: EntryDocument = toml::from_str(text).map_err(|e| Error::InvalidInput(e.to_string()))?;
+    if document.password.is_empty() {
+        return Err(Error::InvalidInput(format!("password can't be empty; use {} to keep it", PASSWORD_MASK)));
+    }
+
+    let title = document.title.trim();
+    if title.is_empty() {
+        return Err(Error::InvalidInput("title can't be empty".to_string()));
+    }
+    let optional = |value: &str, current: Option<&str>| match (value, current) {
+        ("", None) => FieldUpdate::Keep,
+        ("", Some(_)) => FieldUpdate::Clear,
+        (value, Some(current)) if value == current => FieldUpdate::Keep,
+        (value, _) => FieldUpdate::Set(value.to_string()),
+    };
+    let spec = UpdateSpec {
+        title: (title != entry.title).then(|| title.to_string()),
+        username: (document.username != entry.username).then(|| document.username.clone()),
+        url: optional(document.url.trim(), entry.url.as_deref()),
+        notes: optional(document.notes.trim_end(), entry.notes.as_deref()),
+    };
+
+    let tags = document
+        .tags
+        .iter()
+        .map(|tag| PasswordEntry::normalize_tag(tag))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(EntryEdit {
+        spec,
+        password: (document.password != PASSWORD_MASK).then(|| Zeroizing::new(document.password.clone())),
+        add_tags: tags.iter().filter(|tag| !entry.tags.contains(tag)).cloned().collect(),
+        remove_tags: entry.tags.iter().filter(|tag| !tags.contains(tag)).cloned().collect(),
+    })
+}
+
+/// Edit a whole entry in an editor
+///
+/// If the saved text doesn't parse, the editor is reopened on it with the
+/// error at the top, so nothing typed is lost. Saving it unchanged gives up.
+pub fn edit_entry(entry: &PasswordEntry, editor: &str) -> Result<EntryEdit> {
+    let mut text = Zeroizing::new(render_entry(entry)?);
+    loop {
+        let edited = Zeroizing::new(edit_text(&text, editor)?);
+        let error = match parse_entry(&edited, entry) {
+            Ok(edit) => return Ok(edit),
+            Err(e) => e,
+        };
+        if *edited == *text {
+            return Err(error);
+        }
+
+        let body: String = edited.lines().skip_while(|line| line.starts_with('#')).map(|line| format!("{}\n", line)).collect();
+        text = Zeroizing::new(format!("# Error: {}\n{}{}", error.to_string().replace('\n', "\n# "), ENTRY_HEADER, body));
+    }
+}
+
+/// Temp file created with owner-only permissions and shredded on drop
+struct SecureTempFile {
+    path: PathBuf,
+}
+
+impl SecureTempFile {
+    fn create() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("passman-{}.txt", uuid::Uuid::new_v4()));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&path)?;
+
+        Ok(Self { path })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn shred(&self) -> std::io::Result<()> {
+        let len = fs::metadata(&self.path)?.len();
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&vec![0u8; len as usize])?;
+        file.sync_all()?;
+        drop(file);
+        fs::remove_file(&self.path)
+    }
+}
+
+impl Drop for SecureTempFile {
+    fn drop(&mut self) {
+        // Editors may replace the file; make sure it's gone regardless
+        if self.shred().is_err() && fs::remove_file(&self.path).is_err() && self.path.exists() {
+            log::warn!("Failed to remove temporary file {}", self.path.display());
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mock_editor(dir: &Path, script: &str) -> String {
+        let path = dir.join("mock-editor.sh");
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // Serialized: exec'ing a freshly written script races with forks in other tests (ETXTBSY)
+    #[test]
+    #[serial]
+    fn test_edit_text_reads_back_and_removes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = dir.path().join("record");
+        let editor = mock_editor(
+            dir.path(),
+            &format!(
+                "ls -l \"$1\" | cut -c1-10 > {0}\necho \"$1\" >> {0}\nprintf ' edited' >> \"$1\"",
+                record.display()
+            ),
+        );
+
+        let edited = edit_text("original notes", &editor).unwrap();
+        assert_eq!(edited, "original notes edited");
+
+        let record = fs::read_to_string(record).unwrap();
+        let mut lines = record.lines();
+        assert_eq!(lines.next(), Some("-rw-------"));
+        let temp_path = lines.next().unwrap();
+        assert!(!Path::new(temp_path).exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_edit_text_fails_when_editor_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let editor = mock_editor(dir.path(), "exit 1");
+
+        assert!(edit_text("notes", &editor).is_err());
+    }
+
+    #[test]
+    fn test_resolve_editor_prefers_configured() {
+        assert_eq!(resolve_editor(Some("nano")), "nano");
+    }
+
+    #[test]
+    fn test_parse_entry_reports_changes_only() {
+        let mut entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            Default::default(),
+            Some("https://github.com".to_string()),
+            None,
+        );
+        entry.tags = vec!["dev".to_string(), "work".to_string()];
+
+        let unchanged = parse_entry(&render_entry(&entry).unwrap(), &entry).unwrap();
+        assert!(unchanged.is_empty());
+
+        let text = render_entry(&entry)
+            .unwrap()
+            .replace("https://github.com", "")
+            .replace("notes = \"\"", "notes = \"\"\"\nline one\nline two\n\"\"\"")
+            .replace("\"dev\"", "\"Home\"")
+            .replace(PASSWORD_MASK, "n3w-secret");
+        let edit = parse_entry(&text, &entry).unwrap();
+        assert_eq!(edit.spec.title, None);
+        assert_eq!(edit.spec.url, FieldUpdate::Clear);
+        assert_eq!(edit.spec.notes, FieldUpdate::Set("line one\nline two".to_string()));
+        assert_eq!(edit.password.as_deref().map(String::as_str), Some("n3w-secret"));
+        assert_eq!(edit.add_tags, vec!["home"]);
+        assert_eq!(edit.remove_tags, vec!["dev"]);
+
+        assert!(parse_entry("title = \"unterminated", &entry).is_err());
+        assert!(parse_entry(&render_entry(&entry).unwrap().replace("GitHub", " "), &entry).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_edit_entry_reopens_on_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let count = dir.path().join("count");
+        // First save breaks the TOML, second fixes it and renames the entry
+        let editor = mock_editor(
+            dir.path(),
+            &format!(
+                "echo x >> {0}\nif [ $(wc -l < {0}) -eq 1 ]; then echo 'broken =' >> \"$1\"; \
+                 else grep -v '^broken' \"$1\" | sed 's/GitHub/Renamed/' > \"$1.new\" && mv \"$1.new\" \"$1\"; fi",
+                count.display()
+            ),
+        );
+        let entry = PasswordEntry::new("GitHub".to_string(), "octocat".to_string(), Default::default(), None, None);
+
+        let edit = edit_entry(&entry, &editor).unwrap();
+        assert_eq!(edit.spec.title.as_deref(), Some("Renamed"));
+        assert_eq!(fs::read_to_string(count).unwrap().lines().count(), 2);
+    }
+}
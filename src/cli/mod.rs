@@ -1,4 +1,21 @@
-use clap::{Parser, Subcommand};
+use crate::crypto::Cipher;
+use crate::database::{EntryField, SearchField};
+use crate::export::{ExportFormat, ImportFormat};
+use crate::utils::SafeContext;
+use crate::config::Config;
+use crate::Result;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use uuid::Uuid;
+use view::ListFormat;
+
+pub mod capabilities;
+pub mod diagnostics;
+pub mod editor;
+pub mod prompt;
+pub mod session;
+pub mod share;
+pub mod view;
 
 #[derive(Parser)]
 #[command(name = "passman")]
@@ -7,10 +24,49 @@ use clap::{Parser, Subcommand};
 #[command(about = "A secure offline password manager CLI tool")]
 #[command(long_about = None)]
 pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Options accepted by every command
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct GlobalArgs {
+    /// Profile whose config and vault to use
+    #[arg(long, global = true, env = crate::PROFILE_ENV, default_value = crate::DEFAULT_PROFILE)]
+    pub profile: String,
+    /// Vault database to use instead of the config's `database_path` (the config file is left alone)
+    #[arg(long, global = true, env = crate::VAULT_ENV, value_name = "PATH")]
+    pub vault: Option<PathBuf>,
+    /// Read the master password from the first line of this file instead of prompting
+    #[arg(long, global = true, value_name = "PATH")]
+    pub password_file: Option<PathBuf>,
+}
+
+impl GlobalArgs {
+    /// Load the profile's config, creating it if missing, with `--vault` applied
+    pub fn load_config(&self) -> Result<Config> {
+        Ok(self.apply(Config::load(&self.profile)?))
+    }
+
+    /// Load the profile's config without writing a default, with `--vault` applied
+    pub fn load_config_without_create(&self) -> Result<Config> {
+        Ok(self.apply(Config::load_without_create(&self.profile)?))
+    }
+
+    fn apply(&self, mut config: Config) -> Config {
+        if let Some(vault) = &self.vault {
+            config.database_path = vault.clone();
+        }
+        if let Some(password_file) = &self.password_file {
+            config.password_file = Some(password_file.clone());
+        }
+        config
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Initialize a new password vault
@@ -18,6 +74,18 @@ pub enum Commands {
         /// Force initialization even if vault exists
         #[arg(short, long)]
         force: bool,
+        /// Cipher to encrypt entries with
+        #[arg(long, value_enum, default_value_t = Cipher::default())]
+        cipher: Cipher,
+        /// Argon2 memory cost in MiB (default: security.kdf_memory_mib)
+        #[arg(long, value_name = "MIB")]
+        kdf_memory: Option<u32>,
+        /// Argon2 iterations (default: security.kdf_iterations)
+        #[arg(long, value_name = "N")]
+        kdf_iterations: Option<u32>,
+        /// Argon2 parallelism (default: security.kdf_parallelism)
+        #[arg(long, value_name = "N")]
+        kdf_parallelism: Option<u32>,
     },
     /// Add a new password entry
     Add {
@@ -29,54 +97,547 @@ pub enum Commands {
         /// Additional notes (optional)
         #[arg(short, long)]
         notes: Option<String>,
+        /// Write the notes in $EDITOR
+        #[arg(long, conflicts_with = "notes")]
+        edit_with_editor: bool,
+        /// Prompt for the fields of a template (website, card, ssh-key, or one from the config)
+        #[arg(short, long)]
+        template: Option<String>,
+        /// Generate the password with the vault's generator defaults instead of prompting
+        #[arg(short, long, conflicts_with = "template")]
+        generate: bool,
+        /// Tag the entry (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
     },
     /// Get a password entry
     Get {
         /// Name/title of the entry to retrieve
-        name: String,
+        #[arg(required_unless_present = "id")]
+        name: Option<String>,
+        /// Fetch by the entry's full UUID instead of its name
+        #[arg(long, conflicts_with = "name", value_parser = parse_entry_id)]
+        id: Option<Uuid>,
+        /// Print only this field's value, for scripts
+        #[arg(long, value_enum)]
+        field: Option<EntryField>,
+        /// Reveal the password instead of masking it
+        #[arg(long, conflicts_with = "field")]
+        show: bool,
     },
     /// List all password entries
-    List,
+    List {
+        /// Decrypt each entry to check its integrity (passwords are not shown)
+        #[arg(long)]
+        verify: bool,
+        /// Print only the number of entries
+        #[arg(long, conflicts_with_all = ["verify", "stats"])]
+        count_only: bool,
+        /// Print summary and per-tag counts instead of the entries
+        #[arg(long, conflicts_with = "verify")]
+        stats: bool,
+        /// Output format for the entry list
+        #[arg(long, value_enum, default_value_t = ListFormat::Table, conflicts_with_all = ["verify", "count_only", "stats"])]
+        format: ListFormat,
+        /// Only list entries with this tag (repeatable; entries must have every tag)
+        #[arg(long = "tag", value_name = "TAG", conflicts_with_all = ["verify", "count_only", "stats"])]
+        tags: Vec<String>,
+    },
     /// Edit an existing password entry
     Edit {
         /// Name/title of the entry to edit
         name: String,
+        /// New name/title
+        #[arg(long)]
+        title: Option<String>,
+        /// New username
+        #[arg(long)]
+        username: Option<String>,
+        /// New website URL
+        #[arg(short, long, conflicts_with = "clear_url")]
+        url: Option<String>,
+        /// New notes
+        #[arg(short, long, conflicts_with_all = ["clear_notes", "edit_with_editor"])]
+        notes: Option<String>,
+        /// Remove the URL
+        #[arg(long)]
+        clear_url: bool,
+        /// Remove the notes
+        #[arg(long, conflicts_with = "edit_with_editor")]
+        clear_notes: bool,
+        /// Edit the notes in $EDITOR
+        #[arg(long)]
+        edit_with_editor: bool,
+        /// Prompt for a new password
+        #[arg(long)]
+        password: bool,
+        /// Edit the whole entry as TOML in $EDITOR (the password is masked)
+        #[arg(long, conflicts_with_all = ["title", "username", "url", "notes", "clear_url", "clear_notes", "edit_with_editor", "password", "tags", "remove_tags"])]
+        editor: bool,
+        /// Add a tag (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Remove a tag (repeatable)
+        #[arg(long = "remove-tag", value_name = "TAG")]
+        remove_tags: Vec<String>,
     },
-    /// Delete a password entry
+    /// Delete a password entry, or purge one that is already in the trash
     Delete {
         /// Name/title of the entry to delete
         name: String,
         /// Skip confirmation prompt
         #[arg(short, long)]
         force: bool,
+        /// Move the entry to the trash instead of removing it (see `restore`)
+        #[arg(long)]
+        soft: bool,
+    },
+    /// Restore an entry deleted with `delete --soft`
+    Restore {
+        /// Name/title of the entry to restore
+        name: String,
     },
     /// Generate a secure password
     Generate {
-        /// Password length (default: 16)
-        #[arg(short, long, default_value_t = 16)]
-        length: u32,
+        /// Password length (default: the vault's stored default with --vault-defaults, then the config's)
+        #[arg(short, long)]
+        length: Option<u32>,
         /// Exclude symbols from generated password
         #[arg(long)]
         no_symbols: bool,
         /// Exclude numbers from generated password
         #[arg(long)]
         no_numbers: bool,
+        /// Exclude characters that need escaping in the given context
+        #[arg(long, value_enum)]
+        safe_for: Option<SafeContext>,
+        /// Additional characters to exclude
+        #[arg(long)]
+        exclude: Option<String>,
+        /// Exclude visually ambiguous characters such as l, 1, I, O and 0
+        #[arg(long)]
+        no_ambiguous: bool,
+        /// Symbols to draw from instead of the configured symbol set
+        #[arg(long)]
+        symbols: Option<String>,
+        /// Draw only from exactly these characters, ignoring the character classes
+        #[arg(long, conflicts_with_all = ["no_symbols", "no_numbers", "symbols", "save_default", "min_lowercase", "min_uppercase", "min_digits", "min_symbols"])]
+        charset: Option<String>,
+        /// Fill in a fixed structure such as "UUdd-ssll" (L letter, l lowercase, U uppercase, d digit, s symbol; anything else is literal)
+        #[arg(long, conflicts_with_all = ["length", "charset", "no_symbols", "no_numbers", "preview_charset", "save_default", "min_lowercase", "min_uppercase", "min_digits", "min_symbols"])]
+        mask: Option<String>,
+        /// Print the characters the password would be drawn from instead of generating one
+        #[arg(long, conflicts_with = "save_default")]
+        preview_charset: bool,
+        /// Store the resulting length and character classes as this vault's defaults
+        #[arg(long)]
+        save_default: bool,
+        /// Start from this vault's stored defaults, unlocking it if needed (always on in an unlocked shell)
+        #[arg(long)]
+        vault_defaults: bool,
+        /// Write the password to this file (mode 0600) instead of stdout
+        #[arg(short, long, conflicts_with = "preview_charset")]
+        output: Option<std::path::PathBuf>,
+        /// Generate a passphrase of this many words instead of a random string
+        #[arg(long, conflicts_with_all = ["length", "no_symbols", "no_numbers", "safe_for", "exclude", "no_ambiguous", "symbols", "charset", "mask", "preview_charset", "save_default", "min_lowercase", "min_uppercase", "min_digits", "min_symbols"])]
+        words: Option<u32>,
+        /// String placed between passphrase words
+        #[arg(long, requires = "words", default_value = "-")]
+        separator: String,
+        /// Capitalize each passphrase word
+        #[arg(long, requires = "words")]
+        capitalize: bool,
+        /// Append a random digit to the passphrase
+        #[arg(long, requires = "words")]
+        append_digit: bool,
+        /// Check a password read from stdin against a policy instead of generating one
+        #[arg(long, conflicts_with_all = ["preview_charset", "save_default", "output", "words"])]
+        check: bool,
+        /// Minimum length required by --check
+        #[arg(long, requires = "check")]
+        min_length: Option<usize>,
+        /// Minimum number of lowercase letters in the generated password, or required by --check
+        #[arg(long, default_value_t = 0)]
+        min_lowercase: usize,
+        /// Minimum number of uppercase letters in the generated password, or required by --check
+        #[arg(long, default_value_t = 0)]
+        min_uppercase: usize,
+        /// Minimum number of digits in the generated password, or required by --check
+        #[arg(long, default_value_t = 0)]
+        min_digits: usize,
+        /// Minimum number of symbols in the generated password, or required by --check
+        #[arg(long, default_value_t = 0)]
+        min_symbols: usize,
+        /// Minimum estimated entropy in bits required by --check
+        #[arg(long, requires = "check", default_value_t = 0.0)]
+        min_entropy: f64,
+    },
+    /// Duplicate an entry under a new name
+    Clone {
+        /// Name/title of the entry to clone
+        name: String,
+        /// Name/title for the new entry
+        new_name: String,
     },
-    /// Copy password to clipboard
+    /// Copy a password, or another field of an entry, to the clipboard
     Copy {
         /// Name/title of the entry to copy
         name: String,
+        /// Field to copy; only passwords and notes are cleared after `clipboard_timeout`
+        #[arg(short, long, value_enum, default_value_t = EntryField::Password)]
+        field: EntryField,
+    },
+    /// Open an entry's URL in the default browser
+    Open {
+        /// Name/title of the entry
+        name: String,
+        /// Also copy the password to the clipboard
+        #[arg(short, long)]
+        copy: bool,
+    },
+    /// List an entry's previous passwords
+    History {
+        /// Name/title of the entry
+        name: String,
+        /// Reveal the Nth previous password (1 = the most recently replaced)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        reveal: Option<u32>,
+    },
+    /// Copy username and password as a single login block
+    CopyLogin {
+        /// Name/title of the entry to copy
+        name: String,
+    },
+    /// Show a full credential for transfer to another device
+    Share {
+        /// Name/title of the entry to share
+        name: String,
+    },
+    /// Manage the duress password that opens a decoy vault
+    Duress {
+        #[command(subcommand)]
+        action: DuressAction,
+    },
+    /// Manage an entry's TOTP secret
+    Totp {
+        #[command(subcommand)]
+        action: TotpAction,
+    },
+    /// Print the current TOTP code for an entry
+    Otp {
+        /// Name/title of the entry
+        name: String,
     },
     /// Search password entries
     Search {
         /// Search query
         query: String,
+        /// Fields to search, comma-separated (default: all)
+        #[arg(long = "in", visible_alias = "field", value_enum, value_delimiter = ',')]
+        fields: Vec<SearchField>,
+        /// Show which fields matched the query for each result
+        #[arg(long)]
+        explain: bool,
+        /// Also decrypt and search encrypted custom fields (slower)
+        #[arg(long)]
+        deep: bool,
+        /// Treat the query as a regular expression
+        #[arg(long, conflicts_with = "deep")]
+        regex: bool,
+        /// Rank matches by relevance using the full-text index (whole words and prefixes in title, URL and notes)
+        #[arg(long, conflicts_with_all = ["fields", "deep", "regex"])]
+        ranked: bool,
+        /// Output format for the matches
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Summarise the vault's entries without decrypting any passwords
+    Stats,
+    /// Check the vault's passwords for problems
+    Audit {
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+    /// Regenerate passwords for flagged entries
+    Rotate {
+        /// Rotate passwords whose estimated entropy is below --min-bits
+        #[arg(long)]
+        weak: bool,
+        /// Rotate passwords found in Have I Been Pwned (needs --online)
+        #[arg(long)]
+        breached: bool,
+        /// Allow --breached to send the first 5 hex characters of each password's SHA-1 hash to the HIBP API
+        #[arg(long, requires = "breached")]
+        online: bool,
+        /// Entropy threshold in bits used by --weak (default: security.weak_password_min_bits)
+        #[arg(long, requires = "weak")]
+        min_bits: Option<f64>,
+    },
+    /// Copy the encrypted vault file, e.g. before a risky operation
+    Backup {
+        /// Where to write the copy (default: a timestamped file next to the vault)
+        #[arg(short, long)]
+        out: Option<std::path::PathBuf>,
+        /// Replace the file if it already exists
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Export the vault to an encrypted backup file
+    Export {
+        /// File to write the backup to
+        file: std::path::PathBuf,
+        /// Format of the backup
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Gzip the entries before encrypting them
+        #[arg(long)]
+        compress: bool,
+        /// Export only this entry
+        #[arg(long)]
+        entry: Option<String>,
+        /// Write unencrypted JSON (asks for confirmation)
+        #[arg(long, conflicts_with = "compress")]
+        plaintext: bool,
+    },
+    /// Import entries from an encrypted backup or a CSV file
+    Import {
+        /// File to read
+        file: std::path::PathBuf,
+        /// Format of the file
+        #[arg(long, value_enum, default_value_t = ImportFormat::Backup)]
+        format: ImportFormat,
+        /// Report new entries, conflicts and duplicates without importing anything
+        #[arg(long)]
+        report_only: bool,
+        /// Replace entries whose title already exists instead of skipping them (CSV and Bitwarden only)
+        #[arg(long, conflicts_with = "report_only")]
+        overwrite: bool,
+    },
+    /// Compare this vault with another one, e.g. a backup
+    Diff {
+        /// Path to the other vault's database file
+        other_vault: std::path::PathBuf,
+    },
+    /// Generate a new KDF salt and re-encrypt the vault, keeping the master password
+    RehashMaster,
+    /// Change the master password and re-encrypt the vault under it
+    Passwd,
+    /// Re-encrypt every entry under a different cipher
+    Reencrypt {
+        /// Cipher to migrate the vault to
+        #[arg(long, value_enum)]
+        to: Cipher,
+    },
+    /// Print diagnostic information for bug reports
+    Debug {
+        /// Show non-secret vault metadata (versions, KDF, sizes)
+        #[arg(long)]
+        metadata: bool,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
     },
+    /// Print the version and supported features as JSON
+    Capabilities,
+    /// Run known-answer tests against the crypto stack
+    SelfTest,
+    /// Run commands interactively against a vault that stays unlocked until `security.session_timeout`
+    Shell,
+    /// Time the key derivation and suggest Argon2 settings for this machine
+    BenchKdf {
+        /// Unlock time to aim for, in milliseconds
+        #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u64).range(1..))]
+        target_ms: u64,
+    },
+    /// Verify the migrations table against the database schema
+    Migrate {
+        /// Reconcile the recorded migrations with the actual schema
+        #[arg(long)]
+        repair: bool,
+        /// Encrypt an unencrypted vault file created by an older version
+        #[arg(long, conflicts_with = "repair")]
+        encrypt: bool,
+        /// Undo the migrations newer than this schema version (asks for confirmation)
+        #[arg(long, value_name = "VERSION", conflicts_with_all = ["repair", "encrypt"], value_parser = clap::value_parser!(u32).range(1..))]
+        rollback: Option<u32>,
+    },
+    /// Read or change settings in the profile's config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage profiles, each with its own config and vault
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    /// Serve JSON-RPC requests over stdin/stdout
+    #[cfg(feature = "rpc")]
+    Serve,
+    /// Search entries interactively and copy one's password
+    #[cfg(feature = "tui")]
+    Tui,
     /// Start web interface
     #[cfg(feature = "web-ui")]
     Web {
         /// Port to run web server on
         #[arg(short, long, default_value_t = 8080)]
         port: u16,
+        /// Address to listen on; anything but loopback needs --allow-remote
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: std::net::IpAddr,
+        /// Allow a --bind address reachable from other machines (no TLS: the token and passwords travel in plain text)
+        #[arg(long)]
+        allow_remote: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DuressAction {
+    /// Set or replace the duress password, creating a fresh decoy vault
+    Set,
+    /// Remove the duress password and its decoy vault
+    Remove,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print a setting, e.g. `password_generation.default_length`
+    Get {
+        /// Dotted key of the setting
+        key: String,
+    },
+    /// Change a setting and save the config file
+    Set {
+        /// Dotted key of the setting
+        key: String,
+        /// New value, parsed as the setting's type
+        value: String,
+    },
+    /// Print the path of the config file
+    Path,
+}
+
+#[derive(Subcommand)]
+pub enum ProfileAction {
+    /// List profiles, marking the active one
+    List,
+    /// Create a profile with a default config; run `passman --profile <name> init` next
+    New {
+        /// Name of the profile (letters, digits, '-' and '_')
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuditAction {
+    /// List groups of entries that share a password; fails if any are found
+    Reuse,
+    /// List entries whose password's estimated entropy is too low; fails if any are found
+    Weak {
+        /// Entropy threshold in bits (default: security.weak_password_min_bits)
+        #[arg(long)]
+        min_bits: Option<f64>,
+    },
+    /// Check passwords against Have I Been Pwned; fails if any are breached
+    Pwned {
+        /// Allow sending the first 5 hex characters of each password's SHA-1 hash to the HIBP API
+        #[arg(long)]
+        online: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TotpAction {
+    /// Set or replace the TOTP secret (prompted for when not given)
+    Set {
+        /// Name/title of the entry
+        name: String,
+        /// Base32 secret from the site's 2FA setup page
+        secret: Option<String>,
+    },
+    /// Remove the TOTP secret, disabling 2FA codes for the entry
+    Remove {
+        /// Name/title of the entry
+        name: String,
     },
 }
+
+/// Parse a full entry UUID given on the command line
+fn parse_entry_id(value: &str) -> std::result::Result<Uuid, String> {
+    Uuid::parse_str(value.trim())
+        .map_err(|e| format!("'{}' is not a valid entry ID (expected a full UUID): {}", value, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_by_id_parses_uuid() {
+        let id = Uuid::new_v4();
+        let cli = Cli::try_parse_from(["passman", "get", "--id", &id.to_string()]).unwrap();
+
+        match cli.command {
+            Commands::Get { name, id: parsed, .. } => {
+                assert_eq!(name, None);
+                assert_eq!(parsed, Some(id));
+            }
+            _ => panic!("expected get command"),
+        }
+    }
+
+    #[test]
+    fn test_get_by_id_rejects_malformed_uuid_and_name() {
+        assert!(Cli::try_parse_from(["passman", "get", "--id", "not-a-uuid"]).is_err());
+        assert!(Cli::try_parse_from(["passman", "get", "--id", &Uuid::new_v4().to_string(), "GitHub"]).is_err());
+        assert!(Cli::try_parse_from(["passman", "get"]).is_err());
+    }
+
+    #[test]
+    fn test_vault_override_is_global_and_replaces_database_path() {
+        let cli = Cli::try_parse_from(["passman", "list", "--vault", "/media/usb/passman.db"]).unwrap();
+        assert_eq!(cli.global.vault.as_deref(), Some(std::path::Path::new("/media/usb/passman.db")));
+
+        let config = cli.global.apply(Config::default());
+        assert_eq!(config.database_path, PathBuf::from("/media/usb/passman.db"));
+    }
+
+    #[test]
+    fn test_rotate_min_bits_defers_to_config() {
+        let cli = Cli::try_parse_from(["passman", "rotate", "--weak"]).unwrap();
+        assert!(matches!(cli.command, Commands::Rotate { min_bits: None, .. }));
+
+        let cli = Cli::try_parse_from(["passman", "rotate", "--weak", "--min-bits", "80"]).unwrap();
+        assert!(matches!(cli.command, Commands::Rotate { min_bits: Some(bits), .. } if bits == 80.0));
+        assert!(Cli::try_parse_from(["passman", "rotate", "--breached", "--min-bits", "80"]).is_err());
+    }
+
+    #[test]
+    fn test_copy_field_selects_value_and_clearing() {
+        use crate::database::{PasswordEntry, SecureString};
+
+        let cli = Cli::try_parse_from(["passman", "copy", "GitHub"]).unwrap();
+        assert!(matches!(cli.command, Commands::Copy { field: EntryField::Password, .. }));
+        let cli = Cli::try_parse_from(["passman", "copy", "GitHub", "--field", "username"]).unwrap();
+        assert!(matches!(cli.command, Commands::Copy { field: EntryField::Username, .. }));
+
+        let entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::from("hunter2"),
+            None,
+            Some("recovery codes".to_string()),
+        );
+        assert_eq!(EntryField::Password.value(&entry), Some("hunter2"));
+        assert_eq!(EntryField::Username.value(&entry), Some("octocat"));
+        assert_eq!(EntryField::Url.value(&entry), None);
+        assert_eq!(EntryField::Notes.value(&entry), Some("recovery codes"));
+
+        // Secrets are cleared after clipboard_timeout; the rest stays
+        assert!(EntryField::Password.is_sensitive());
+        assert!(EntryField::Notes.is_sensitive());
+        assert!(!EntryField::Username.is_sensitive());
+        assert!(!EntryField::Url.is_sensitive());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::crypto::read_password;
+use crate::database::templates::TemplateField;
+use crate::database::{FieldKind, FieldUpdate, PasswordEntry, UpdateSpec};
+use crate::Result;
+use std::io::{BufRead, Write};
+
+/// Ask a yes/no question on the terminal, defaulting to no
+pub fn confirm(prompt: &str) -> Result<bool> {
+    let stdin = std::io::stdin();
+    confirm_with(prompt, &mut stdin.lock(), &mut std::io::stderr())
+}
+
+/// Ask a yes/no question using the given input and output streams
+pub fn confirm_with<R: BufRead, W: Write>(prompt: &str, input: &mut R, output: &mut W) -> Result<bool> {
+    write!(output, "{} [y/N] ", prompt)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Read a single line of text from the terminal
+pub fn read_line(prompt: &str) -> Result<String> {
+    let stdin = std::io::stdin();
+    read_line_with(prompt, &mut stdin.lock(), &mut std::io::stderr())
+}
+
+/// Read a single line of text using the given input and output streams
+pub fn read_line_with<R: BufRead, W: Write>(prompt: &str, input: &mut R, output: &mut W) -> Result<String> {
+    write!(output, "{}", prompt)?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Read a secret without echo, returning `None` when left blank
+pub fn read_optional_secret(prompt: &str) -> Result<Option<String>> {
+    let secret = rpassword::prompt_password(prompt)?;
+    Ok(if secret.is_empty() { None } else { Some(secret) })
+}
+
+/// Prompt for new values of an entry's metadata on the terminal
+pub fn edit_spec(entry: &PasswordEntry) -> Result<UpdateSpec> {
+    let stdin = std::io::stdin();
+    edit_spec_with(entry, &mut stdin.lock(), &mut std::io::stderr())
+}
+
+/// Prompt for new metadata values using the given input and output streams
+///
+/// Blank input keeps the current value; `-` clears an optional field.
+pub fn edit_spec_with<R: BufRead, W: Write>(entry: &PasswordEntry, input: &mut R, output: &mut W) -> Result<UpdateSpec> {
+    let mut ask = |name: &str, current: &str| {
+        read_line_with(&format!("{} [{}]: ", name, current), input, output).map(|value| value.trim().to_string())
+    };
+
+    let required = |value: String, current: &str| (!value.is_empty() && value != current).then_some(value);
+    let optional = |value: String, current: Option<&str>| match value.as_str() {
+        "" => FieldUpdate::Keep,
+        "-" if current.is_some() => FieldUpdate::Clear,
+        "-" => FieldUpdate::Keep,
+        _ if Some(value.as_str()) == current => FieldUpdate::Keep,
+        _ => FieldUpdate::Set(value),
+    };
+
+    let title = required(ask("Title", &entry.title)?, &entry.title);
+    let username = required(ask("Username", &entry.username)?, &entry.username);
+    let url = optional(ask("URL ('-' to clear)", entry.url.as_deref().unwrap_or(""))?, entry.url.as_deref());
+    let notes = optional(ask("Notes ('-' to clear)", entry.notes.as_deref().unwrap_or(""))?, entry.notes.as_deref());
+
+    Ok(UpdateSpec { title, username, url, notes })
+}
+
+/// Prompt for a template field according to its kind
+///
+/// Secrets are read without echo; file fields ask for a path and return the
+/// file's contents.
+pub fn template_field(field: &TemplateField) -> Result<String> {
+    let kind = if field.name == "password" { FieldKind::Secret } else { field.kind };
+    match kind {
+        FieldKind::Text => read_line(&format!("{}: ", field.name)),
+        FieldKind::Secret => read_password(&format!("{}: ", field.name)),
+        FieldKind::File => {
+            let path = read_line(&format!("{} (path): ", field.name))?;
+            Ok(std::fs::read_to_string(path.trim())?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_confirm_defaults_to_no() {
+        let mut output = Vec::new();
+        assert!(confirm_with("Reveal?", &mut Cursor::new("y\n"), &mut output).unwrap());
+        assert!(confirm_with("Reveal?", &mut Cursor::new("YES\n"), &mut output).unwrap());
+        assert!(!confirm_with("Reveal?", &mut Cursor::new("\n"), &mut output).unwrap());
+        assert!(!confirm_with("Reveal?", &mut Cursor::new(""), &mut output).unwrap());
+        assert!(String::from_utf8(output).unwrap().starts_with("Reveal? [y/N] "));
+    }
+
+    #[test]
+    fn test_edit_spec_blank_keeps_and_dash_clears() {
+        let entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            Default::default(),
+            Some("https://github.com".to_string()),
+            Some("old notes".to_string()),
+        );
+
+        let mut output = Vec::new();
+        let spec = edit_spec_with(&entry, &mut Cursor::new("
+monalisa
+-
+
+"), &mut output).unwrap();
+        assert_eq!(
+            spec,
+            UpdateSpec {
+                title: None,
+                username: Some("monalisa".to_string()),
+                url: FieldUpdate::Clear,
+                notes: FieldUpdate::Keep,
+            }
+        );
+        assert!(String::from_utf8(output).unwrap().starts_with("Title [GitHub]: "));
+
+        let spec = edit_spec_with(&entry, &mut Cursor::new(""), &mut Vec::new()).unwrap();
+        assert!(spec.is_empty());
+    }
+}
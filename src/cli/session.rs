@@ -0,0 +1,914 @@
+use crate::config::{Config, SecurityConfig};
+use crate::crypto::{read_password, Argon2Params, Cipher, LockedKey, PasswordManager};
+use crate::database::{DatabaseState, PasswordRepository};
+use crate::utils::idle::{Clock, IdleTimer};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// An unlocked vault kept open across commands, e.g. by `passman shell`
+///
+/// The key is a `LockedKey`, so it's zeroized when the session is dropped.
+pub struct Session {
+    repo: PasswordRepository,
+    key: LockedKey,
+    // Never touched, so it measures the time since unlocking
+    unlocked: IdleTimer,
+}
+
+impl Session {
+    /// Start a session that expires `timeout` after now; a zero timeout never expires
+    pub fn new(repo: PasswordRepository, key: LockedKey, timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            repo,
+            key,
+            unlocked: IdleTimer::new(timeout, clock),
+        }
+    }
+
+    pub fn repo(&self) -> &PasswordRepository {
+        &self.repo
+    }
+
+    pub fn key(&self) -> &LockedKey {
+        &self.key
+    }
+
+    /// Whether the session has outlived its timeout and must be unlocked again
+    pub fn is_expired(&self) -> bool {
+        self.unlocked.is_expired()
+    }
+}
+
+/// Drop `session` if it has expired, zeroizing its key
+///
+/// Returns whether a session was dropped.
+pub fn expire_session(session: &mut Option<Session>) -> bool {
+    if session.as_ref().is_some_and(Session::is_expired) {
+        *session = None;
+        return true;
+    }
+    false
+}
+
+/// Prefix of the `Error::Authentication` message for a locked-out vault
+const LOCKED_OUT: &str = "Too many failed attempts; vault locked until";
+
+/// When repeated wrong master passwords lock the vault
+///
+/// The failure count and the end of a lockout are kept in a file next to
+/// the vault rather than in it: an encrypted vault can't be read until the
+/// right password is known, which is too late to count the wrong ones.
+#[derive(Debug, Clone)]
+pub struct Lockout {
+    /// Failed unlocks that trigger a lockout (0 = never lock)
+    pub max_attempts: u32,
+    /// How long a lockout lasts
+    pub duration: chrono::Duration,
+    /// File holding the `LockoutState`; `None` never locks
+    state_path: Option<PathBuf>,
+}
+
+/// Contents of a vault's lockout file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockoutState {
+    failed_attempts: u32,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl Lockout {
+    /// Lockout for the vault at `vault` from `security.max_login_attempts` and `security.lockout_duration`
+    pub fn for_vault(security: &SecurityConfig, vault: &Path) -> Self {
+        Self {
+            max_attempts: security.max_login_attempts,
+            duration: chrono::Duration::minutes(security.lockout_duration as i64),
+            state_path: Some(Self::state_path(vault)),
+        }
+    }
+
+    /// A lockout that never triggers
+    pub fn disabled() -> Self {
+        Self { max_attempts: 0, duration: chrono::Duration::zero(), state_path: None }
+    }
+
+    /// File next to `vault` holding its failure count, e.g. `passman.db.lockout`
+    pub fn state_path(vault: &Path) -> PathBuf {
+        let mut name = vault.file_name().unwrap_or_default().to_os_string();
+        name.push(".lockout");
+        vault.with_file_name(name)
+    }
+
+    fn enabled_path(&self) -> Option<&Path> {
+        let enabled = self.max_attempts > 0 && self.duration > chrono::Duration::zero();
+        self.state_path.as_deref().filter(|_| enabled)
+    }
+
+    fn read_state(path: &Path) -> Result<LockoutState> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(LockoutState::default()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable lockout file {}: {}", path.display(), e);
+            LockoutState::default()
+        }))
+    }
+
+    /// End of the current lockout, if the vault is locked out at `now`
+    pub fn locked_until(&self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let Some(path) = self.enabled_path() else { return Ok(None) };
+        Ok(Self::read_state(path)?.locked_until.filter(|until| *until > now))
+    }
+
+    /// Fail if the vault is within a lockout window
+    pub fn check(&self) -> Result<()> {
+        match self.locked_until(Utc::now())? {
+            Some(until) => Err(locked_out(until)),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a wrong master password, or forget earlier ones after a successful unlock
+    ///
+    /// A failure that starts a lockout is reported as one.
+    pub fn record<T>(&self, result: Result<T>) -> Result<T> {
+        let Some(path) = self.enabled_path() else { return result };
+        match result {
+            Err(Error::Authentication(message)) => {
+                let mut state = Self::read_state(path)?;
+                state.failed_attempts += 1;
+                let locked_until = (state.failed_attempts >= self.max_attempts).then(|| Utc::now() + self.duration);
+                if let Some(until) = locked_until {
+                    // The count starts over once the lockout begins
+                    state = LockoutState { failed_attempts: 0, locked_until: Some(until) };
+                }
+                std::fs::write(path, serde_json::to_string(&state)?)?;
+                Err(locked_until.map_or(Error::Authentication(message), locked_out))
+            }
+            Ok(value) => {
+                match std::fs::remove_file(path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+}
+
+fn locked_out(until: DateTime<Utc>) -> Error {
+    Error::Authentication(format!("{} {}", LOCKED_OUT, until.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)))
+}
+
+/// Like `unlock_with_password`, refusing during a lockout and counting wrong passwords
+///
+/// A successful unlock resets the count.
+pub fn unlock_with_lockout(repo: &PasswordRepository, password: &str, lockout: &Lockout) -> Result<Vec<u8>> {
+    lockout.check()?;
+    lockout.record(unlock_with_password(repo, password))
+}
+
+/// Open the vault at `path` and unlock it, refusing during a lockout and counting wrong passwords
+///
+/// A password that the encrypted file itself rejects counts as well. Unlike
+/// `unlock_with_retries`, the duress password isn't recognised.
+pub fn open_with_lockout(open: Opener, path: &Path, password: &str, lockout: &Lockout) -> Result<(PasswordRepository, Vec<u8>)> {
+    lockout.check()?;
+    lockout.record(open(path, password).and_then(|repo| {
+        let key = unlock_with_password(&repo, password)?;
+        Ok((repo, key))
+    }))
+}
+
+/// Open the configured vault and unlock it with the master password
+///
+/// Returns the repository together with the derived encryption key, locked
+/// into RAM when `security.lock_memory` is enabled.
+///
+/// If a duress password is configured and entered instead, the decoy vault is
+/// returned in place of the real one. A wrong password is re-prompted up to
+/// `security.max_login_attempts` times when `security.retry_wrong_password` is set.
+pub fn unlock(config: &Config) -> Result<(PasswordRepository, LockedKey)> {
+    config.prepare_database_dir()?;
+    unlock_session(config, open_initialized)
+}
+
+/// Like `unlock`, but opens the vault read-only for audits and exports
+///
+/// See `PasswordRepository::open_readonly`.
+pub fn unlock_readonly(config: &Config) -> Result<(PasswordRepository, LockedKey)> {
+    unlock_session(config, open_initialized_readonly)
+}
+
+fn unlock_session(config: &Config, open: Opener) -> Result<(PasswordRepository, LockedKey)> {
+    let mut scripted = scripted_master_password(config)?;
+    // Nobody is there to retype a wrong scripted password
+    let attempts = if scripted.is_some() || !config.security.retry_wrong_password {
+        1
+    } else {
+        config.security.max_login_attempts.max(1)
+    };
+
+    let mut agent_tried = false;
+    let source = || {
+        if let Some(password) = scripted.take() {
+            return Ok(password);
+        }
+        let password = if agent_tried {
+            Zeroizing::new(read_password("Master password: ")?)
+        } else {
+            agent_tried = true;
+            read_master_password(config)?
+        };
+        Ok(password)
+    };
+    let lockout = Lockout::for_vault(&config.security, &config.database_path);
+    let (mut repo, key) = unlock_with_retries(open, &config.database_path, &config.decoy_path(), source, attempts, &lockout)?;
+    repo.set_history_limit(config.max_history_per_entry);
+
+    if PasswordRepository::is_plaintext_file(&config.database_path)? {
+        eprintln!(
+            "Warning: {} is not encrypted; run `passman migrate --encrypt` to encrypt it",
+            config.database_path.display()
+        );
+    }
+
+    Ok((repo, LockedKey::new(key, config.security.lock_memory)))
+}
+
+/// Opens a vault file with the master password, failing if it hasn't been initialized
+pub type Opener = fn(&Path, &str) -> Result<PasswordRepository>;
+
+/// Fail with `Error::VaultNotInitialized` before prompting if there is no vault at `path`
+fn ensure_vault_exists(path: &Path) -> Result<()> {
+    match PasswordRepository::inspect(path)? {
+        DatabaseState::Missing | DatabaseState::Empty | DatabaseState::Uninitialized => Err(Error::VaultNotInitialized),
+        _ => Ok(()),
+    }
+}
+
+/// Unlock the vault at `path`, asking `next_password` again after a wrong password
+///
+/// Gives up with `Error::Authentication` after `attempts` wrong passwords, or
+/// as soon as the vault is locked out. The duress password is refused during
+/// a lockout but never counts as a failed unlock.
+pub fn unlock_with_retries<F>(
+    open: Opener,
+    path: &Path,
+    decoy_path: &Path,
+    mut next_password: F,
+    attempts: u32,
+    lockout: &Lockout,
+) -> Result<(PasswordRepository, Vec<u8>)>
+where
+    F: FnMut() -> Result<Zeroizing<String>>,
+{
+    ensure_vault_exists(path)?;
+
+    let mut attempt = 1;
+    loop {
+        lockout.check()?;
+        let password = next_password()?;
+        let result = match open(path, &password) {
+            Ok(repo) => unlock_or_decoy(repo, &password, decoy_path),
+            // An encrypted vault can't be opened with the duress password to check it
+            Err(Error::Authentication(message)) => {
+                unlock_existing_decoy(decoy_path, &password)?.ok_or(Error::Authentication(message))
+            }
+            Err(e) => Err(e),
+        };
+
+        match lockout.record(result) {
+            Err(Error::Authentication(message)) if attempt < attempts && !message.starts_with(LOCKED_OUT) => {
+                eprintln!("Wrong master password ({} of {} attempts)", attempt, attempts);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Open and unlock the vault at `path`, prompting with `prompt`
+///
+/// Wrong passwords count towards that vault's own lockout.
+pub fn unlock_path(path: &Path, prompt: &str, security: &SecurityConfig) -> Result<(PasswordRepository, LockedKey)> {
+    ensure_vault_exists(path)?;
+
+    let lockout = Lockout::for_vault(security, path);
+    lockout.check()?;
+    let password = Zeroizing::new(read_password(prompt)?);
+    let (repo, key) = open_with_lockout(open_initialized, path, &password, &lockout)?;
+
+    Ok((repo, LockedKey::new(key, security.lock_memory)))
+}
+
+/// `PASSMAN_PASSWORD`, once `take_password_env` has moved it out of the environment
+static PASSWORD_FROM_ENV: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+/// Move `PASSMAN_PASSWORD` out of the environment for the next unlock
+///
+/// Child processes such as `$EDITOR` then don't inherit it. Call this first
+/// thing in `main`, before any other thread exists: changing the environment
+/// while another thread may read it is undefined behaviour.
+pub fn take_password_env() {
+    if let Ok(password) = std::env::var(crate::PASSWORD_ENV) {
+        std::env::remove_var(crate::PASSWORD_ENV);
+        *PASSWORD_FROM_ENV.lock().unwrap() = Some(Zeroizing::new(password));
+    }
+}
+
+/// The master password given for scripts, from `password_file` or `PASSMAN_PASSWORD`
+fn scripted_master_password(config: &Config) -> Result<Option<Zeroizing<String>>> {
+    if let Some(path) = &config.password_file {
+        return read_password_file(path).map(Some);
+    }
+
+    Ok(PASSWORD_FROM_ENV.lock().unwrap().take())
+}
+
+/// Read a master password from the first line of `path`, trimmed
+///
+/// On Unix the file is refused if other users can read it.
+pub fn read_password_file(path: &Path) -> Result<Zeroizing<String>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o004 != 0 {
+            return Err(Error::InvalidInput(format!(
+                "Refusing to read the master password from {}: it is world-readable; run `chmod 600` on it",
+                path.display()
+            )));
+        }
+    }
+
+    let contents = Zeroizing::new(std::fs::read_to_string(path)?);
+    Ok(Zeroizing::new(contents.lines().next().unwrap_or_default().trim().to_string()))
+}
+
+/// Get the master password from the configured agent, falling back to a prompt
+fn read_master_password(config: &Config) -> Result<Zeroizing<String>> {
+    #[cfg(all(feature = "agent", unix))]
+    if let Some(socket) = &config.agent_socket {
+        let vault = config.database_path.to_string_lossy();
+        match crate::agent::request_master_password(socket, &vault) {
+            Ok(Some(password)) => return Ok(password),
+            Ok(None) => log::debug!("Agent has no password for {}", vault),
+            Err(e) => log::warn!("Failed to query agent at {}: {}", socket.display(), e),
+        }
+    }
+    #[cfg(not(all(feature = "agent", unix)))]
+    if config.agent_socket.is_some() {
+        log::warn!("agent_socket is set but this build lacks agent support");
+    }
+
+    Ok(Zeroizing::new(read_password("Master password: ")?))
+}
+
+/// Open the vault at `path`, failing if it hasn't been initialized
+pub fn open_initialized(path: &Path, password: &str) -> Result<PasswordRepository> {
+    let repo = PasswordRepository::open(path, password)?;
+    if !repo.is_initialized()? {
+        return Err(Error::VaultNotInitialized);
+    }
+    Ok(repo)
+}
+
+/// Open the vault at `path` read-only, failing if it hasn't been initialized
+pub fn open_initialized_readonly(path: &Path, password: &str) -> Result<PasswordRepository> {
+    let repo = PasswordRepository::open_readonly(path, password)?;
+    if !repo.is_initialized()? {
+        return Err(Error::VaultNotInitialized);
+    }
+    Ok(repo)
+}
+
+/// Create and seal a vault at `path` with the given master password
+///
+/// Fails with `Error::VaultAlreadyExists` if the vault is already initialized,
+/// unless `force` is set, in which case the old file is replaced. The new
+/// database file is encrypted under the master password.
+pub fn init_vault(path: &Path, password: &str, force: bool) -> Result<()> {
+    init_vault_with(path, password, force, &VaultOptions::default())
+}
+
+/// Cipher and key derivation settings chosen when a vault is created
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VaultOptions {
+    /// Cipher entries are encrypted with
+    pub cipher: Cipher,
+    /// Argon2 parameters the key is derived with
+    pub kdf: Argon2Params,
+}
+
+/// Like `init_vault`, creating the vault with the given `options`
+pub fn init_vault_with(path: &Path, password: &str, force: bool, options: &VaultOptions) -> Result<()> {
+    options.kdf.validate()?;
+    // Check before opening: opening runs migrations and would mutate the file
+    PasswordRepository::inspect(path)?.check_initializable(force)?;
+
+    // Start from a fresh file so it is created encrypted
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let repo = PasswordRepository::open(path, password)?;
+    initialize_with_params(&repo, password, &options.kdf)?;
+    repo.set_cipher(options.cipher)
+}
+
+/// Encrypt an unencrypted vault file in place after checking the master password
+pub fn encrypt_vault_file(path: &Path, password: &str) -> Result<()> {
+    let repo = open_initialized(path, password)?;
+    unlock_with_password(&repo, password)?;
+    drop(repo);
+
+    PasswordRepository::encrypt_file(path, password)
+}
+
+/// Initialize a vault whose master password is `password`
+pub fn initialize_with_password(repo: &PasswordRepository, password: &str) -> Result<()> {
+    initialize_with_params(repo, password, &Argon2Params::default())
+}
+
+/// Initialize a vault whose key is derived from `password` with the given Argon2 parameters
+pub fn initialize_with_params(repo: &PasswordRepository, password: &str, kdf: &Argon2Params) -> Result<()> {
+    let password_manager = PasswordManager::with_params(*kdf)?;
+    let (password_hash, _) = password_manager.hash_password(password)?;
+    let salt = password_manager.generate_salt()?;
+    let key = Zeroizing::new(password_manager.derive_key(password, &salt)?);
+    repo.in_transaction(|| {
+        repo.initialize_vault(salt, password_hash.into_bytes())?;
+        repo.set_key_check(&crate::crypto::key_check(&key))?;
+        repo.set_kdf_params(kdf)
+    })
+}
+
+/// Rotate the vault salt and re-encrypt everything under the re-derived key
+///
+/// The master password stays the same. Returns the new encryption key.
+pub fn rehash_master(repo: &PasswordRepository, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let old_key = Zeroizing::new(unlock_with_password(repo, password)?);
+    rekey_master(repo, &old_key, password, None).map(Zeroizing::new)
+}
+
+/// Change the master password, re-encrypting everything under the new key
+///
+/// The vault file itself is re-keyed to the new password in the same
+/// transaction. Returns the new encryption key.
+pub fn change_master_password(repo: &PasswordRepository, current: &str, new: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let old_key = Zeroizing::new(unlock_with_password(repo, current)?);
+
+    if let Some(hash) = repo.get_duress_hash()? {
+        if repo.password_manager()?.verify_password(new, &hash)? {
+            return Err(Error::InvalidInput(
+                "The new master password must differ from the duress password".to_string(),
+            ));
+        }
+    }
+
+    rekey_master(repo, &old_key, new, Some(new)).map(Zeroizing::new)
+}
+
+/// Derive a fresh salt, hash and key for `password` and re-encrypt the vault under them
+fn rekey_master(repo: &PasswordRepository, old_key: &[u8], password: &str, file_password: Option<&str>) -> Result<Vec<u8>> {
+    let password_manager = repo.password_manager()?;
+    let salt = password_manager.generate_salt()?;
+    let (password_hash, _) = password_manager.hash_password(password)?;
+    let new_key = password_manager.derive_key(password, &salt)?;
+
+    repo.rekey(old_key, &new_key, salt, password_hash.into_bytes(), file_password)?;
+    Ok(new_key)
+}
+
+/// Unlock with the master password, or open the decoy vault for the duress password
+///
+/// Both hashes are always checked so the two outcomes take the same time, and
+/// a duress unlock returns exactly what a normal unlock would.
+pub fn unlock_or_decoy(repo: PasswordRepository, password: &str, decoy_path: &Path) -> Result<(PasswordRepository, Vec<u8>)> {
+    let duress_hash = repo.get_duress_hash()?;
+    let is_duress = match &duress_hash {
+        Some(hash) => repo.password_manager()?.verify_password(password, hash)?,
+        None => false,
+    };
+
+    match unlock_with_password(&repo, password) {
+        Err(Error::Authentication(_)) if is_duress => {
+            let decoy = open_decoy(decoy_path, password)?;
+            let key = unlock_with_password(&decoy, password)?;
+            Ok((decoy, key))
+        }
+        result => result.map(|key| (repo, key)),
+    }
+}
+
+/// Unlock the decoy vault with `password` if it exists and the password opens it
+fn unlock_existing_decoy(path: &Path, password: &str) -> Result<Option<(PasswordRepository, Vec<u8>)>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let decoy = match open_initialized(path, password) {
+        Ok(decoy) => decoy,
+        Err(Error::Authentication(_)) | Err(Error::VaultNotInitialized) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    match unlock_with_password(&decoy, password) {
+        Ok(key) => Ok(Some((decoy, key))),
+        Err(Error::Authentication(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Open the decoy vault, creating an empty one for `password` if it's missing
+fn open_decoy(path: &Path, password: &str) -> Result<PasswordRepository> {
+    let decoy = PasswordRepository::open(path, password)?;
+    if !decoy.is_initialized()? {
+        initialize_with_password(&decoy, password)?;
+    }
+    Ok(decoy)
+}
+
+/// Set the duress password and create a fresh decoy vault for it
+pub fn set_duress_password(repo: &PasswordRepository, password: &str, decoy_path: &Path) -> Result<()> {
+    let metadata = repo.get_vault_metadata()?;
+    let master_hash = std::str::from_utf8(&metadata.password_hash)
+        .map_err(|_| Error::Crypto("Stored password hash is not valid UTF-8".to_string()))?;
+
+    let password_manager = repo.password_manager()?;
+    if password_manager.verify_password(password, master_hash)? {
+        return Err(Error::InvalidInput("Duress password must differ from the master password".to_string()));
+    }
+
+    if decoy_path.exists() {
+        std::fs::remove_file(decoy_path)?;
+    }
+    open_decoy(decoy_path, password)?;
+
+    let (duress_hash, _) = password_manager.hash_password(password)?;
+    repo.set_duress_hash(Some(&duress_hash))
+}
+
+/// Verify the master password against the vault and derive the encryption key
+///
+/// The key is checked against the vault's key check tag, so a key that
+/// verifies but can't decrypt fails with `Error::KeyMismatch` up front.
+/// Vaults from before the tag are checked against a live entry instead, and
+/// get the tag once that entry decrypts.
+pub fn unlock_with_password(repo: &PasswordRepository, password: &str) -> Result<Vec<u8>> {
+    let metadata = repo.get_vault_metadata()?;
+    metadata.check_format()?;
+    let password_hash = std::str::from_utf8(&metadata.password_hash)
+        .map_err(|_| Error::Crypto("Stored password hash is not valid UTF-8".to_string()))?;
+
+    let password_manager = repo.password_manager()?;
+    if !password_manager.verify_password(password, password_hash)? {
+        return Err(Error::Authentication("Invalid master password".to_string()));
+    }
+
+    let key = password_manager.derive_key(password, &metadata.salt)?;
+    let mut store_key_check = false;
+    match repo.get_key_check()? {
+        Some(tag) if !crate::crypto::verify_key_check(&key, &tag) => return Err(Error::KeyMismatch),
+        Some(_) => {}
+        None => {
+            let decrypts = match repo.sample_ciphertext()? {
+                Some(ciphertext) => repo.encryption_manager()?.decrypt(&key, &ciphertext).map(Zeroizing::new).is_ok(),
+                None => true,
+            };
+            // One corrupt entry mustn't lock the user out of the others
+            if !decrypts {
+                eprintln!("Warning: an entry failed to decrypt; run `passman list --verify` to find damaged entries");
+            }
+            store_key_check = decrypts;
+        }
+    }
+
+    if !repo.is_readonly() {
+        if store_key_check {
+            repo.set_key_check(&crate::crypto::key_check(&key))?;
+        }
+        repo.update_last_access()?;
+        // New ciphertexts carry a header that older builds would misread
+        if metadata.format_version < crate::crypto::VAULT_FORMAT_VERSION {
+            repo.set_format_version(crate::crypto::VAULT_FORMAT_VERSION)?;
+        }
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(password: &str) -> PasswordRepository {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        initialize_with_password(&repo, password).unwrap();
+        repo
+    }
+
+    #[test]
+    fn test_wrong_then_right_password_within_attempts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passman.db");
+        let decoy_path = dir.path().join("decoy.db");
+        initialize_with_password(&PasswordRepository::open(&path, "master").unwrap(), "master").unwrap();
+
+        let inputs = |passwords: &'static [&'static str]| {
+            let mut passwords = passwords.iter();
+            move || Ok(Zeroizing::new(passwords.next().expect("too many prompts").to_string()))
+        };
+
+        let (_, key) = unlock_with_retries(open_initialized, &path, &decoy_path, inputs(&["typo", "master"]), 3, &Lockout::disabled()).unwrap();
+        assert_eq!(key.len(), 32);
+
+        let result = unlock_with_retries(open_initialized, &path, &decoy_path, inputs(&["typo", "master"]), 1, &Lockout::disabled());
+        assert!(matches!(result, Err(Error::Authentication(_))));
+
+        let result = unlock_with_retries(open_initialized, &path, &decoy_path, inputs(&["typo", "again", "nope"]), 3, &Lockout::disabled());
+        assert!(matches!(result, Err(Error::Authentication(_))));
+    }
+
+    #[test]
+    fn test_duress_password_opens_decoy() {
+        use crate::database::{PasswordEntry, SecureString};
+
+        let dir = tempfile::tempdir().unwrap();
+        let real_path = dir.path().join("passman.db");
+        let decoy_path = dir.path().join("decoy.db");
+
+        let repo = PasswordRepository::open(&real_path, "master").unwrap();
+        initialize_with_password(&repo, "master").unwrap();
+        let key = unlock_with_password(&repo, "master").unwrap();
+        let entry = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap()).unwrap();
+        set_duress_password(&repo, "duress", &decoy_path).unwrap();
+        assert!(set_duress_password(&repo, "master", &decoy_path).is_err());
+        drop(repo);
+
+        let unlock = |password: &'static str| {
+            let mut asked = false;
+            let input = move || {
+                assert!(!asked, "asked for a password twice");
+                asked = true;
+                Ok(Zeroizing::new(password.to_string()))
+            };
+            unlock_with_retries(open_initialized, &real_path, &decoy_path, input, 1, &Lockout::disabled())
+        };
+
+        let (real, real_key) = unlock("master").unwrap();
+        assert!(real.title_exists("Bank").unwrap());
+
+        let (decoy, decoy_key) = unlock("duress").unwrap();
+        assert!(!decoy.title_exists("Bank").unwrap());
+        assert_eq!(decoy.count_entries().unwrap(), 0);
+        assert_ne!(decoy_key, real_key);
+
+        assert!(matches!(unlock("guess"), Err(Error::Authentication(_))));
+    }
+
+    #[test]
+    fn test_duress_password_on_unencrypted_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let real_path = dir.path().join("passman.db");
+        let decoy_path = dir.path().join("decoy.db");
+
+        let repo = PasswordRepository::new(&real_path).unwrap();
+        initialize_with_password(&repo, "master").unwrap();
+        set_duress_password(&repo, "duress", &decoy_path).unwrap();
+
+        let (decoy, _) = unlock_or_decoy(PasswordRepository::new(&real_path).unwrap(), "duress", &decoy_path).unwrap();
+        assert_eq!(decoy.count_entries().unwrap(), 0);
+        let wrong = unlock_or_decoy(PasswordRepository::new(&real_path).unwrap(), "guess", &decoy_path);
+        assert!(matches!(wrong, Err(Error::Authentication(_))));
+    }
+
+    #[test]
+    fn test_rehash_master_keeps_password_and_changes_salt() {
+        use crate::database::{PasswordEntry, SecureString};
+
+        let repo = vault("master");
+        let key = unlock_with_password(&repo, "master").unwrap();
+        let entry = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap()).unwrap();
+        let old_salt = repo.get_vault_metadata().unwrap().salt;
+
+        assert!(matches!(rehash_master(&repo, "wrong"), Err(Error::Authentication(_))));
+        let new_key = rehash_master(&repo, "master").unwrap();
+
+        assert_ne!(repo.get_vault_metadata().unwrap().salt, old_salt);
+        assert_ne!(*new_key, key);
+        assert_eq!(unlock_with_password(&repo, "master").unwrap(), *new_key);
+        assert_eq!(repo.get_entry_decrypted("Bank", &new_key).unwrap().password.as_str(), "hunter2");
+        assert!(repo.get_entry_decrypted("Bank", &key).is_err());
+    }
+
+    #[test]
+    fn test_change_master_password_reencrypts_entries() {
+        use crate::database::{PasswordEntry, SecureString};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passman.db");
+        init_vault(&path, "old master", false).unwrap();
+
+        let repo = open_initialized(&path, "old master").unwrap();
+        let key = unlock_with_password(&repo, "old master").unwrap();
+        let entry = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap()).unwrap();
+
+        assert!(matches!(
+            change_master_password(&repo, "wrong", "new master"),
+            Err(Error::Authentication(_))
+        ));
+        change_master_password(&repo, "old master", "new master").unwrap();
+        drop(repo);
+
+        assert!(matches!(open_initialized(&path, "old master"), Err(Error::Authentication(_))));
+        let repo = open_initialized(&path, "new master").unwrap();
+        let new_key = unlock_with_password(&repo, "new master").unwrap();
+        assert_ne!(new_key, key);
+        assert_eq!(repo.get_entry_decrypted("Bank", &new_key).unwrap().password.as_str(), "hunter2");
+        assert!(repo.get_entry_decrypted("Bank", &key).is_err());
+    }
+
+    #[test]
+    fn test_unlock_detects_key_mismatch() {
+        use crate::database::{PasswordEntry, SecureString};
+
+        let repo = vault("master");
+        let key = unlock_with_password(&repo, "master").unwrap();
+        let entry = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap()).unwrap();
+
+        // Swap the KDF salt without re-encrypting: the hash still verifies
+        let metadata = repo.get_vault_metadata().unwrap();
+        repo.rekey(&key, &key, vec![0u8; 32], metadata.password_hash, None).unwrap();
+
+        assert!(matches!(unlock_with_password(&repo, "master"), Err(Error::KeyMismatch)));
+        assert!(matches!(unlock_with_password(&repo, "wrong"), Err(Error::Authentication(_))));
+    }
+
+    #[test]
+    fn test_corrupt_entry_does_not_block_unlock() {
+        use crate::database::{PasswordEntry, SecureString};
+
+        let repo = vault("master");
+        let entry = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, b"not a ciphertext").unwrap();
+        assert_eq!(unlock_with_password(&repo, "master").unwrap().len(), 32);
+
+        // A vault from before the key check falls back to the entry, with a warning
+        let legacy = PasswordRepository::new(":memory:").unwrap();
+        let password_manager = legacy.password_manager().unwrap();
+        let (hash, _) = password_manager.hash_password("master").unwrap();
+        legacy.initialize_vault(password_manager.generate_salt().unwrap(), hash.into_bytes()).unwrap();
+        legacy.add_entry(&entry, b"not a ciphertext").unwrap();
+        assert!(unlock_with_password(&legacy, "master").is_ok());
+        assert!(legacy.get_key_check().unwrap().is_none());
+
+        // Once the entry is gone, the next unlock stores the key check
+        legacy.delete_entry(&entry.id).unwrap();
+        let key = unlock_with_password(&legacy, "master").unwrap();
+        assert!(crate::crypto::verify_key_check(&key, &legacy.get_key_check().unwrap().unwrap()));
+    }
+
+    #[test]
+    fn test_unlock_rejects_unsupported_format() {
+        let repo = vault("correct horse");
+        assert_eq!(unlock_with_password(&repo, "correct horse").unwrap().len(), 32);
+
+        repo.set_format_version(crate::crypto::VAULT_FORMAT_VERSION + 1).unwrap();
+        let err = unlock_with_password(&repo, "correct horse").unwrap_err();
+        assert!(matches!(err, Error::IncompatibleVault(_)));
+        assert!(err.to_string().contains("please upgrade"));
+
+        repo.set_format_version(0).unwrap();
+        let err = unlock_with_password(&repo, "correct horse").unwrap_err();
+        assert!(err.to_string().contains("please migrate"));
+
+        // Format 1 vaults are still readable and are upgraded on unlock
+        repo.set_format_version(crate::crypto::OLDEST_VAULT_FORMAT_VERSION).unwrap();
+        unlock_with_password(&repo, "correct horse").unwrap();
+        assert_eq!(repo.get_vault_metadata().unwrap().format_version, crate::crypto::VAULT_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn test_session_expires_after_timeout() {
+        use crate::utils::idle::ManualClock;
+
+        let clock = ManualClock::new();
+        let key = LockedKey::new(vec![7u8; 32], false);
+        let mut session = Some(Session::new(vault("master"), key, Duration::from_secs(15 * 60), Arc::new(clock.clone())));
+
+        clock.advance(Duration::from_secs(14 * 60));
+        assert!(!expire_session(&mut session));
+        assert_eq!(&session.as_ref().unwrap().key()[..], &[7u8; 32][..]);
+
+        clock.advance(Duration::from_secs(2 * 60));
+        assert!(expire_session(&mut session));
+        assert!(session.is_none());
+        assert!(!expire_session(&mut session));
+    }
+
+    #[test]
+    fn test_wrong_passwords_lock_an_encrypted_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passman.db");
+        let decoy_path = dir.path().join("decoy.db");
+        init_vault(&path, "master", false).unwrap();
+
+        let security = SecurityConfig { max_login_attempts: 3, lockout_duration: 5, ..SecurityConfig::default() };
+        let lockout = Lockout::for_vault(&security, &path);
+        let unlock = |password: &'static str| {
+            let input = move || Ok(Zeroizing::new(password.to_string()));
+            unlock_with_retries(open_initialized, &path, &decoy_path, input, 1, &lockout).map(|(_, key)| key)
+        };
+        let is_locked_out = |result: &Result<Vec<u8>>| matches!(result, Err(Error::Authentication(m)) if m.starts_with(LOCKED_OUT));
+
+        // The wrong key is already refused by the encrypted file, and still counts
+        assert!(matches!(open_initialized(&path, "typo"), Err(Error::Authentication(_))));
+        assert!(unlock("typo").is_err());
+        assert!(unlock("master").is_ok());
+        assert!(!Lockout::state_path(&path).exists());
+
+        // The successful unlock reset the count, so two more failures don't lock
+        for _ in 0..2 {
+            let result = unlock("typo");
+            assert!(matches!(result, Err(Error::Authentication(_))) && !is_locked_out(&result));
+        }
+        assert!(lockout.locked_until(Utc::now()).unwrap().is_none());
+
+        assert!(is_locked_out(&unlock("typo")));
+        // Even the right password is refused during the lockout, through every entry point
+        assert!(is_locked_out(&unlock("master")));
+        assert!(is_locked_out(&open_with_lockout(open_initialized, &path, "master", &lockout).map(|(_, key)| key)));
+
+        let later = Utc::now() + chrono::Duration::minutes(6);
+        assert!(lockout.locked_until(later).unwrap().is_none());
+        assert!(Lockout::disabled().locked_until(Utc::now()).unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_password_file_first_line_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("master");
+        std::fs::write(&path, "  correct horse \nsecond line\n").unwrap();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(read_password_file(&path).unwrap().as_str(), "correct horse");
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(read_password_file(&path), Err(Error::InvalidInput(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unlock_from_config_decrypts_real_entries() {
+        use crate::database::{PasswordEntry, SecureString};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passman.db");
+        init_vault(&path, "master", false).unwrap();
+        let repo = open_initialized(&path, "master").unwrap();
+        let key = unlock_with_password(&repo, "master").unwrap();
+        let entry = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap()).unwrap();
+        drop(repo);
+
+        let password_file = dir.path().join("master");
+        std::fs::write(&password_file, "master\n").unwrap();
+        std::fs::set_permissions(&password_file, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let config = Config { database_path: path, password_file: Some(password_file.clone()), ..Config::default() };
+
+        let (repo, key) = unlock(&config).unwrap();
+        assert_eq!(repo.get_entry_decrypted("Bank", &key).unwrap().password.as_str(), "hunter2");
+        let (repo, key) = unlock_readonly(&config).unwrap();
+        assert_eq!(repo.list_entries().unwrap()[0].title, "Bank");
+        assert_eq!(repo.get_entry_decrypted("Bank", &key).unwrap().password.as_str(), "hunter2");
+
+        std::fs::write(&password_file, "wrong\n").unwrap();
+        assert!(matches!(unlock(&config), Err(Error::Authentication(_))));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_password_env_is_taken_once() {
+        std::env::set_var(crate::PASSWORD_ENV, "correct horse");
+        take_password_env();
+        assert!(std::env::var_os(crate::PASSWORD_ENV).is_none());
+
+        let config = Config::default();
+        assert_eq!(scripted_master_password(&config).unwrap().unwrap().as_str(), "correct horse");
+        assert!(scripted_master_password(&config).unwrap().is_none());
+    }
+}
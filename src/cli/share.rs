@@ -0,0 +1,97 @@
+use crate::crypto::totp::otpauth_uri;
+use crate::database::PasswordEntry;
+use crate::Result;
+
+/// Render everything needed to move a credential to another device
+///
+/// The password is only included when `reveal_password` is set, and the TOTP
+/// provisioning URI only when the entry has a secret. Builds with the `qr`
+/// feature follow the URI with a QR code an authenticator app can scan.
+pub fn format_share(entry: &PasswordEntry, reveal_password: bool, totp_secret: Option<&str>) -> Result<String> {
+    let mut lines = vec![
+        format!("Title:    {}", entry.title),
+        format!("Username: {}", entry.username),
+    ];
+
+    if let Some(url) = &entry.url {
+        lines.push(format!("URL:      {}", url));
+    }
+
+    if reveal_password {
+        lines.push(format!("Password: {}", entry.password.as_str()));
+    } else {
+        lines.push("Password: (hidden)".to_string());
+    }
+
+    if let Some(secret) = totp_secret {
+        let uri = otpauth_uri(&entry.title, &entry.username, secret);
+        lines.push(format!("TOTP:     {}", uri));
+        #[cfg(feature = "qr")]
+        lines.push(totp_qr(&uri)?);
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Render `uri` as a QR code of half-height block characters
+#[cfg(feature = "qr")]
+pub fn totp_qr(uri: &str) -> Result<String> {
+    use qrcode::render::unicode::Dense1x2;
+    use qrcode::QrCode;
+
+    let code = QrCode::new(uri.as_bytes())
+        .map_err(|e| crate::Error::InvalidInput(format!("Can't encode the TOTP URI as a QR code: {}", e)))?;
+    // Inverted, so the code keeps its light border on a dark terminal
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SecureString;
+
+    fn entry() -> PasswordEntry {
+        PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::from("hunter2"),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_share_includes_totp_uri_when_secret_present() {
+        let output = format_share(&entry(), true, Some("JBSWY3DPEHPK3PXP")).unwrap();
+        assert!(output.contains("Password: hunter2"));
+        assert!(output.contains("otpauth://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_share_omits_totp_and_hidden_password() {
+        let output = format_share(&entry(), false, None).unwrap();
+        assert!(!output.contains("otpauth://"));
+        assert!(!output.contains("hunter2"));
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_share_follows_totp_uri_with_qr_code() {
+        let uri = otpauth_uri("GitHub", "octocat", "JBSWY3DPEHPK3PXP");
+        let qr = totp_qr(&uri).unwrap();
+        let width = qrcode::QrCode::new(uri.as_bytes()).unwrap().width();
+
+        // Two modules per row, plus a four-module quiet zone on every side
+        let rows: Vec<&str> = qr.lines().collect();
+        assert_eq!(rows.len(), (width + 9) / 2);
+        assert!(rows.iter().all(|row| row.chars().count() == width + 8));
+        assert_ne!(qr, totp_qr(&otpauth_uri("GitHub", "octocat", "GEZDGNBVGY3TQOJQ")).unwrap());
+
+        let output = format_share(&entry(), false, Some("JBSWY3DPEHPK3PXP")).unwrap();
+        assert!(output.ends_with(&qr));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::database::{PasswordEntry, SearchPattern};
+use crate::utils::csv;
+use crate::Result;
+
+/// Shown in place of a password that isn't revealed
+pub const PASSWORD_MASK: &str = "••••••";
+
+/// Render a decrypted entry for `passman get`
+///
+/// The password is masked unless `show_password` is set.
+pub fn format_entry(entry: &PasswordEntry, show_password: bool) -> String {
+    let password = if show_password { entry.password.as_str() } else { PASSWORD_MASK };
+
+    let mut lines = vec![
+        format!("Title:    {}", entry.title),
+        format!("Username: {}", entry.username),
+        format!("Password: {}", password),
+    ];
+
+    if let Some(url) = &entry.url {
+        lines.push(format!("URL:      {}", url));
+    }
+    if let Some(notes) = &entry.notes {
+        lines.push(format!("Notes:    {}", notes));
+    }
+    if !entry.tags.is_empty() {
+        lines.push(format!("Tags:     {}", entry.tags.join(", ")));
+    }
+    lines.push(format!("Updated:  {}", entry.updated_at.format("%Y-%m-%d %H:%M:%S UTC")));
+
+    lines.join("\n")
+}
+
+/// Output formats for `passman list`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ListFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// Render entries for `passman list`; passwords are never included
+pub fn format_list(entries: &[PasswordEntry], format: ListFormat, short_id_length: usize) -> Result<String> {
+    format_list_highlighted(entries, format, short_id_length, None)
+}
+
+/// Render entries like `format_list`, highlighting matches of `pattern` in table titles
+pub fn format_list_highlighted(
+    entries: &[PasswordEntry],
+    format: ListFormat,
+    short_id_length: usize,
+    pattern: Option<&SearchPattern>,
+) -> Result<String> {
+    match format {
+        ListFormat::Table => Ok(format_table(entries, short_id_length, pattern)),
+        ListFormat::Json => Ok(serde_json::to_string_pretty(entries)?),
+        ListFormat::Csv => Ok(format_csv(entries)),
+    }
+}
+
+fn list_row(entry: &PasswordEntry) -> [String; 4] {
+    [
+        entry.title.clone(),
+        entry.username.clone(),
+        entry.url.clone().unwrap_or_default(),
+        entry.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+    ]
+}
+
+fn format_table(entries: &[PasswordEntry], short_id_length: usize, pattern: Option<&SearchPattern>) -> String {
+    if entries.is_empty() {
+        return "No entries".to_string();
+    }
+
+    let header = ["ID", "TITLE", "USERNAME", "URL", "UPDATED"].map(String::from);
+    let rows: Vec<[String; 5]> = std::iter::once(header)
+        .chain(entries.iter().map(|entry| {
+            let [title, username, url, updated] = list_row(entry);
+            [entry.short_id(short_id_length), title, username, url, updated]
+        }))
+        .collect();
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .enumerate()
+                .map(|(column, (cell, width))| {
+                    let padded = format!("{:<width$}", cell, width = width);
+                    match pattern {
+                        // Highlight after padding so escape codes don't skew the widths
+                        Some(pattern) if i > 0 && column == 1 => highlight(&padded, pattern),
+                        _ => padded,
+                    }
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wrap every match of `pattern` in `text` in terminal highlighting
+pub fn highlight(text: &str, pattern: &SearchPattern) -> String {
+    use colored::Colorize;
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for range in pattern.find_ranges(text) {
+        output.push_str(&text[last..range.start]);
+        output.push_str(&text[range.clone()].yellow().bold().to_string());
+        last = range.end;
+    }
+    output.push_str(&text[last..]);
+    output
+}
+
+fn format_csv(entries: &[PasswordEntry]) -> String {
+    let mut output = csv::format_record(&["id", "title", "username", "url", "updated_at"]);
+    for entry in entries {
+        let [title, username, url, _] = list_row(entry);
+        output.push_str(&csv::format_record(&[
+            entry.id.to_string(),
+            title,
+            username,
+            url,
+            entry.updated_at.to_rfc3339(),
+        ]));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SecureString;
+
+    #[test]
+    fn test_password_masked_unless_shown() {
+        let entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::from("hunter2"),
+            Some("https://github.com".to_string()),
+            None,
+        );
+
+        let masked = format_entry(&entry, false);
+        assert!(masked.contains(PASSWORD_MASK));
+        assert!(!masked.contains("hunter2"));
+        assert!(!masked.contains("Notes:"));
+
+        assert!(format_entry(&entry, true).contains("Password: hunter2"));
+    }
+
+    #[test]
+    fn test_list_formats() {
+        let entry = PasswordEntry::new(
+            "Bank, personal".to_string(),
+            "alice".to_string(),
+            SecureString::from("hunter2"),
+            None,
+            None,
+        );
+
+        let table = format_list(std::slice::from_ref(&entry), ListFormat::Table, 8).unwrap();
+        assert!(table.starts_with("ID"));
+        assert!(table.contains("Bank, personal  alice"));
+
+        let csv = format_list(std::slice::from_ref(&entry), ListFormat::Csv, 8).unwrap();
+        assert!(csv.starts_with("id,title,username,url,updated_at\r\n"));
+        assert!(csv.contains("\"Bank, personal\",alice,,"));
+
+        let json = format_list(std::slice::from_ref(&entry), ListFormat::Json, 8).unwrap();
+        assert!(!json.contains("hunter2"));
+
+        assert_eq!(format_list(&[], ListFormat::Table, 8).unwrap(), "No entries");
+        assert_eq!(format_list(&[], ListFormat::Json, 8).unwrap(), "[]");
+        assert_eq!(format_list(&[], ListFormat::Csv, 8).unwrap(), "id,title,username,url,updated_at\r\n");
+    }
+
+    #[test]
+    fn test_highlight_marks_matches_only() {
+        colored::control::set_override(true);
+        let pattern = SearchPattern::Literal("hub".to_string());
+        let highlighted = highlight("GitHub hub", &pattern);
+        assert_eq!(highlighted.matches("\u{1b}[").count(), 4);
+        assert!(highlighted.starts_with("Git\u{1b}["));
+        assert_eq!(highlight("GitLab", &pattern), "GitLab");
+    }
+}
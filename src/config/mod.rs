@@ -1,4 +1,8 @@
-use crate::{Error, Result, APP_NAME, CONFIG_FILE_NAME, DEFAULT_DB_NAME};
+use crate::crypto::Argon2Params;
+use crate::database::templates::EntryTemplate;
+use crate::utils::GeneratorConfig;
+use crate::{Error, Result, APP_NAME, CONFIG_FILE_NAME, DEFAULT_DB_NAME, DEFAULT_PROFILE, NAMESPACE_ENV};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -9,6 +13,45 @@ pub struct Config {
     pub database_path: PathBuf,
     /// Clipboard timeout in seconds (0 = no timeout)
     pub clipboard_timeout: u64,
+    /// Largest payload in bytes that may be copied to the clipboard
+    #[serde(default = "default_clipboard_max_bytes")]
+    pub clipboard_max_bytes: usize,
+    /// Copy through the terminal with OSC 52 escape sequences
+    #[serde(default)]
+    pub clipboard_osc52: bool,
+    /// Split OSC 52 output into chunks of this many bytes (0 = single sequence)
+    #[serde(default)]
+    pub osc52_chunk_size: usize,
+    /// Editor command for multi-line fields (falls back to $VISUAL / $EDITOR)
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Template for `copy-login`; supports {title}, {username}, {password} and {url}
+    #[serde(default = "default_login_template")]
+    pub login_template: String,
+    /// Show timestamps in the system's local timezone instead of UTC
+    #[serde(default)]
+    pub display_local_time: bool,
+    /// Vault opened when the duress password is entered (default: `passman-cache.db` next to the vault)
+    #[serde(default)]
+    pub decoy_database_path: Option<PathBuf>,
+    /// Unix socket of a master-password agent to ask before prompting (needs the `agent` feature)
+    #[serde(default)]
+    pub agent_socket: Option<PathBuf>,
+    /// File whose first line is the master password, for unattended runs (must not be world-readable)
+    #[serde(default)]
+    pub password_file: Option<PathBuf>,
+    /// Recreate the vault's directory if it has gone missing instead of failing
+    #[serde(default = "default_create_missing_dir")]
+    pub create_missing_dir: bool,
+    /// Templates for `add --template`; a template here replaces a built-in of the same name
+    #[serde(default)]
+    pub entry_templates: Vec<EntryTemplate>,
+    /// Number of ID characters shown when listing entries
+    #[serde(default = "default_short_id_length")]
+    pub short_id_length: usize,
+    /// Previous passwords kept per entry for `history` (0 keeps none)
+    #[serde(default = "default_max_history_per_entry")]
+    pub max_history_per_entry: usize,
     /// Password generation settings
     pub password_generation: PasswordGenerationConfig,
     /// Security settings
@@ -41,26 +84,193 @@ pub struct SecurityConfig {
     pub max_login_attempts: u32,
     /// Lockout duration in minutes
     pub lockout_duration: u64,
+    /// Re-prompt for a mistyped master password, up to `max_login_attempts` times
+    #[serde(default = "default_retry_wrong_password")]
+    pub retry_wrong_password: bool,
+    /// Lock the derived key into RAM so it cannot be swapped to disk
+    #[serde(default)]
+    pub lock_memory: bool,
+    /// Most secrets one command may copy or reveal without an extra confirmation
+    #[serde(default = "default_max_secrets_per_operation")]
+    pub max_secrets_per_operation: usize,
+    /// Argon2 memory cost in MiB for new vaults
+    #[serde(default = "default_kdf_memory_mib")]
+    pub kdf_memory_mib: u32,
+    /// Argon2 iterations for new vaults
+    #[serde(default = "default_kdf_iterations")]
+    pub kdf_iterations: u32,
+    /// Argon2 parallelism for new vaults
+    #[serde(default = "default_kdf_parallelism")]
+    pub kdf_parallelism: u32,
+    /// Entropy in bits below which `audit weak` flags a password
+    #[serde(default = "default_weak_password_min_bits")]
+    pub weak_password_min_bits: f64,
+}
+
+/// Directory name used under the config directory
+///
+/// Defaults to the application name and can be overridden at runtime or build
+/// time through `PASSMAN_NAMESPACE`, so parallel installs don't share a vault.
+pub fn app_namespace() -> String {
+    let runtime = std::env::var(NAMESPACE_ENV).ok();
+    let build_time = option_env!("PASSMAN_NAMESPACE").map(str::to_string);
+
+    for namespace in [runtime, build_time].into_iter().flatten() {
+        if is_valid_namespace(&namespace) {
+            return namespace;
+        }
+        log::warn!("Ignoring invalid namespace '{}'", namespace);
+    }
+
+    APP_NAME.to_string()
+}
+
+fn is_valid_namespace(namespace: &str) -> bool {
+    !namespace.is_empty()
+        && namespace != "."
+        && namespace != ".."
+        && !namespace.contains(['/', '\\'])
+}
+
+/// Directory under the namespace directory holding the named profiles
+const PROFILES_DIR: &str = "profiles";
+
+/// Check that a profile name is usable as a directory name
+pub fn validate_profile_name(profile: &str) -> Result<()> {
+    if profile.is_empty() || !profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::InvalidInput(format!(
+            "Invalid profile name '{}'; use letters, digits, '-' and '_'",
+            profile
+        )));
+    }
+    Ok(())
+}
+
+/// Directory holding a profile's config and database under `base`
+///
+/// The default profile keeps the original layout directly in the namespace
+/// directory, so vaults created before profiles existed stay where they are.
+fn profile_path(mut base: PathBuf, profile: &str) -> PathBuf {
+    base.push(app_namespace());
+    if profile != DEFAULT_PROFILE {
+        base.push(PROFILES_DIR);
+        base.push(profile);
+    }
+    base
+}
+
+fn config_base_dir() -> Result<PathBuf> {
+    dirs::config_dir().or_else(dirs::home_dir).ok_or_else(|| {
+        Error::Config(config::ConfigError::Message(
+            "Cannot determine config directory".to_string(),
+        ))
+    })
+}
+
+/// Directory holding a profile's config and database
+pub fn profile_dir(profile: &str) -> Result<PathBuf> {
+    validate_profile_name(profile)?;
+    Ok(profile_path(config_base_dir()?, profile))
+}
+
+/// Names of all profiles, the default one first and the rest sorted
+pub fn list_profiles() -> Result<Vec<String>> {
+    let mut profiles = Vec::new();
+    let dir = profile_path(config_base_dir()?, DEFAULT_PROFILE).join(PROFILES_DIR);
+    if dir.is_dir() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir() && name != DEFAULT_PROFILE && validate_profile_name(&name).is_ok() {
+                profiles.push(name);
+            }
+        }
+    }
+    profiles.sort();
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(profiles)
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let mut database_path = dirs::config_dir()
-            .or_else(|| dirs::home_dir())
-            .unwrap_or_else(|| PathBuf::from("."));
+        Self::for_profile(DEFAULT_PROFILE)
+    }
+}
 
-        database_path.push(APP_NAME);
-        database_path.push(DEFAULT_DB_NAME);
+impl Config {
+    /// Default settings for a profile, with the database in the profile's directory
+    pub fn for_profile(profile: &str) -> Self {
+        let base = dirs::config_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| PathBuf::from("."));
+        let database_path = profile_path(base, profile).join(DEFAULT_DB_NAME);
 
         Self {
             database_path,
             clipboard_timeout: 30, // 30 seconds
+            clipboard_max_bytes: default_clipboard_max_bytes(),
+            clipboard_osc52: false,
+            osc52_chunk_size: 0,
+            editor: None,
+            login_template: default_login_template(),
+            display_local_time: false,
+            decoy_database_path: None,
+            agent_socket: None,
+            password_file: None,
+            create_missing_dir: default_create_missing_dir(),
+            entry_templates: Vec::new(),
+            short_id_length: default_short_id_length(),
+            max_history_per_entry: default_max_history_per_entry(),
             password_generation: PasswordGenerationConfig::default(),
             security: SecurityConfig::default(),
         }
     }
 }
 
+fn default_clipboard_max_bytes() -> usize {
+    crate::utils::clipboard::DEFAULT_MAX_PAYLOAD
+}
+
+fn default_login_template() -> String {
+    "{username}\t{password}".to_string()
+}
+
+fn default_retry_wrong_password() -> bool {
+    true
+}
+
+fn default_max_secrets_per_operation() -> usize {
+    10
+}
+
+fn default_kdf_memory_mib() -> u32 {
+    Argon2Params::default().m_cost / 1024
+}
+
+fn default_kdf_iterations() -> u32 {
+    Argon2Params::default().t_cost
+}
+
+fn default_kdf_parallelism() -> u32 {
+    Argon2Params::default().p_cost
+}
+
+fn default_weak_password_min_bits() -> f64 {
+    60.0
+}
+
+fn default_create_missing_dir() -> bool {
+    true
+}
+
+fn default_short_id_length() -> usize {
+    8
+}
+
+fn default_max_history_per_entry() -> usize {
+    crate::database::DEFAULT_HISTORY_LIMIT
+}
+
 impl Default for PasswordGenerationConfig {
     fn default() -> Self {
         Self {
@@ -74,36 +284,136 @@ impl Default for PasswordGenerationConfig {
     }
 }
 
+impl PasswordGenerationConfig {
+    /// Check that these settings can generate a password
+    pub fn validate(&self) -> Result<()> {
+        if self.default_length == 0 {
+            return Err(config_error("password_generation.default_length must be at least 1".to_string()));
+        }
+
+        let classes = [
+            self.include_uppercase,
+            self.include_lowercase,
+            self.include_numbers,
+            self.include_symbols,
+        ];
+        let enabled = classes.iter().filter(|&&enabled| enabled).count() as u32;
+        if enabled == 0 {
+            return Err(config_error(
+                "password_generation enables no character classes; set at least one of \
+                 include_uppercase, include_lowercase, include_numbers or include_symbols"
+                    .to_string(),
+            ));
+        }
+        // Every enabled class contributes at least one character
+        if self.default_length < enabled {
+            return Err(config_error(format!(
+                "password_generation.default_length is {}, shorter than the {} enabled character classes",
+                self.default_length, enabled
+            )));
+        }
+
+        if self.include_symbols && self.symbol_set.is_empty() {
+            return Err(config_error(
+                "password_generation.symbol_set is empty but include_symbols is true".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build a generator configuration from these settings
+    pub fn generator_config(&self) -> GeneratorConfig {
+        GeneratorConfig {
+            length: self.default_length,
+            include_uppercase: self.include_uppercase,
+            include_lowercase: self.include_lowercase,
+            include_numbers: self.include_numbers,
+            include_symbols: self.include_symbols,
+            symbol_set: self.symbol_set.clone(),
+            ..Default::default()
+        }
+    }
+}
+
 impl Default for SecurityConfig {
     fn default() -> Self {
         Self {
             session_timeout: 15, // 15 minutes
             max_login_attempts: 3,
             lockout_duration: 5, // 5 minutes
+            retry_wrong_password: default_retry_wrong_password(),
+            lock_memory: false,
+            max_secrets_per_operation: default_max_secrets_per_operation(),
+            kdf_memory_mib: default_kdf_memory_mib(),
+            kdf_iterations: default_kdf_iterations(),
+            kdf_parallelism: default_kdf_parallelism(),
+            weak_password_min_bits: default_weak_password_min_bits(),
+        }
+    }
+}
+
+impl SecurityConfig {
+    /// Argon2 parameters new vaults are created with
+    pub fn kdf_params(&self) -> Argon2Params {
+        Argon2Params {
+            m_cost: self.kdf_memory_mib.saturating_mul(1024),
+            t_cost: self.kdf_iterations,
+            p_cost: self.kdf_parallelism,
         }
     }
 }
 
 impl Config {
-    /// Load configuration from file or create default
-    pub fn load() -> Result<Self> {
-        let config_path = Self::config_file_path()?;
+    /// Load a profile's configuration from file or create its default
+    pub fn load(profile: &str) -> Result<Self> {
+        match Self::read_existing(profile)? {
+            Some(config) => Ok(config),
+            None => {
+                let config = Config::for_profile(profile);
+                config.save(profile)?;
+                Ok(config)
+            }
+        }
+    }
 
+    /// Load a profile's configuration from file, falling back to an in-memory default
+    ///
+    /// Unlike `load`, nothing is written when no config file exists.
+    pub fn load_without_create(profile: &str) -> Result<Self> {
+        Ok(Self::read_existing(profile)?.unwrap_or_else(|| Config::for_profile(profile)))
+    }
+
+    /// Create a new profile with a default config, failing if it already exists
+    pub fn create_profile(profile: &str) -> Result<Self> {
+        let config_path = Self::config_file_path(profile)?;
         if config_path.exists() {
-            let contents = std::fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&contents)
-                .map_err(|e| Error::Config(config::ConfigError::Message(e.to_string())))?;
-            Ok(config)
-        } else {
-            let config = Config::default();
-            config.save()?;
-            Ok(config)
+            return Err(Error::InvalidInput(format!("Profile '{}' already exists", profile)));
         }
+
+        let config = Config::for_profile(profile);
+        config.save(profile)?;
+        Ok(config)
+    }
+
+    fn read_existing(profile: &str) -> Result<Option<Self>> {
+        let config_path = Self::config_file_path(profile)?;
+        if !config_path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(&config_path)?;
+        let config: Config = toml::from_str(&contents)
+            .map_err(|e| Error::Config(config::ConfigError::Message(e.to_string())))?;
+        config.validate().map_err(|e| match e {
+            Error::Config(e) => config_error(format!("{} in {}", e, config_path.display())),
+            e => e,
+        })?;
+        Ok(Some(config))
     }
 
-    /// Save configuration to file
-    pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_file_path()?;
+    /// Save configuration to the profile's config file
+    pub fn save(&self, profile: &str) -> Result<()> {
+        let config_path = Self::config_file_path(profile)?;
 
         // Create config directory if it doesn't exist
         if let Some(parent) = config_path.parent() {
@@ -117,19 +427,14 @@ impl Config {
         Ok(())
     }
 
-    /// Get the path to the configuration file
-    fn config_file_path() -> Result<PathBuf> {
-        let mut config_path = dirs::config_dir()
-            .or_else(|| dirs::home_dir())
-            .ok_or_else(|| {
-                Error::Config(config::ConfigError::Message(
-                    "Cannot determine config directory".to_string(),
-                ))
-            })?;
+    /// Get the path to a profile's configuration file
+    pub fn config_file_path(profile: &str) -> Result<PathBuf> {
+        Ok(profile_dir(profile)?.join(CONFIG_FILE_NAME))
+    }
 
-        config_path.push(APP_NAME);
-        config_path.push(CONFIG_FILE_NAME);
-        Ok(config_path)
+    /// Check for settings that would only fail later, e.g. when generating a password
+    pub fn validate(&self) -> Result<()> {
+        self.password_generation.validate()
     }
 
     /// Get the directory containing the database
@@ -137,6 +442,27 @@ impl Config {
         self.database_path.parent()
     }
 
+    /// Path of the decoy vault opened by the duress password
+    ///
+    /// The default name is deliberately unremarkable.
+    pub fn decoy_path(&self) -> PathBuf {
+        self.decoy_database_path.clone().unwrap_or_else(|| {
+            self.database_dir()
+                .map(|dir| dir.join("passman-cache.db"))
+                .unwrap_or_else(|| PathBuf::from("passman-cache.db"))
+        })
+    }
+
+    /// Default file for a `backup` taken at `at`, next to the vault
+    pub fn backup_path(&self, at: DateTime<Utc>) -> PathBuf {
+        let stem = self.database_path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.database_path.extension() {
+            Some(extension) => format!("{}-backup-{}.{}", stem, at.format("%Y%m%d-%H%M%S"), extension.to_string_lossy()),
+            None => format!("{}-backup-{}", stem, at.format("%Y%m%d-%H%M%S")),
+        };
+        self.database_path.with_file_name(name)
+    }
+
     /// Ensure the database directory exists
     pub fn ensure_database_dir(&self) -> Result<()> {
         if let Some(db_dir) = self.database_dir() {
@@ -144,4 +470,301 @@ impl Config {
         }
         Ok(())
     }
+
+    /// Make sure the database directory exists before the vault is opened
+    ///
+    /// A missing directory is recreated when `create_missing_dir` is set;
+    /// otherwise an error naming the directory is returned.
+    pub fn prepare_database_dir(&self) -> Result<()> {
+        match self.database_dir() {
+            Some(dir) if !dir.as_os_str().is_empty() && !dir.exists() => {
+                if self.create_missing_dir {
+                    self.ensure_database_dir()
+                } else {
+                    Err(Error::Config(config::ConfigError::Message(format!(
+                        "Vault directory {} does not exist; recreate it, update database_path, \
+                         or set create_missing_dir = true",
+                        dir.display()
+                    ))))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Optional settings, which are left out of the serialized config while unset
+const OPTIONAL_KEYS: &[&str] = &["editor", "decoy_database_path", "agent_socket", "password_file"];
+
+impl Config {
+    /// Dotted keys of every setting `get_key` and `set_key` accept
+    ///
+    /// Tables and lists, such as `entry_templates`, have to be edited in the file.
+    pub fn keys() -> Vec<String> {
+        let mut keys = Vec::new();
+        if let Ok(toml::Value::Table(table)) = toml::Value::try_from(Config::default()) {
+            collect_keys(&table, "", &mut keys);
+        }
+        keys.extend(OPTIONAL_KEYS.iter().map(|key| key.to_string()));
+        keys.sort();
+        keys
+    }
+
+    /// Current value of a setting, or `None` if an optional one is unset
+    pub fn get_key(&self, key: &str) -> Result<Option<toml::Value>> {
+        Self::check_key(key)?;
+        let value = toml::Value::try_from(self).map_err(|e| config_error(e.to_string()))?;
+        Ok(key.split('.').try_fold(&value, |value, part| value.get(part)).cloned())
+    }
+
+    /// Change a setting, parsing `value` as the type the setting holds
+    pub fn set_key(&mut self, key: &str, value: &str) -> Result<()> {
+        Self::check_key(key)?;
+        let defaults = toml::Value::try_from(Config::default()).map_err(|e| config_error(e.to_string()))?;
+        let invalid = |expected: &str| Error::InvalidInput(format!("{} must be {}, got '{}'", key, expected, value));
+
+        let parsed = match key.split('.').try_fold(&defaults, |value, part| value.get(part)) {
+            Some(toml::Value::Integer(_)) => toml::Value::Integer(value.parse().map_err(|_| invalid("an integer"))?),
+            Some(toml::Value::Float(_)) => toml::Value::Float(value.parse().map_err(|_| invalid("a number"))?),
+            Some(toml::Value::Boolean(_)) => toml::Value::Boolean(value.parse().map_err(|_| invalid("true or false"))?),
+            // Strings, paths and the optional settings
+            _ => toml::Value::String(value.to_string()),
+        };
+
+        let mut config = toml::Value::try_from(&*self).map_err(|e| config_error(e.to_string()))?;
+        let (parents, name) = key.rsplit_once('.').map_or((None, key), |(parents, name)| (Some(parents), name));
+        let mut table = config.as_table_mut().expect("config serializes to a table");
+        for part in parents.into_iter().flat_map(|parents| parents.split('.')) {
+            table = table
+                .get_mut(part)
+                .and_then(toml::Value::as_table_mut)
+                .expect("parent of a known key is a table");
+        }
+        table.insert(name.to_string(), parsed);
+
+        // Deserializing checks ranges, e.g. that a u32 isn't negative
+        let config: Config = config.try_into().map_err(|e: toml::de::Error| {
+            Error::InvalidInput(format!("invalid value '{}' for {}: {}", value, key, e.message()))
+        })?;
+        config.validate()?;
+        *self = config;
+        Ok(())
+    }
+
+    fn check_key(key: &str) -> Result<()> {
+        let keys = Self::keys();
+        if keys.iter().any(|k| k == key) {
+            return Ok(());
+        }
+        Err(Error::InvalidInput(format!("unknown config key '{}'; valid keys are: {}", key, keys.join(", "))))
+    }
+}
+
+/// Collect the dotted paths of the scalar values in `table`
+fn collect_keys(table: &toml::Table, prefix: &str, keys: &mut Vec<String>) {
+    for (name, value) in table {
+        let key = format!("{}{}", prefix, name);
+        match value {
+            toml::Value::Table(table) => collect_keys(table, &format!("{}.", key), keys),
+            toml::Value::Array(_) => {}
+            _ => keys.push(key),
+        }
+    }
+}
+
+fn config_error(message: String) -> Error {
+    Error::Config(config::ConfigError::Message(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_namespace_override() {
+        std::env::set_var(NAMESPACE_ENV, "passman-fork");
+
+        let config = Config::default();
+        let config_path = Config::config_file_path(DEFAULT_PROFILE).unwrap();
+        std::env::remove_var(NAMESPACE_ENV);
+
+        let db_dir = config.database_dir().unwrap();
+        assert_eq!(db_dir.file_name().unwrap(), "passman-fork");
+        assert_eq!(config_path.parent().unwrap().file_name().unwrap(), "passman-fork");
+        assert_eq!(config_path.file_name().unwrap(), CONFIG_FILE_NAME);
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_without_create_writes_nothing() {
+        let namespace = format!("passman-test-{}", uuid::Uuid::new_v4().simple());
+        std::env::set_var(NAMESPACE_ENV, &namespace);
+        let config_path = Config::config_file_path(DEFAULT_PROFILE).unwrap();
+        let config = Config::load_without_create(DEFAULT_PROFILE);
+        let default_path = Config::default().database_path;
+        std::env::remove_var(NAMESPACE_ENV);
+
+        assert_eq!(config.unwrap().database_path, default_path);
+        assert!(!config_path.exists());
+        assert!(!config_path.parent().unwrap().exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_profiles_are_isolated() {
+        let namespace = format!("passman-test-{}", uuid::Uuid::new_v4().simple());
+        std::env::set_var(NAMESPACE_ENV, &namespace);
+        let created = Config::create_profile("work").map(|config| config.database_path);
+        let duplicate = Config::create_profile("work");
+        let profiles = list_profiles();
+        let default = Config::load_without_create(DEFAULT_PROFILE).unwrap();
+        let work = Config::load_without_create("work").unwrap();
+        let work_dir = profile_dir("work").unwrap();
+        let invalid = Config::load_without_create("../work");
+        std::env::remove_var(NAMESPACE_ENV);
+        std::fs::remove_dir_all(work_dir.parent().unwrap().parent().unwrap()).unwrap();
+
+        assert_eq!(created.unwrap(), work_dir.join(DEFAULT_DB_NAME));
+        assert!(matches!(duplicate, Err(Error::InvalidInput(_))));
+        assert_eq!(profiles.unwrap(), vec![DEFAULT_PROFILE, "work"]);
+        assert_eq!(work.database_path, work_dir.join(DEFAULT_DB_NAME));
+        assert_ne!(default.database_path, work.database_path);
+        assert!(matches!(invalid, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_namespace_ignored() {
+        std::env::set_var(NAMESPACE_ENV, "../elsewhere");
+        let namespace = app_namespace();
+        std::env::remove_var(NAMESPACE_ENV);
+
+        assert_ne!(namespace, "../elsewhere");
+    }
+
+    #[test]
+    fn test_prepare_database_dir_creates_or_errors() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            database_path: temp_dir.path().join("gone").join(DEFAULT_DB_NAME),
+            create_missing_dir: false,
+            ..Config::default()
+        };
+
+        let err = config.prepare_database_dir().unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+        assert!(!temp_dir.path().join("gone").exists());
+
+        config.create_missing_dir = true;
+        config.prepare_database_dir().unwrap();
+        assert!(temp_dir.path().join("gone").is_dir());
+    }
+
+    #[test]
+    fn test_entry_templates_round_trip_through_toml() {
+        use crate::database::templates::TemplateField;
+        use crate::database::FieldKind;
+
+        let config = Config {
+            entry_templates: vec![EntryTemplate {
+                name: "wifi".to_string(),
+                fields: vec![
+                    TemplateField { name: "ssid".to_string(), kind: FieldKind::Text },
+                    TemplateField { name: "password".to_string(), kind: FieldKind::Secret },
+                ],
+            }],
+            ..Config::default()
+        };
+
+        let loaded: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(loaded.entry_templates, config.entry_templates);
+    }
+
+    #[test]
+    fn test_get_and_set_keys() {
+        let mut config = Config::default();
+
+        config.set_key("password_generation.default_length", "24").unwrap();
+        assert_eq!(config.password_generation.default_length, 24);
+        assert_eq!(config.get_key("password_generation.default_length").unwrap(), Some(toml::Value::Integer(24)));
+
+        config.set_key("clipboard_osc52", "true").unwrap();
+        assert!(config.clipboard_osc52);
+        config.set_key("security.weak_password_min_bits", "72").unwrap();
+        assert_eq!(config.security.weak_password_min_bits, 72.0);
+
+        assert_eq!(config.get_key("editor").unwrap(), None);
+        config.set_key("editor", "vim -n").unwrap();
+        assert_eq!(config.editor.as_deref(), Some("vim -n"));
+        for key in OPTIONAL_KEYS {
+            config.set_key(key, "value").unwrap();
+            assert_eq!(config.get_key(key).unwrap(), Some(toml::Value::String("value".to_string())), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_set_key_rejects_bad_values_and_keys() {
+        let mut config = Config::default();
+
+        for (key, value) in [
+            ("clipboard_timeout", "soon"),
+            ("clipboard_timeout", "-5"),
+            ("security.kdf_iterations", "5000000000"),
+            ("clipboard_osc52", "yes"),
+        ] {
+            let err = config.set_key(key, value).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput(_)), "{} = {}", key, value);
+        }
+        assert_eq!(config.clipboard_timeout, 30);
+
+        let err = config.set_key("clipboard_timout", "5").unwrap_err().to_string();
+        assert!(err.contains("unknown config key 'clipboard_timout'"));
+        assert!(err.contains("clipboard_timeout"));
+        assert!(config.get_key("entry_templates").is_err());
+        assert!(config.get_key("security").is_err());
+    }
+
+    #[test]
+    fn test_validate_password_generation() {
+        assert!(Config::default().validate().is_ok());
+
+        let invalid: [fn(&mut PasswordGenerationConfig); 4] = [
+            |config| config.default_length = 0,
+            |config| {
+                config.include_uppercase = false;
+                config.include_lowercase = false;
+                config.include_numbers = false;
+                config.include_symbols = false;
+            },
+            |config| config.default_length = 3,
+            |config| config.symbol_set.clear(),
+        ];
+        for (i, break_config) in invalid.iter().enumerate() {
+            let mut config = Config::default();
+            break_config(&mut config.password_generation);
+            assert!(matches!(config.validate(), Err(Error::Config(_))), "case {}", i);
+        }
+
+        let mut config = Config::default();
+        config.password_generation.include_symbols = false;
+        config.password_generation.symbol_set.clear();
+        assert!(config.validate().is_ok());
+
+        // `config set` refuses to leave the config invalid
+        let err = config.set_key("password_generation.default_length", "0").unwrap_err();
+        assert!(err.to_string().contains("default_length must be at least 1"));
+        assert_eq!(config.password_generation.default_length, PasswordGenerationConfig::default().default_length);
+    }
+
+    #[test]
+    fn test_backup_path_is_timestamped_next_to_the_vault() {
+        let at = DateTime::parse_from_rfc3339("2024-03-05T07:08:09Z").unwrap().with_timezone(&Utc);
+        let mut config = Config { database_path: PathBuf::from("/vaults/passman.db"), ..Config::default() };
+        assert_eq!(config.backup_path(at), PathBuf::from("/vaults/passman-backup-20240305-070809.db"));
+
+        config.database_path = PathBuf::from("vault");
+        assert_eq!(config.backup_path(at), PathBuf::from("vault-backup-20240305-070809"));
+    }
 }
@@ -1,30 +1,159 @@
 use crate::{Error, Result};
+use aes_gcm::Aes256Gcm;
 use chacha20poly1305::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
     ChaCha20Poly1305, Key, Nonce,
 };
+use ring::hmac;
 use ring::rand::{SecureRandom, SystemRandom};
 use zeroize::Zeroize;
 
-/// Encryption manager using ChaCha20Poly1305
+/// Version of the on-disk encryption format written by this build
+///
+/// Stored in each vault's metadata so a build can refuse vaults whose
+/// ciphertexts it would misread.
+pub const VAULT_FORMAT_VERSION: u32 = 2;
+
+/// Oldest vault encryption format this build still reads
+///
+/// Format 1 vaults hold unversioned ciphertexts, which `decrypt` still
+/// accepts; they are upgraded to the current format on unlock.
+pub const OLDEST_VAULT_FORMAT_VERSION: u32 = 1;
+
+/// Version byte at the start of every ciphertext written by `encrypt`
+pub const CIPHERTEXT_VERSION: u8 = 1;
+
+/// Version byte and cipher id in front of the nonce
+const HEADER_LEN: usize = 2;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Length of `encrypt`'s output for an empty plaintext
+///
+/// Lets callers spot empty passwords without decrypting them.
+pub const EMPTY_CIPHERTEXT_LEN: usize = HEADER_LEN + NONCE_LEN + TAG_LEN;
+
+/// What the key check authenticates; any fixed string would do
+const KEY_CHECK_CONTEXT: &[u8] = b"passman-cli vault key check";
+
+/// Tag stored in the vault metadata to recognise the vault's key
+///
+/// An HMAC rather than a ciphertext, so it doesn't depend on the vault's
+/// cipher and can't be confused with an entry.
+pub fn key_check(key: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), KEY_CHECK_CONTEXT).as_ref().to_vec()
+}
+
+/// Whether `tag` is `key_check(key)`, compared in constant time
+pub fn verify_key_check(key: &[u8], tag: &[u8]) -> bool {
+    hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, key), KEY_CHECK_CONTEXT, tag).is_ok()
+}
+
+/// AEAD ciphers a vault's entries can be encrypted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Cipher {
+    /// ChaCha20-Poly1305 (the default)
+    #[default]
+    #[value(name = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+    /// AES-256-GCM
+    #[value(name = "aes-256-gcm")]
+    Aes256Gcm,
+}
+
+impl Cipher {
+    /// Identifier stored in the vault metadata
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Cipher::ChaCha20Poly1305 => "chacha20-poly1305",
+            Cipher::Aes256Gcm => "aes-256-gcm",
+        }
+    }
+
+    /// Parse a stored cipher identifier
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "chacha20-poly1305" => Ok(Cipher::ChaCha20Poly1305),
+            "aes-256-gcm" => Ok(Cipher::Aes256Gcm),
+            other => Err(Error::Crypto(format!("Unknown cipher '{}'", other))),
+        }
+    }
+
+    /// Identifier stored in each ciphertext's header
+    pub fn id(self) -> u8 {
+        match self {
+            Cipher::ChaCha20Poly1305 => 1,
+            Cipher::Aes256Gcm => 2,
+        }
+    }
+
+    /// Parse a cipher id from a ciphertext header
+    pub fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(Cipher::ChaCha20Poly1305),
+            2 => Ok(Cipher::Aes256Gcm),
+            other => Err(Error::Crypto(format!("Unknown cipher id {} in ciphertext", other))),
+        }
+    }
+
+    /// Human-readable algorithm name
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Cipher::ChaCha20Poly1305 => "ChaCha20-Poly1305",
+            Cipher::Aes256Gcm => "AES-256-GCM",
+        }
+    }
+}
+
+/// Encryption manager for a vault's AEAD cipher
+///
+/// Ciphertexts are `version || cipher id || nonce || ciphertext || tag`
+/// with a 12-byte nonce for every supported cipher. Ciphertexts written
+/// before the header existed (version 0) are plain `nonce || ciphertext || tag`
+/// under the manager's cipher.
 pub struct EncryptionManager {
     rng: SystemRandom,
+    cipher: Cipher,
 }
 
 impl EncryptionManager {
-    /// Create a new encryption manager
+    /// Create a new encryption manager using the default cipher
     pub fn new() -> Self {
+        Self::with_cipher(Cipher::default())
+    }
+
+    /// Create an encryption manager for a specific cipher
+    pub fn with_cipher(cipher: Cipher) -> Self {
         Self {
             rng: SystemRandom::new(),
+            cipher,
         }
     }
 
+    /// The cipher this manager encrypts and decrypts with
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
     /// Encrypt data with a given key
     pub fn encrypt(&self, key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
         if key.len() != 32 {
             return Err(Error::Crypto("Key must be 32 bytes".to_string()));
         }
 
+        let sealed = match self.cipher {
+            Cipher::ChaCha20Poly1305 => Self::encrypt_chacha(key, plaintext),
+            Cipher::Aes256Gcm => Self::encrypt_aes_gcm(key, plaintext),
+        }?;
+
+        let mut result = Vec::with_capacity(HEADER_LEN + sealed.len());
+        result.push(CIPHERTEXT_VERSION);
+        result.push(self.cipher.id());
+        result.extend_from_slice(&sealed);
+        Ok(result)
+    }
+
+    fn encrypt_chacha(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
         let key = Key::from_slice(key);
         let cipher = ChaCha20Poly1305::new(key);
         let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
@@ -40,16 +169,61 @@ impl EncryptionManager {
         Ok(result)
     }
 
+    fn encrypt_aes_gcm(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let cipher = Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(key));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| Error::Crypto(format!("Encryption failed: {}", e)))?;
+
+        // Same layout as ChaCha20-Poly1305: nonce first
+        let mut result = nonce.to_vec();
+        result.extend_from_slice(&ciphertext);
+
+        Ok(result)
+    }
+
     /// Decrypt data with a given key
     pub fn decrypt(&self, key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
         if key.len() != 32 {
             return Err(Error::Crypto("Key must be 32 bytes".to_string()));
         }
 
-        if ciphertext.len() < 12 {
+        if ciphertext.len() < NONCE_LEN + TAG_LEN {
             return Err(Error::Crypto("Ciphertext too short".to_string()));
         }
 
+        // Too short for a header, so it can only be a legacy ciphertext
+        if ciphertext.len() < HEADER_LEN + NONCE_LEN + TAG_LEN {
+            return Self::decrypt_with(self.cipher, key, ciphertext);
+        }
+
+        match ciphertext[0] {
+            CIPHERTEXT_VERSION => {
+                let versioned = Cipher::from_id(ciphertext[1])
+                    .and_then(|cipher| Self::decrypt_with(cipher, key, &ciphertext[HEADER_LEN..]));
+                // A legacy nonce can start with bytes that look like a header
+                versioned.or_else(|e| Self::decrypt_with(self.cipher, key, ciphertext).map_err(|_| e))
+            }
+            version => Self::decrypt_with(self.cipher, key, ciphertext).map_err(|_| {
+                Error::Crypto(format!(
+                    "Decryption failed: not a legacy ciphertext and unknown format version {}",
+                    version
+                ))
+            }),
+        }
+    }
+
+    /// Decrypt a headerless `nonce || ciphertext || tag` blob
+    fn decrypt_with(cipher: Cipher, key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        match cipher {
+            Cipher::ChaCha20Poly1305 => Self::decrypt_chacha(key, ciphertext),
+            Cipher::Aes256Gcm => Self::decrypt_aes_gcm(key, ciphertext),
+        }
+    }
+
+    fn decrypt_chacha(key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
         let key = Key::from_slice(key);
         let cipher = ChaCha20Poly1305::new(key);
         
@@ -64,6 +238,19 @@ impl EncryptionManager {
         Ok(plaintext)
     }
 
+    fn decrypt_aes_gcm(key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let cipher = Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(key));
+
+        let (nonce_bytes, encrypted_data) = ciphertext.split_at(12);
+        let nonce = aes_gcm::Nonce::from_slice(nonce_bytes);
+
+        let plaintext = cipher
+            .decrypt(nonce, encrypted_data)
+            .map_err(|e| Error::Crypto(format!("Decryption failed: {}", e)))?;
+
+        Ok(plaintext)
+    }
+
     /// Generate a random encryption key
     pub fn generate_key(&self) -> Result<Vec<u8>> {
         let mut key = vec![0u8; 32];
@@ -138,6 +325,57 @@ mod tests {
         assert_eq!(plaintext, &decrypted[..]);
     }
 
+    #[test]
+    fn test_ciphers_round_trip_and_do_not_mix() {
+        let key = EncryptionManager::new().generate_key().unwrap();
+        let chacha = EncryptionManager::with_cipher(Cipher::ChaCha20Poly1305);
+        let aes = EncryptionManager::with_cipher(Cipher::Aes256Gcm);
+
+        let ciphertext = aes.encrypt(&key, b"Hello, World!").unwrap();
+        assert_eq!(aes.decrypt(&key, &ciphertext).unwrap(), b"Hello, World!");
+        // The header records the cipher, so any manager can decrypt it
+        assert_eq!(chacha.decrypt(&key, &ciphertext).unwrap(), b"Hello, World!");
+
+        // Legacy ciphertexts rely on the manager's cipher
+        let legacy = EncryptionManager::encrypt_aes_gcm(&key, b"Hello, World!").unwrap();
+        assert_eq!(aes.decrypt(&key, &legacy).unwrap(), b"Hello, World!");
+        assert!(chacha.decrypt(&key, &legacy).is_err());
+
+        let mut tampered = ciphertext.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(aes.decrypt(&key, &tampered).is_err());
+
+        assert_eq!(Cipher::parse(Cipher::Aes256Gcm.as_str()).unwrap(), Cipher::Aes256Gcm);
+    }
+
+    #[test]
+    fn test_ciphertext_header_and_legacy_format() {
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+
+        let ciphertext = manager.encrypt(&key, b"secret").unwrap();
+        assert_eq!(ciphertext[0], CIPHERTEXT_VERSION);
+        assert_eq!(ciphertext[1], Cipher::ChaCha20Poly1305.id());
+        assert_eq!(ciphertext.len(), HEADER_LEN + NONCE_LEN + b"secret".len() + TAG_LEN);
+        assert_eq!(manager.decrypt(&key, &ciphertext).unwrap(), b"secret");
+
+        // Version 0: nonce || ciphertext || tag, including an empty plaintext
+        // that is shorter than any versioned ciphertext
+        for plaintext in [&b"secret"[..], b""] {
+            let legacy = EncryptionManager::encrypt_chacha(&key, plaintext).unwrap();
+            assert_eq!(manager.decrypt(&key, &legacy).unwrap(), plaintext);
+        }
+
+        let mut unknown_version = ciphertext.clone();
+        unknown_version[0] = 0xfe;
+        let err = manager.decrypt(&key, &unknown_version).unwrap_err();
+        assert!(matches!(err, Error::Crypto(msg) if msg.contains("unknown format version 254")));
+
+        let mut unknown_cipher = ciphertext;
+        unknown_cipher[1] = 0xfe;
+        assert!(matches!(manager.decrypt(&key, &unknown_cipher), Err(Error::Crypto(_))));
+    }
+
     #[test]
     fn test_secure_data() {
         let key = EncryptionManager::new().generate_key().unwrap();
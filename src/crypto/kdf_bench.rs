@@ -0,0 +1,124 @@
+use crate::crypto::{Argon2Params, PasswordManager};
+use crate::Result;
+use std::time::{Duration, Instant};
+
+/// Largest memory cost tried, in KiB (4 GiB)
+const MAX_M_COST: u32 = 4 * 1024 * 1024;
+/// Most iterations tried
+const MAX_T_COST: u32 = 10;
+
+/// A single timed key derivation
+#[derive(Debug, Clone, Copy)]
+pub struct KdfSample {
+    pub params: Argon2Params,
+    pub elapsed: Duration,
+}
+
+/// Outcome of tuning the KDF against a target duration
+#[derive(Debug, Clone)]
+pub struct KdfTuning {
+    /// Every derivation that was timed, in order
+    pub samples: Vec<KdfSample>,
+    /// The strongest parameters that stayed within the target, never below the floor
+    pub recommended: Argon2Params,
+}
+
+impl KdfTuning {
+    /// Whether even the floor parameters took longer than the target
+    pub fn floor_exceeds(&self, target: Duration) -> bool {
+        self.samples.first().is_some_and(|s| s.elapsed > target)
+    }
+
+    /// Lines for the `[security]` section of the config file
+    pub fn config_snippet(&self) -> String {
+        format!(
+            "[security]\nkdf_memory_mib = {}\nkdf_iterations = {}\nkdf_parallelism = {}\n",
+            self.recommended.m_cost / 1024,
+            self.recommended.t_cost,
+            self.recommended.p_cost
+        )
+    }
+}
+
+/// Time `derive_key` with increasing costs to find parameters near `target`
+///
+/// Starts from the default parameters, which also serve as the floor.
+pub fn tune(target: Duration) -> Result<KdfTuning> {
+    let salt = PasswordManager::new().generate_salt()?;
+    tune_with(target, Argon2Params::default(), |params| {
+        let manager = PasswordManager::with_params(params)?;
+        let start = Instant::now();
+        manager.derive_key("passman-bench-kdf", &salt)?;
+        Ok(start.elapsed())
+    })
+}
+
+/// Tuning strategy with a pluggable timer
+///
+/// Memory is doubled until a derivation exceeds `target`, then iterations
+/// are raised on the largest memory cost that fit.
+fn tune_with<F>(target: Duration, floor: Argon2Params, mut measure: F) -> Result<KdfTuning>
+where
+    F: FnMut(Argon2Params) -> Result<Duration>,
+{
+    let mut samples = Vec::new();
+    let mut time = |params: Argon2Params, samples: &mut Vec<KdfSample>| -> Result<bool> {
+        let elapsed = measure(params)?;
+        samples.push(KdfSample { params, elapsed });
+        Ok(elapsed <= target)
+    };
+
+    let mut recommended = floor;
+    if !time(floor, &mut samples)? {
+        return Ok(KdfTuning { samples, recommended });
+    }
+
+    while recommended.m_cost <= MAX_M_COST / 2 {
+        let candidate = Argon2Params { m_cost: recommended.m_cost * 2, ..recommended };
+        if !time(candidate, &mut samples)? {
+            break;
+        }
+        recommended = candidate;
+    }
+
+    while recommended.t_cost < MAX_T_COST {
+        let candidate = Argon2Params { t_cost: recommended.t_cost + 1, ..recommended };
+        if !time(candidate, &mut samples)? {
+            break;
+        }
+        recommended = candidate;
+    }
+
+    Ok(KdfTuning { samples, recommended })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pretend a derivation costs 1ms per MiB per iteration
+    fn simulated(params: Argon2Params) -> Result<Duration> {
+        Ok(Duration::from_millis(u64::from(params.m_cost / 1024) * u64::from(params.t_cost)))
+    }
+
+    #[test]
+    fn test_tune_doubles_memory_then_raises_iterations() {
+        let floor = Argon2Params { m_cost: 16 * 1024, t_cost: 2, p_cost: 1 };
+        let tuning = tune_with(Duration::from_millis(500), floor, simulated).unwrap();
+
+        // 128 MiB x 2 = 256ms fits, 256 MiB x 2 = 512ms doesn't; 128 MiB x 3 = 384ms fits, x 4 = 512ms doesn't
+        assert_eq!(tuning.recommended, Argon2Params { m_cost: 128 * 1024, t_cost: 3, p_cost: 1 });
+        assert!(tuning.config_snippet().contains("kdf_memory_mib = 128\nkdf_iterations = 3"));
+        assert!(!tuning.floor_exceeds(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_tune_never_goes_below_floor() {
+        let floor = Argon2Params::default();
+        let tuning = tune_with(Duration::from_millis(1), floor, simulated).unwrap();
+
+        assert_eq!(tuning.recommended, floor);
+        assert_eq!(tuning.samples.len(), 1);
+        assert!(tuning.floor_exceeds(Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,165 @@
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+/// Thin abstraction over the platform's page-locking primitives
+pub trait MemoryLocker {
+    /// Lock the given memory region into RAM, returning whether it succeeded
+    fn lock(&self, ptr: *const u8, len: usize) -> bool;
+
+    /// Unlock a previously locked memory region
+    fn unlock(&self, ptr: *const u8, len: usize) -> bool;
+}
+
+/// Memory locker backed by mlock (Unix) or VirtualLock (Windows)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemLocker;
+
+impl MemoryLocker for SystemLocker {
+    #[cfg(unix)]
+    fn lock(&self, ptr: *const u8, len: usize) -> bool {
+        // SAFETY: the caller passes a live allocation of at least `len` bytes
+        unsafe { libc::mlock(ptr as *const libc::c_void, len) == 0 }
+    }
+
+    #[cfg(unix)]
+    fn unlock(&self, ptr: *const u8, len: usize) -> bool {
+        // SAFETY: the caller passes a live allocation of at least `len` bytes
+        unsafe { libc::munlock(ptr as *const libc::c_void, len) == 0 }
+    }
+
+    #[cfg(windows)]
+    fn lock(&self, ptr: *const u8, len: usize) -> bool {
+        use windows_sys::Win32::System::Memory::VirtualLock;
+        // SAFETY: the caller passes a live allocation of at least `len` bytes
+        unsafe { VirtualLock(ptr as *const _, len) != 0 }
+    }
+
+    #[cfg(windows)]
+    fn unlock(&self, ptr: *const u8, len: usize) -> bool {
+        use windows_sys::Win32::System::Memory::VirtualUnlock;
+        // SAFETY: the caller passes a live allocation of at least `len` bytes
+        unsafe { VirtualUnlock(ptr as *const _, len) != 0 }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn lock(&self, _ptr: *const u8, _len: usize) -> bool {
+        false
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn unlock(&self, _ptr: *const u8, _len: usize) -> bool {
+        false
+    }
+}
+
+/// Encryption key that is optionally locked into RAM and zeroized on drop
+pub struct LockedKey<L: MemoryLocker = SystemLocker> {
+    key: Box<[u8]>,
+    locker: L,
+    locked: bool,
+}
+
+impl LockedKey<SystemLocker> {
+    /// Wrap a key, locking its memory when `lock_memory` is set
+    pub fn new(key: Vec<u8>, lock_memory: bool) -> Self {
+        if lock_memory {
+            Self::with_locker(key, SystemLocker)
+        } else {
+            Self {
+                key: key.into_boxed_slice(),
+                locker: SystemLocker,
+                locked: false,
+            }
+        }
+    }
+}
+
+impl<L: MemoryLocker> LockedKey<L> {
+    /// Wrap a key and lock its memory using the given locker
+    pub fn with_locker(key: Vec<u8>, locker: L) -> Self {
+        let key = key.into_boxed_slice();
+        let locked = key.is_empty() || locker.lock(key.as_ptr(), key.len());
+        if !locked {
+            log::warn!("Unable to lock key memory; it may be swapped to disk");
+        }
+
+        Self { key, locker, locked }
+    }
+
+    /// Whether the key memory is currently locked
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Access the raw key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl<L: MemoryLocker> Deref for LockedKey<L> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl<L: MemoryLocker> Drop for LockedKey<L> {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        if self.locked && !self.key.is_empty() {
+            self.locker.unlock(self.key.as_ptr(), self.key.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Call = (&'static str, usize, usize);
+
+    #[derive(Clone, Default)]
+    struct RecordingLocker {
+        calls: Rc<RefCell<Vec<Call>>>,
+    }
+
+    impl MemoryLocker for RecordingLocker {
+        fn lock(&self, ptr: *const u8, len: usize) -> bool {
+            self.calls.borrow_mut().push(("lock", ptr as usize, len));
+            true
+        }
+
+        fn unlock(&self, ptr: *const u8, len: usize) -> bool {
+            self.calls.borrow_mut().push(("unlock", ptr as usize, len));
+            true
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_and_unlock_wrap_key_buffer() {
+        let locker = RecordingLocker::default();
+        let key = LockedKey::with_locker(vec![7u8; 32], locker.clone());
+        let ptr = key.as_bytes().as_ptr() as usize;
+
+        assert!(key.is_locked());
+        assert_eq!(*locker.calls.borrow(), vec![("lock", ptr, 32)]);
+
+        drop(key);
+        assert_eq!(
+            *locker.calls.borrow(),
+            vec![("lock", ptr, 32), ("unlock", ptr, 32)]
+        );
+    }
+
+    #[test]
+    fn test_unlocked_key_skips_locker() {
+        let key = LockedKey::new(vec![1, 2, 3], false);
+        assert!(!key.is_locked());
+        assert_eq!(&*key, &[1, 2, 3]);
+    }
+}
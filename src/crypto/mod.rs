@@ -1,5 +1,11 @@
 pub mod encryption;
+pub mod kdf_bench;
+pub mod memlock;
 pub mod password;
+pub mod selftest;
+pub mod totp;
 
 pub use encryption::*;
+pub use memlock::*;
 pub use password::*;
+pub use selftest::self_test;
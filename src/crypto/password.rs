@@ -2,8 +2,42 @@ use crate::{Error, Result};
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use argon2::password_hash::{rand_core::OsRng, SaltString};
 use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
 use zeroize::Zeroize;
 
+/// Argon2id cost parameters a vault's key is derived with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    /// Memory cost in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self {
+            m_cost: argon2::Params::DEFAULT_M_COST,
+            t_cost: argon2::Params::DEFAULT_T_COST,
+            p_cost: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl Argon2Params {
+    /// Check the parameters are accepted by Argon2
+    pub fn validate(&self) -> Result<()> {
+        self.to_argon2_params().map(|_| ())
+    }
+
+    fn to_argon2_params(self) -> Result<argon2::Params> {
+        argon2::Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+            .map_err(|e| Error::InvalidInput(format!("Invalid KDF parameters: {}", e)))
+    }
+}
+
 /// Password hashing and verification utilities
 pub struct PasswordManager {
     argon2: Argon2<'static>,
@@ -19,6 +53,29 @@ impl PasswordManager {
         }
     }
 
+    /// Create a password manager using the given Argon2 parameters
+    pub fn with_params(params: Argon2Params) -> Result<Self> {
+        Ok(Self {
+            argon2: Argon2::new(
+                argon2::Algorithm::Argon2id,
+                argon2::Version::V0x13,
+                params.to_argon2_params()?,
+            ),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Human-readable description of the key derivation parameters in use
+    pub fn kdf_description(&self) -> String {
+        let params = self.argon2.params();
+        format!(
+            "Argon2id v19 (m={} KiB, t={}, p={})",
+            params.m_cost(),
+            params.t_cost(),
+            params.p_cost()
+        )
+    }
+
     /// Hash a master password with a salt
     pub fn hash_password(&self, password: &str) -> Result<(String, Vec<u8>)> {
         let salt = SaltString::generate(&mut OsRng);
@@ -50,24 +107,17 @@ impl PasswordManager {
         Ok(salt)
     }
 
-    /// Derive an encryption key from a password and salt
+    /// Derive a 256-bit encryption key from a password and salt
+    ///
+    /// Uses Argon2's raw output, so the key doesn't depend on PHC string
+    /// encoding. For the 32-byte vault salts this yields the same bytes as
+    /// the hash field of a PHC hash over the same salt, which older versions used.
     pub fn derive_key(&self, password: &str, salt: &[u8]) -> Result<Vec<u8>> {
-        let mut key = vec![0u8; 32]; // 256-bit key
-        
-        // Use Argon2 for key derivation
-        let salt_string = SaltString::encode_b64(salt)
-            .map_err(|e| Error::Crypto(format!("Invalid salt: {}", e)))?;
-        
-        let password_hash = self.argon2
-            .hash_password(password.as_bytes(), &salt_string)
-            .map_err(Error::from)?;
-        
-        // Extract the hash bytes (32 bytes for our key)
-        let hash = password_hash.hash.unwrap();
-        let hash_bytes = hash.as_bytes();
-        let copy_len = std::cmp::min(key.len(), hash_bytes.len());
-        key[..copy_len].copy_from_slice(&hash_bytes[..copy_len]);
-        
+        let mut key = vec![0u8; 32];
+        if let Err(e) = self.argon2.hash_password_into(password.as_bytes(), salt, &mut key) {
+            key.zeroize();
+            return Err(Error::Crypto(format!("Key derivation failed: {}", e)));
+        }
         Ok(key)
     }
 }
@@ -81,7 +131,7 @@ impl Default for PasswordManager {
 /// Secure password input utility
 pub fn read_password(prompt: &str) -> Result<String> {
     let password = rpassword::prompt_password(prompt)
-        .map_err(|e| Error::Io(e))?;
+        .map_err(Error::Io)?;
     
     if password.trim().is_empty() {
         return Err(Error::InvalidInput("Password cannot be empty".to_string()));
@@ -107,3 +157,32 @@ pub fn read_password_with_confirmation(prompt: &str) -> Result<String> {
     
     Ok(password)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_key_is_deterministic_per_salt() {
+        let manager = PasswordManager::new();
+        let salt = manager.generate_salt().unwrap();
+
+        let key = manager.derive_key("correct horse", &salt).unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, manager.derive_key("correct horse", &salt).unwrap());
+
+        let other_salt = manager.generate_salt().unwrap();
+        assert_ne!(key, manager.derive_key("correct horse", &other_salt).unwrap());
+    }
+
+    #[test]
+    fn test_derive_key_matches_phc_hash_output() {
+        // Vaults created before the raw KDF API was used must keep their keys
+        let manager = PasswordManager::new();
+        let salt = [7u8; 32];
+        let salt_string = SaltString::encode_b64(&salt).unwrap();
+        let phc = manager.argon2.hash_password(b"correct horse", &salt_string).unwrap();
+
+        assert_eq!(manager.derive_key("correct horse", &salt).unwrap(), phc.hash.unwrap().as_bytes());
+    }
+}
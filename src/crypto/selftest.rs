@@ -0,0 +1,150 @@
+use crate::crypto::{EncryptionManager, PasswordManager};
+use crate::Result;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+
+/// RFC 8439 section 2.8.2 AEAD test vector
+const KAT_NONCE: [u8; 12] = [0x07, 0x00, 0x00, 0x00, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47];
+const KAT_AAD: [u8; 12] = [0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7];
+const KAT_PLAINTEXT: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+const KAT_CIPHERTEXT_HEX: &str = concat!(
+    "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+    "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+    "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+    "3ff4def08e4b7a9de576d26586cec64b6116",
+    // Poly1305 tag
+    "1ae10b594f09e26a7e902ecbd0600691",
+);
+
+/// A check returns `Some(reason)` when it fails
+type Check = fn() -> Result<Option<String>>;
+
+/// Outcome of a single self-test check
+#[derive(Debug, Clone)]
+pub struct SelfTestResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+/// Outcome of the full crypto self-test
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    pub results: Vec<SelfTestResult>,
+}
+
+impl SelfTestReport {
+    /// Whether every check passed
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+}
+
+/// Run known-answer and consistency checks against the crypto stack
+pub fn self_test() -> SelfTestReport {
+    let checks: [(&'static str, Check); 4] = [
+        ("ChaCha20-Poly1305 known answer", chacha20_known_answer),
+        ("Encrypt/decrypt round-trip", encryption_round_trip),
+        ("Argon2 hash/verify", argon2_hash_verify),
+        ("KDF determinism", kdf_determinism),
+    ];
+
+    let results = checks
+        .into_iter()
+        .map(|(name, check)| match check() {
+            Ok(None) => SelfTestResult { name, passed: true, detail: None },
+            Ok(Some(failure)) => SelfTestResult { name, passed: false, detail: Some(failure) },
+            Err(e) => SelfTestResult { name, passed: false, detail: Some(e.to_string()) },
+        })
+        .collect();
+
+    SelfTestReport { results }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn chacha20_known_answer() -> Result<Option<String>> {
+    let key: Vec<u8> = (0x80u8..=0x9f).collect();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let payload = Payload { msg: KAT_PLAINTEXT, aad: &KAT_AAD };
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&KAT_NONCE), payload)
+        .map_err(|e| crate::Error::Crypto(format!("Encryption failed: {}", e)))?;
+
+    if to_hex(&ciphertext) != KAT_CIPHERTEXT_HEX {
+        return Ok(Some("ciphertext does not match the RFC 8439 vector".to_string()));
+    }
+    Ok(None)
+}
+
+fn encryption_round_trip() -> Result<Option<String>> {
+    let manager = EncryptionManager::new();
+    let key = manager.generate_key()?;
+    let plaintext = b"passman self-test";
+
+    let mut ciphertext = manager.encrypt(&key, plaintext)?;
+    if manager.decrypt(&key, &ciphertext)? != plaintext {
+        return Ok(Some("decrypted data differs from the original".to_string()));
+    }
+
+    let last = ciphertext.len() - 1;
+    ciphertext[last] ^= 0x01;
+    if manager.decrypt(&key, &ciphertext).is_ok() {
+        return Ok(Some("tampered ciphertext was accepted".to_string()));
+    }
+    Ok(None)
+}
+
+fn argon2_hash_verify() -> Result<Option<String>> {
+    let manager = PasswordManager::new();
+    let (hash, _) = manager.hash_password("correct horse")?;
+
+    if !manager.verify_password("correct horse", &hash)? {
+        return Ok(Some("correct password was rejected".to_string()));
+    }
+    if manager.verify_password("battery staple", &hash)? {
+        return Ok(Some("wrong password was accepted".to_string()));
+    }
+    Ok(None)
+}
+
+fn kdf_determinism() -> Result<Option<String>> {
+    let manager = PasswordManager::new();
+    let salt = [7u8; 32];
+    let other_salt = [8u8; 32];
+
+    let first = manager.derive_key("correct horse", &salt)?;
+    let second = manager.derive_key("correct horse", &salt)?;
+    let other = manager.derive_key("correct horse", &other_salt)?;
+
+    if first.len() != 32 {
+        return Ok(Some(format!("derived key is {} bytes, expected 32", first.len())));
+    }
+    if first != second {
+        return Ok(Some("same password and salt produced different keys".to_string()));
+    }
+    if first == other {
+        return Ok(Some("different salts produced the same key".to_string()));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let report = self_test();
+        for result in &report.results {
+            assert!(result.passed, "{} failed: {:?}", result.name, result.detail);
+        }
+        assert!(report.all_passed());
+        assert_eq!(report.results.len(), 4);
+    }
+}
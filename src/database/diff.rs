@@ -0,0 +1,154 @@
+use crate::database::{PasswordEntry, PasswordRepository, SecureString};
+use crate::Result;
+use std::collections::BTreeMap;
+
+/// Entry present in both vaults whose contents differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedEntry {
+    pub title: String,
+    /// Names of the fields that differ; values are never included
+    pub fields: Vec<String>,
+}
+
+/// Differences between two vaults, matched by entry title
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VaultDiff {
+    pub only_in_left: Vec<String>,
+    pub only_in_right: Vec<String>,
+    pub changed: Vec<ChangedEntry>,
+}
+
+impl VaultDiff {
+    /// Whether the vaults hold the same entries
+    pub fn is_empty(&self) -> bool {
+        self.only_in_left.is_empty() && self.only_in_right.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Decrypted entry contents compared between vaults
+struct Snapshot {
+    entry: PasswordEntry,
+    password: SecureString,
+    totp_secret: Option<SecureString>,
+    custom_fields: BTreeMap<String, SecureString>,
+}
+
+/// Compare two unlocked vaults
+///
+/// Entries are matched by title. Passwords and custom fields are decrypted
+/// for the comparison only; the result names the differing fields without
+/// their values.
+pub fn diff_vaults(
+    left: &PasswordRepository,
+    left_key: &[u8],
+    right: &PasswordRepository,
+    right_key: &[u8],
+) -> Result<VaultDiff> {
+    let mut left = snapshot(left, left_key)?;
+    let right = snapshot(right, right_key)?;
+    let mut diff = VaultDiff::default();
+
+    for (title, theirs) in right {
+        match left.remove(&title) {
+            Some(ours) => {
+                let fields = changed_fields(&ours, &theirs);
+                if !fields.is_empty() {
+                    diff.changed.push(ChangedEntry { title, fields });
+                }
+            }
+            None => diff.only_in_right.push(title),
+        }
+    }
+    diff.only_in_left = left.into_keys().collect();
+
+    Ok(diff)
+}
+
+fn snapshot(repo: &PasswordRepository, key: &[u8]) -> Result<BTreeMap<String, Snapshot>> {
+    let mut snapshots = BTreeMap::new();
+    for (entry, password) in repo.iter_decrypted(key)? {
+        let custom_fields = repo
+            .get_custom_fields(&entry.id, key)?
+            .into_iter()
+            .map(|field| (field.name, field.value))
+            .collect();
+
+        let totp_secret = repo.get_totp_secret(&entry.id, key)?;
+
+        snapshots.insert(
+            entry.title.clone(),
+            Snapshot { password: password?, totp_secret, entry, custom_fields },
+        );
+    }
+
+    Ok(snapshots)
+}
+
+fn changed_fields(ours: &Snapshot, theirs: &Snapshot) -> Vec<String> {
+    let mut fields = Vec::new();
+    if ours.entry.username != theirs.entry.username {
+        fields.push("username".to_string());
+    }
+    if ours.password.as_str() != theirs.password.as_str() {
+        fields.push("password".to_string());
+    }
+    if ours.entry.url != theirs.entry.url {
+        fields.push("url".to_string());
+    }
+    if ours.entry.notes != theirs.entry.notes {
+        fields.push("notes".to_string());
+    }
+    if ours.totp_secret.as_ref().map(SecureString::as_str) != theirs.totp_secret.as_ref().map(SecureString::as_str) {
+        fields.push("totp".to_string());
+    }
+
+    let names: std::collections::BTreeSet<&String> =
+        ours.custom_fields.keys().chain(theirs.custom_fields.keys()).collect();
+    for name in names {
+        let ours = ours.custom_fields.get(name).map(SecureString::as_str);
+        let theirs = theirs.custom_fields.get(name).map(SecureString::as_str);
+        if ours != theirs {
+            fields.push(name.clone());
+        }
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::EncryptionManager;
+
+    fn add(repo: &PasswordRepository, key: &[u8], title: &str, username: &str, password: &str) {
+        let entry = PasswordEntry::new(title.to_string(), username.to_string(), SecureString::default(), None, None);
+        let encrypted = EncryptionManager::new().encrypt(key, password.as_bytes()).unwrap();
+        repo.add_entry(&entry, &encrypted).unwrap();
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_and_modified() {
+        let manager = EncryptionManager::new();
+        let dir = tempfile::tempdir().unwrap();
+        let left = PasswordRepository::new(dir.path().join("left.db")).unwrap();
+        let right = PasswordRepository::new(dir.path().join("right.db")).unwrap();
+        let left_key = manager.generate_key().unwrap();
+        let right_key = manager.generate_key().unwrap();
+
+        add(&left, &left_key, "Unchanged", "alice", "same");
+        add(&right, &right_key, "Unchanged", "alice", "same");
+        add(&left, &left_key, "Removed", "alice", "gone");
+        add(&right, &right_key, "Added", "alice", "new");
+        add(&left, &left_key, "Modified", "alice", "old-password");
+        add(&right, &right_key, "Modified", "alice", "new-password");
+
+        let diff = diff_vaults(&left, &left_key, &right, &right_key).unwrap();
+        assert_eq!(diff.only_in_left, vec!["Removed"]);
+        assert_eq!(diff.only_in_right, vec!["Added"]);
+        assert_eq!(
+            diff.changed,
+            vec![ChangedEntry { title: "Modified".to_string(), fields: vec!["password".to_string()] }]
+        );
+        assert!(!format!("{:?}", diff).contains("password-"));
+    }
+}
@@ -1,11 +1,46 @@
-use crate::Result;
-use rusqlite::Connection;
+use crate::{Error, Result};
+use rusqlite::{params, Connection};
+use std::fmt;
 
 /// Database migration management
 pub struct Migration {
     pub version: u32,
     pub description: &'static str,
     pub sql: &'static str,
+    /// SQL undoing the migration, `None` if it can't be rolled back
+    pub down_sql: Option<&'static str>,
+    /// Schema objects (tables, indexes, or `table.column`) the migration is expected to create
+    pub objects: &'static [&'static str],
+}
+
+/// Inconsistency between the migrations table and the actual schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationIssue {
+    /// Migration is recorded but some of its schema objects are missing
+    MissingObjects { version: u32, objects: Vec<String> },
+    /// Migration's schema objects exist but it is not recorded
+    Unrecorded { version: u32 },
+    /// Recorded migration is not known to this build
+    Unknown { version: u32 },
+}
+
+impl fmt::Display for MigrationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationIssue::MissingObjects { version, objects } => write!(
+                f,
+                "Migration {} is recorded but missing: {}",
+                version,
+                objects.join(", ")
+            ),
+            MigrationIssue::Unrecorded { version } => {
+                write!(f, "Migration {} is applied but not recorded", version)
+            }
+            MigrationIssue::Unknown { version } => {
+                write!(f, "Migration {} is recorded but unknown to this version", version)
+            }
+        }
+    }
 }
 
 /// All available migrations
@@ -46,6 +81,211 @@ CREATE INDEX idx_password_entries_url ON password_entries(url);
 CREATE INDEX idx_password_entries_created_at ON password_entries(created_at);
 CREATE INDEX idx_password_entries_updated_at ON password_entries(updated_at);
 "#,
+        down_sql: None,
+        objects: &[
+            "vault_metadata",
+            "password_entries",
+            "idx_password_entries_title",
+            "idx_password_entries_username",
+            "idx_password_entries_url",
+            "idx_password_entries_created_at",
+            "idx_password_entries_updated_at",
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "Track password change time",
+        sql: r#"
+ALTER TABLE password_entries ADD COLUMN password_changed_at TEXT;
+UPDATE password_entries SET password_changed_at = updated_at;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE password_entries DROP COLUMN password_changed_at;
+"#),
+        objects: &["password_entries.password_changed_at"],
+    },
+    Migration {
+        version: 3,
+        description: "Custom entry fields",
+        sql: r#"
+CREATE TABLE entry_fields (
+    entry_id TEXT NOT NULL REFERENCES password_entries(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    encrypted_value BLOB NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (entry_id, name)
+);
+"#,
+        down_sql: Some(r#"
+DROP TABLE entry_fields;
+"#),
+        objects: &["entry_fields"],
+    },
+    Migration {
+        version: 4,
+        description: "Per-vault generator defaults",
+        sql: r#"
+ALTER TABLE vault_metadata ADD COLUMN generator_defaults TEXT;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE vault_metadata DROP COLUMN generator_defaults;
+"#),
+        objects: &["vault_metadata.generator_defaults"],
+    },
+    Migration {
+        version: 5,
+        description: "Encrypted TOTP secrets",
+        sql: r#"
+ALTER TABLE password_entries ADD COLUMN totp_secret BLOB;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE password_entries DROP COLUMN totp_secret;
+"#),
+        objects: &["password_entries.totp_secret"],
+    },
+    Migration {
+        version: 6,
+        description: "Record the vault encryption format",
+        sql: r#"
+-- Vaults created before this marker use the original format
+ALTER TABLE vault_metadata ADD COLUMN format_version INTEGER NOT NULL DEFAULT 1;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE vault_metadata DROP COLUMN format_version;
+"#),
+        objects: &["vault_metadata.format_version"],
+    },
+    Migration {
+        version: 7,
+        description: "Optional duress password",
+        sql: r#"
+ALTER TABLE vault_metadata ADD COLUMN duress_password_hash BLOB;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE vault_metadata DROP COLUMN duress_password_hash;
+"#),
+        objects: &["vault_metadata.duress_password_hash"],
+    },
+    Migration {
+        version: 8,
+        description: "Record the cipher entries are encrypted with",
+        sql: r#"
+ALTER TABLE vault_metadata ADD COLUMN cipher TEXT NOT NULL DEFAULT 'chacha20-poly1305';
+"#,
+        down_sql: Some(r#"
+ALTER TABLE vault_metadata DROP COLUMN cipher;
+"#),
+        objects: &["vault_metadata.cipher"],
+    },
+    Migration {
+        version: 9,
+        description: "Soft-deleted entries",
+        sql: r#"
+ALTER TABLE password_entries ADD COLUMN deleted_at TEXT;
+"#,
+        down_sql: Some(r#"
+-- Trashed entries become live again rather than being purged
+ALTER TABLE password_entries DROP COLUMN deleted_at;
+"#),
+        objects: &["password_entries.deleted_at"],
+    },
+    Migration {
+        version: 10,
+        description: "Per-vault Argon2 parameters",
+        sql: r#"
+ALTER TABLE vault_metadata ADD COLUMN kdf_params TEXT;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE vault_metadata DROP COLUMN kdf_params;
+"#),
+        objects: &["vault_metadata.kdf_params"],
+    },
+    Migration {
+        version: 11,
+        description: "Password history",
+        sql: r#"
+CREATE TABLE password_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_id TEXT NOT NULL REFERENCES password_entries(id) ON DELETE CASCADE,
+    encrypted_password BLOB NOT NULL,
+    changed_at TEXT NOT NULL
+);
+CREATE INDEX idx_password_history_entry ON password_history(entry_id);
+"#,
+        down_sql: Some(r#"
+DROP TABLE password_history;
+"#),
+        objects: &["password_history", "idx_password_history_entry"],
+    },
+    Migration {
+        version: 12,
+        description: "Entry tags",
+        sql: r#"
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE entry_tags (
+    entry_id TEXT NOT NULL REFERENCES password_entries(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (entry_id, tag_id)
+);
+CREATE INDEX idx_entry_tags_tag ON entry_tags(tag_id);
+"#,
+        down_sql: Some(r#"
+DROP TABLE entry_tags;
+DROP TABLE tags;
+"#),
+        objects: &["tags", "entry_tags", "idx_entry_tags_tag"],
+    },
+    Migration {
+        version: 13,
+        description: "Full-text search index",
+        sql: r#"
+-- Standalone rather than external-content: password_entries has no INTEGER
+-- PRIMARY KEY, so its rowids aren't stable across VACUUM
+CREATE VIRTUAL TABLE password_entries_fts USING fts5(entry_id UNINDEXED, title, url, notes);
+INSERT INTO password_entries_fts (entry_id, title, url, notes)
+    SELECT id, title, url, notes FROM password_entries;
+
+CREATE TRIGGER password_entries_fts_insert AFTER INSERT ON password_entries BEGIN
+    INSERT INTO password_entries_fts (entry_id, title, url, notes)
+        VALUES (new.id, new.title, new.url, new.notes);
+END;
+CREATE TRIGGER password_entries_fts_update AFTER UPDATE OF id, title, url, notes ON password_entries BEGIN
+    DELETE FROM password_entries_fts WHERE entry_id = old.id;
+    INSERT INTO password_entries_fts (entry_id, title, url, notes)
+        VALUES (new.id, new.title, new.url, new.notes);
+END;
+CREATE TRIGGER password_entries_fts_delete AFTER DELETE ON password_entries BEGIN
+    DELETE FROM password_entries_fts WHERE entry_id = old.id;
+END;
+"#,
+        down_sql: Some(r#"
+DROP TRIGGER password_entries_fts_delete;
+DROP TRIGGER password_entries_fts_update;
+DROP TRIGGER password_entries_fts_insert;
+DROP TABLE password_entries_fts;
+"#),
+        objects: &[
+            "password_entries_fts",
+            "password_entries_fts_insert",
+            "password_entries_fts_update",
+            "password_entries_fts_delete",
+        ],
+    },
+    Migration {
+        version: 14,
+        description: "Key check tag",
+        sql: r#"
+-- HMAC of a fixed string under the vault key; NULL until the next unlock
+ALTER TABLE vault_metadata ADD COLUMN key_check BLOB;
+"#,
+        down_sql: Some(r#"
+ALTER TABLE vault_metadata DROP COLUMN key_check;
+"#),
+        objects: &["vault_metadata.key_check"],
     },
 ];
 
@@ -59,17 +299,35 @@ impl<'a> MigrationRunner<'a> {
         Self { conn }
     }
 
+    /// Newest schema version this build knows
+    pub fn latest_version() -> u32 {
+        MIGRATIONS.iter().map(|m| m.version).max().unwrap_or(0)
+    }
+
+    /// Whether migrations newer than the database's schema exist
+    pub fn has_pending(&self) -> Result<bool> {
+        Ok(self.get_current_version()? < Self::latest_version())
+    }
+
+    /// Fail if the database was migrated by a newer passman than this one
+    pub fn check_supported(&self) -> Result<()> {
+        let current = self.get_current_version()?;
+        let latest = Self::latest_version();
+        if current > latest {
+            return Err(Error::IncompatibleVault(format!(
+                "database schema v{} is newer than supported v{}; upgrade passman",
+                current, latest
+            )));
+        }
+        Ok(())
+    }
+
     /// Run all pending migrations
+    ///
+    /// Refuses to touch a database whose schema is newer than this build's.
     pub fn migrate(&self) -> Result<()> {
-        // Create migration table if it doesn't exist
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS migrations (
-                version INTEGER PRIMARY KEY,
-                description TEXT NOT NULL,
-                applied_at TEXT NOT NULL
-            )",
-            [],
-        )?;
+        self.ensure_migrations_table()?;
+        self.check_supported()?;
 
         // Get current version
         let current_version = self.get_current_version()?;
@@ -77,7 +335,7 @@ impl<'a> MigrationRunner<'a> {
         // Apply pending migrations
         for migration in MIGRATIONS {
             if migration.version > current_version {
-                println!("Applying migration {}: {}", migration.version, migration.description);
+                log::info!("Applying migration {}: {}", migration.version, migration.description);
                 self.apply_migration(migration)?;
             }
         }
@@ -85,7 +343,154 @@ impl<'a> MigrationRunner<'a> {
         Ok(())
     }
 
-    fn get_current_version(&self) -> Result<u32> {
+    /// Check that the migrations table matches the schema objects actually present
+    pub fn verify(&self) -> Result<Vec<MigrationIssue>> {
+        self.ensure_migrations_table()?;
+
+        let recorded = self.recorded_versions()?;
+        let mut issues = Vec::new();
+
+        for migration in MIGRATIONS {
+            let missing = self.missing_objects(migration)?;
+            let is_recorded = recorded.contains(&migration.version);
+
+            if is_recorded && !missing.is_empty() {
+                issues.push(MigrationIssue::MissingObjects {
+                    version: migration.version,
+                    objects: missing,
+                });
+            } else if !is_recorded && missing.is_empty() {
+                issues.push(MigrationIssue::Unrecorded {
+                    version: migration.version,
+                });
+            }
+        }
+
+        for version in recorded {
+            if !MIGRATIONS.iter().any(|m| m.version == version) {
+                issues.push(MigrationIssue::Unknown { version });
+            }
+        }
+
+        Ok(issues)
+    }
+
+    /// Reconcile the migrations table with the schema, returning the issues fixed
+    ///
+    /// Unrecorded migrations whose objects all exist are recorded, and recorded
+    /// migrations whose objects are all missing are unrecorded so they re-run.
+    /// Partially applied migrations are left for manual intervention.
+    pub fn repair(&self) -> Result<Vec<MigrationIssue>> {
+        let mut repaired = Vec::new();
+
+        for issue in self.verify()? {
+            match &issue {
+                MigrationIssue::Unrecorded { version } => {
+                    let migration = MIGRATIONS.iter().find(|m| m.version == *version);
+                    if let Some(migration) = migration {
+                        Self::record_migration(self.conn, migration)?;
+                        repaired.push(issue);
+                    }
+                }
+                MigrationIssue::MissingObjects { version, objects } => {
+                    let migration = MIGRATIONS.iter().find(|m| m.version == *version);
+                    if migration.is_some_and(|m| m.objects.len() == objects.len()) {
+                        self.conn.execute(
+                            "DELETE FROM migrations WHERE version = ?1",
+                            params![version],
+                        )?;
+                        repaired.push(issue);
+                    }
+                }
+                MigrationIssue::Unknown { .. } => {}
+            }
+        }
+
+        Ok(repaired)
+    }
+
+    /// Undo every applied migration newer than `version`, newest first
+    ///
+    /// Runs in one transaction, so either all of them are rolled back or none
+    /// are. Returns the migrations that were undone. The base schema
+    /// (version 1) can't be rolled back.
+    pub fn rollback_to(&self, version: u32) -> Result<Vec<&'static Migration>> {
+        if version < 1 {
+            return Err(Error::InvalidInput("Can't roll back past version 1, the base schema".to_string()));
+        }
+        self.ensure_migrations_table()?;
+
+        let mut undo = Vec::new();
+        for recorded in self.recorded_versions()?.into_iter().rev().filter(|v| *v > version) {
+            let migration = MIGRATIONS
+                .iter()
+                .find(|m| m.version == recorded)
+                .ok_or_else(|| Error::InvalidInput(format!("Migration {} is unknown to this version", recorded)))?;
+            if migration.down_sql.is_none() {
+                return Err(Error::InvalidInput(format!("Migration {} can't be rolled back", recorded)));
+            }
+            undo.push(migration);
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        for migration in &undo {
+            log::info!("Rolling back migration {}: {}", migration.version, migration.description);
+            tx.execute_batch(migration.down_sql.unwrap_or_default())?;
+            tx.execute("DELETE FROM migrations WHERE version = ?1", params![migration.version])?;
+        }
+        tx.commit()?;
+
+        Ok(undo)
+    }
+
+    fn ensure_migrations_table(&self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS migrations (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    fn recorded_versions(&self) -> Result<Vec<u32>> {
+        let mut stmt = self.conn.prepare("SELECT version FROM migrations ORDER BY version")?;
+        let versions = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<u32>, _>>()?;
+
+        Ok(versions)
+    }
+
+    fn missing_objects(&self, migration: &Migration) -> Result<Vec<String>> {
+        let mut missing = Vec::new();
+
+        for object in migration.objects {
+            let count: i64 = match object.split_once('.') {
+                Some((table, column)) => self.conn.query_row(
+                    "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+                    params![table, column],
+                    |row| row.get(0),
+                )?,
+                None => self.conn.query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+                    params![object],
+                    |row| row.get(0),
+                )?,
+            };
+
+            if count == 0 {
+                missing.push(object.to_string());
+            }
+        }
+
+        Ok(missing)
+    }
+
+    pub fn get_current_version(&self) -> Result<u32> {
         let version = self.conn
             .query_row(
                 "SELECT MAX(version) FROM migrations",
@@ -106,14 +511,111 @@ impl<'a> MigrationRunner<'a> {
         tx.execute_batch(migration.sql)?;
         
         // Record migration
-        tx.execute(
-            "INSERT INTO migrations (version, description, applied_at) VALUES (?1, ?2, datetime('now'))",
-            rusqlite::params![migration.version, migration.description],
-        )?;
+        Self::record_migration(&tx, migration)?;
         
         // Commit transaction
         tx.commit()?;
         
         Ok(())
     }
+
+    fn record_migration(conn: &Connection, migration: &Migration) -> Result<()> {
+        conn.execute(
+            "INSERT INTO migrations (version, description, applied_at) VALUES (?1, ?2, datetime('now'))",
+            params![migration.version, migration.description],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_consistent_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        let runner = MigrationRunner::new(&conn);
+        runner.migrate().unwrap();
+
+        assert!(runner.verify().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_reports_deleted_migration_row() {
+        let conn = Connection::open_in_memory().unwrap();
+        let runner = MigrationRunner::new(&conn);
+        runner.migrate().unwrap();
+
+        conn.execute("DELETE FROM migrations WHERE version = 1", []).unwrap();
+
+        let issues = runner.verify().unwrap();
+        assert_eq!(issues, vec![MigrationIssue::Unrecorded { version: 1 }]);
+
+        let repaired = runner.repair().unwrap();
+        assert_eq!(repaired, issues);
+        assert!(runner.verify().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_reports_missing_objects() {
+        let conn = Connection::open_in_memory().unwrap();
+        let runner = MigrationRunner::new(&conn);
+        runner.migrate().unwrap();
+
+        conn.execute("DROP INDEX idx_password_entries_url", []).unwrap();
+
+        let issues = runner.verify().unwrap();
+        assert_eq!(
+            issues,
+            vec![MigrationIssue::MissingObjects {
+                version: 1,
+                objects: vec!["idx_password_entries_url".to_string()],
+            }]
+        );
+
+        // Partially applied migrations are not repaired automatically
+        assert!(runner.repair().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rollback_undoes_migrations_in_reverse() {
+        let conn = Connection::open_in_memory().unwrap();
+        let runner = MigrationRunner::new(&conn);
+        runner.migrate().unwrap();
+        let latest = runner.get_current_version().unwrap();
+
+        let undone = runner.rollback_to(1).unwrap();
+        assert_eq!(undone.first().map(|m| m.version), Some(latest));
+        assert_eq!(undone.last().map(|m| m.version), Some(2));
+        assert_eq!(runner.get_current_version().unwrap(), 1);
+        assert!(runner.verify().unwrap().is_empty());
+
+        // Every down migration leaves a schema the up migrations apply to again
+        runner.migrate().unwrap();
+        assert_eq!(runner.get_current_version().unwrap(), latest);
+        assert!(runner.verify().unwrap().is_empty());
+
+        assert!(runner.rollback_to(latest).unwrap().is_empty());
+        assert!(matches!(runner.rollback_to(0), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_migrate_refuses_newer_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        let runner = MigrationRunner::new(&conn);
+        runner.migrate().unwrap();
+
+        let future = MigrationRunner::latest_version() + 1;
+        conn.execute(
+            "INSERT INTO migrations (version, description, applied_at) VALUES (?1, 'From the future', datetime('now'))",
+            params![future],
+        )
+        .unwrap();
+
+        let err = runner.migrate().unwrap_err();
+        assert!(matches!(err, Error::IncompatibleVault(_)));
+        assert!(err.to_string().contains(&format!("v{} is newer than supported v{}", future, future - 1)));
+    }
 }
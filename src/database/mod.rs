@@ -1,6 +1,10 @@
+pub mod diff;
 pub mod migrations;
 pub mod models;
 pub mod repository;
+pub mod shared;
+pub mod templates;
 
 pub use models::*;
 pub use repository::*;
+pub use shared::SharedRepository;
@@ -23,6 +23,11 @@ pub struct PasswordEntry {
     pub created_at: DateTime<Utc>,
     /// Last update timestamp
     pub updated_at: DateTime<Utc>,
+    /// Timestamp of the last password change
+    pub password_changed_at: DateTime<Utc>,
+    /// Tags, sorted by name
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 /// Secure string that zeros memory on drop
@@ -63,6 +68,311 @@ impl From<&str> for SecureString {
     }
 }
 
+/// State of a database file as seen before initializing a vault in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseState {
+    /// No file exists at the path
+    Missing,
+    /// The file exists but contains no tables
+    Empty,
+    /// The passman schema exists but no vault has been initialized
+    Uninitialized,
+    /// A vault has already been initialized
+    Initialized,
+    /// The file looks like an encrypted database; its contents can't be
+    /// inspected without the master password
+    Encrypted,
+    /// The file is not a passman database
+    Foreign,
+}
+
+impl DatabaseState {
+    /// Check whether a vault may be initialized over this database
+    pub fn check_initializable(self, force: bool) -> crate::Result<()> {
+        match self {
+            DatabaseState::Initialized | DatabaseState::Encrypted if !force => Err(crate::Error::VaultAlreadyExists),
+            DatabaseState::Foreign => Err(crate::Error::InvalidInput(
+                "Database file exists but is not a passman vault; refusing to modify it".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How a custom field's value is entered and displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldKind {
+    /// Plain text, echoed when entered
+    Text,
+    /// Sensitive value, entered without echo and hidden by default
+    Secret,
+    /// Contents of a file, entered as a path
+    File,
+}
+
+impl FieldKind {
+    /// Name stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FieldKind::Text => "text",
+            FieldKind::Secret => "secret",
+            FieldKind::File => "file",
+        }
+    }
+
+    /// Parse a name stored in the database
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "text" => Some(FieldKind::Text),
+            "secret" => Some(FieldKind::Secret),
+            "file" => Some(FieldKind::File),
+            _ => None,
+        }
+    }
+}
+
+/// Additional named field attached to an entry, stored encrypted
+#[derive(Debug, Clone)]
+pub struct CustomField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub value: SecureString,
+}
+
+/// Change to an optional entry field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FieldUpdate {
+    /// Leave the current value unchanged
+    #[default]
+    Keep,
+    /// Replace the value
+    Set(String),
+    /// Reset the field to NULL
+    Clear,
+}
+
+impl FieldUpdate {
+    fn apply(&self, field: &mut Option<String>) {
+        match self {
+            FieldUpdate::Keep => {}
+            FieldUpdate::Set(value) => *field = Some(value.clone()),
+            FieldUpdate::Clear => *field = None,
+        }
+    }
+}
+
+/// Metadata changes to apply to an entry; unspecified fields are left unchanged
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateSpec {
+    pub title: Option<String>,
+    pub username: Option<String>,
+    pub url: FieldUpdate,
+    pub notes: FieldUpdate,
+}
+
+impl UpdateSpec {
+    /// Whether the spec changes nothing
+    pub fn is_empty(&self) -> bool {
+        *self == UpdateSpec::default()
+    }
+
+    /// Apply the changes to an entry, refreshing its `updated_at`
+    pub fn apply(&self, entry: &mut PasswordEntry) {
+        if let Some(title) = &self.title {
+            entry.title = title.clone();
+        }
+        if let Some(username) = &self.username {
+            entry.username = username.clone();
+        }
+        self.url.apply(&mut entry.url);
+        self.notes.apply(&mut entry.notes);
+        entry.touch();
+    }
+}
+
+/// Summary counts over a vault's entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryStats {
+    pub total: u64,
+    pub with_url: u64,
+    pub with_notes: u64,
+    pub with_totp: u64,
+    pub with_custom_fields: u64,
+    /// Entries whose password is empty
+    pub missing_password: u64,
+    /// Earliest and latest `updated_at`, `None` for an empty vault
+    pub oldest_update: Option<DateTime<Utc>>,
+    pub newest_update: Option<DateTime<Utc>>,
+    /// Mean of the entries' `password_changed_at`; the average password age is the time since
+    pub mean_password_changed_at: Option<DateTime<Utc>>,
+    /// Number of entries with each tag
+    pub per_tag: std::collections::BTreeMap<String, u64>,
+}
+
+/// Plaintext entry fields that searches can match against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SearchField {
+    Title,
+    Username,
+    Url,
+    Notes,
+}
+
+impl SearchField {
+    /// Every searchable field, the default search scope
+    pub const ALL: [SearchField; 4] = [
+        SearchField::Title,
+        SearchField::Username,
+        SearchField::Url,
+        SearchField::Notes,
+    ];
+
+    /// Column backing this field in `password_entries`
+    pub fn column(self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Username => "username",
+            SearchField::Url => "url",
+            SearchField::Notes => "notes",
+        }
+    }
+
+    /// This field's value on an entry
+    pub fn value(self, entry: &PasswordEntry) -> Option<&str> {
+        match self {
+            SearchField::Title => Some(&entry.title),
+            SearchField::Username => Some(&entry.username),
+            SearchField::Url => entry.url.as_deref(),
+            SearchField::Notes => entry.notes.as_deref(),
+        }
+    }
+
+    /// Whether this field contains `query`, ignoring ASCII case like SQL `LIKE`
+    pub fn matches(self, entry: &PasswordEntry, query: &str) -> bool {
+        self.value(entry)
+            .is_some_and(|value| value.to_ascii_lowercase().contains(&query.to_ascii_lowercase()))
+    }
+
+    /// Whether this field matches a search pattern
+    pub fn matches_pattern(self, entry: &PasswordEntry, pattern: &SearchPattern) -> bool {
+        self.value(entry).is_some_and(|value| pattern.is_match(value))
+    }
+}
+
+/// How a search query is matched against field values
+#[derive(Debug, Clone)]
+pub enum SearchPattern {
+    /// Substring match ignoring ASCII case, like SQL `LIKE`
+    Literal(String),
+    Regex(regex::Regex),
+}
+
+impl SearchPattern {
+    /// Compile a regular expression query
+    pub fn regex(pattern: &str) -> crate::Result<Self> {
+        regex::Regex::new(pattern)
+            .map(SearchPattern::Regex)
+            .map_err(|e| crate::Error::InvalidInput(format!("Invalid search regex: {}", e)))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            SearchPattern::Literal(query) => text.to_ascii_lowercase().contains(&query.to_ascii_lowercase()),
+            SearchPattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+
+    /// Byte ranges of every non-overlapping match in `text`, for highlighting
+    pub fn find_ranges(&self, text: &str) -> Vec<std::ops::Range<usize>> {
+        match self {
+            SearchPattern::Literal(query) if query.is_empty() => Vec::new(),
+            SearchPattern::Literal(query) => {
+                // ASCII lowercasing keeps byte offsets intact
+                let haystack = text.to_ascii_lowercase();
+                let needle = query.to_ascii_lowercase();
+                haystack.match_indices(&needle).map(|(start, m)| start..start + m.len()).collect()
+            }
+            SearchPattern::Regex(regex) => regex
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+        }
+    }
+}
+
+/// Single entry field that can be printed on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum EntryField {
+    Username,
+    Password,
+    Url,
+    Notes,
+}
+
+impl EntryField {
+    pub fn name(self) -> &'static str {
+        match self {
+            EntryField::Username => "username",
+            EntryField::Password => "password",
+            EntryField::Url => "url",
+            EntryField::Notes => "notes",
+        }
+    }
+
+    /// Whether copies of this field should be cleared from the clipboard again
+    pub fn is_sensitive(self) -> bool {
+        matches!(self, EntryField::Password | EntryField::Notes)
+    }
+
+    /// This field's value on a decrypted entry
+    pub fn value(self, entry: &PasswordEntry) -> Option<&str> {
+        match self {
+            EntryField::Username => Some(&entry.username),
+            EntryField::Password => Some(entry.password.as_str()),
+            EntryField::Url => entry.url.as_deref(),
+            EntryField::Notes => entry.notes.as_deref(),
+        }
+    }
+}
+
+/// A password an entry had before it was changed
+#[derive(Debug, Clone)]
+pub struct PasswordHistoryEntry {
+    /// When this password was replaced
+    pub changed_at: DateTime<Utc>,
+    pub encrypted_password: Vec<u8>,
+}
+
+/// Outcome of `PasswordRepository::rotate_passwords`
+#[derive(Debug, Default)]
+pub struct RotationReport {
+    pub rotated: Vec<PasswordEntry>,
+    /// Entries left alone because their password couldn't be decrypted
+    pub failed: Vec<(PasswordEntry, crate::Error)>,
+}
+
+/// Result of a deep search, see `PasswordRepository::deep_search`
+#[derive(Debug, Clone)]
+pub struct DeepMatch {
+    pub entry: PasswordEntry,
+    /// Encrypted fields whose decrypted contents matched the query
+    pub encrypted_fields: Vec<String>,
+}
+
+impl PasswordEntry {
+    /// Which of `fields` contain `query`, for explaining search results
+    pub fn matched_fields(&self, query: &str, fields: &[SearchField]) -> Vec<SearchField> {
+        fields.iter().copied().filter(|field| field.matches(self, query)).collect()
+    }
+
+    /// Which of `fields` match a search pattern
+    pub fn fields_matching(&self, pattern: &SearchPattern, fields: &[SearchField]) -> Vec<SearchField> {
+        fields.iter().copied().filter(|field| field.matches_pattern(self, pattern)).collect()
+    }
+}
+
 /// Database schema version for migrations
 #[derive(Debug, Clone)]
 pub struct SchemaVersion {
@@ -79,6 +389,8 @@ pub struct VaultMetadata {
     pub last_access: DateTime<Utc>,
     /// Schema version
     pub schema_version: u32,
+    /// On-disk encryption format version
+    pub format_version: u32,
     /// Salt for key derivation
     pub salt: Vec<u8>,
     /// Password verification hash
@@ -104,13 +416,29 @@ impl PasswordEntry {
             notes,
             created_at: now,
             updated_at: now,
+            password_changed_at: now,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Normalize a tag name: trimmed and lowercase, rejecting empty names
+    pub fn normalize_tag(tag: &str) -> crate::Result<String> {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            return Err(crate::Error::InvalidInput("Tag names can't be empty".to_string()));
         }
+        Ok(tag)
     }
 
     /// Update the entry's timestamp
     pub fn touch(&mut self) {
         self.updated_at = Utc::now();
     }
+
+    /// Leading characters of the entry ID for display, like a git short hash
+    pub fn short_id(&self, length: usize) -> String {
+        self.id.simple().to_string().chars().take(length).collect()
+    }
 }
 
 impl VaultMetadata {
@@ -121,6 +449,7 @@ impl VaultMetadata {
             created_at: now,
             last_access: now,
             schema_version: 1,
+            format_version: crate::crypto::VAULT_FORMAT_VERSION,
             salt,
             password_hash,
         }
@@ -130,4 +459,24 @@ impl VaultMetadata {
     pub fn update_access(&mut self) {
         self.last_access = Utc::now();
     }
+
+    /// Check that this build can read the vault's encryption format
+    pub fn check_format(&self) -> crate::Result<()> {
+        use std::cmp::Ordering;
+
+        let supported = crate::crypto::VAULT_FORMAT_VERSION;
+        match self.format_version.cmp(&supported) {
+            Ordering::Equal => Ok(()),
+            Ordering::Less if self.format_version >= crate::crypto::OLDEST_VAULT_FORMAT_VERSION => Ok(()),
+            Ordering::Greater => Err(crate::Error::IncompatibleVault(format!(
+                "vault uses encryption format {} but this build only supports format {}; please upgrade passman",
+                self.format_version, supported
+            ))),
+            Ordering::Less => Err(crate::Error::IncompatibleVault(format!(
+                "vault uses legacy encryption format {} (current is {}); please migrate it with a release that \
+                 supports both formats before using this version",
+                self.format_version, supported
+            ))),
+        }
+    }
 }
@@ -1,24 +1,61 @@
+use crate::config::PasswordGenerationConfig;
+use crate::crypto::{Argon2Params, Cipher, EncryptionManager, PasswordManager};
 use crate::database::{models::*, migrations::MigrationRunner};
+use crate::utils::PasswordGenerator;
 use crate::{Error, Result};
-use chrono::Utc;
-use rusqlite::{params, Connection, Row};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags, Row};
 use uuid::Uuid;
 use std::path::Path;
+use zeroize::Zeroize;
+
+/// Columns selected for password entries, in the order `row_to_entry` expects
+///
+/// Tags are joined with the ASCII unit separator, which can't be typed into a tag.
+const ENTRY_COLUMNS: &str =
+    "id, title, username, encrypted_password, url, notes, created_at, updated_at, password_changed_at,
+     (SELECT group_concat(name, char(31)) FROM (
+          SELECT tags.name FROM entry_tags JOIN tags ON tags.id = entry_tags.tag_id
+          WHERE entry_tags.entry_id = password_entries.id ORDER BY tags.name
+      )) AS tags";
+
+/// Separator between tag names in the `tags` column of `ENTRY_COLUMNS`
+const TAG_SEPARATOR: char = '\u{1f}';
+
+/// Previous passwords kept per entry unless `set_history_limit` says otherwise
+pub const DEFAULT_HISTORY_LIMIT: usize = 10;
+
+/// First bytes of every unencrypted SQLite database file
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
 
 /// Database repository for password management
 pub struct PasswordRepository {
     conn: Connection,
+    history_limit: usize,
 }
 
 impl PasswordRepository {
-    /// Create a new repository with database at given path
+    /// Create a new repository with an unencrypted database at given path
+    ///
+    /// Used for in-memory databases and tests; vault files are opened with `open`.
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
-        let conn = Connection::open(db_path.as_ref())?;
-        
+        Self::with_connection(Connection::open(db_path.as_ref())?)
+    }
+
+    /// Open the database at `db_path` encrypted with SQLCipher under `password`
+    ///
+    /// A missing file is created encrypted. Vaults created before database
+    /// encryption are still plain SQLite files; those are opened without a key
+    /// and can be converted with `encrypt_file`.
+    pub fn open<P: AsRef<Path>>(db_path: P, password: &str) -> Result<Self> {
+        Self::with_connection(Self::connect(db_path.as_ref(), OpenFlags::default(), Some(password))?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
         // Enable foreign keys
         conn.execute("PRAGMA foreign_keys = ON", [])?;
         
-        let repo = Self { conn };
+        let repo = Self { conn, history_limit: DEFAULT_HISTORY_LIMIT };
         
         // Run migrations
         let migration_runner = MigrationRunner::new(&repo.conn);
@@ -27,14 +64,162 @@ impl PasswordRepository {
         Ok(repo)
     }
 
+    /// Open a raw connection, keying it with `password` unless the file is plain SQLite
+    ///
+    /// The key is applied before anything is read, then checked by reading the
+    /// schema so a wrong password fails with `Error::Authentication` here
+    /// rather than with an obscure error on the first query.
+    pub fn connect(db_path: &Path, flags: OpenFlags, password: Option<&str>) -> Result<Connection> {
+        let conn = Connection::open_with_flags(db_path, flags)?;
+
+        if let Some(password) = password {
+            if !Self::is_plaintext_file(db_path)? {
+                // SQLCipher derives the page key from the passphrase with its own KDF
+                conn.pragma_update(None, "key", password)?;
+            }
+        }
+
+        match conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+            Ok(_) => Ok(conn),
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => Err(
+                Error::Authentication("Wrong master password, or the file is not a passman vault".to_string()),
+            ),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether the file at `db_path` is an unencrypted SQLite database
+    pub fn is_plaintext_file(db_path: &Path) -> Result<bool> {
+        use std::io::Read;
+
+        let mut header = [0u8; 16];
+        match std::fs::File::open(db_path) {
+            Ok(mut file) => Ok(file.read_exact(&mut header).is_ok() && &header == SQLITE_HEADER),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Convert an unencrypted vault file into one encrypted under `password`
+    ///
+    /// The encrypted copy is written next to the original and then renamed
+    /// over it, so an interruption leaves the original intact.
+    pub fn encrypt_file(db_path: &Path, password: &str) -> Result<()> {
+        if !Self::is_plaintext_file(db_path)? {
+            return Err(Error::InvalidInput(format!("{} is not an unencrypted database", db_path.display())));
+        }
+
+        let mut encrypted_path = db_path.as_os_str().to_owned();
+        encrypted_path.push(".encrypting");
+        let encrypted_path = std::path::PathBuf::from(encrypted_path);
+        if encrypted_path.exists() {
+            std::fs::remove_file(&encrypted_path)?;
+        }
+
+        let conn = Connection::open(db_path)?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted_path.to_string_lossy(), password],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+        conn.execute("DETACH DATABASE encrypted", [])?;
+        drop(conn);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&encrypted_path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&encrypted_path, db_path)?;
+        Ok(())
+    }
+
+    /// Open an existing database read-only, e.g. for audits and exports
+    ///
+    /// No migrations are run and nothing is ever written, so this can run
+    /// alongside another process that holds the vault open for writing. The
+    /// database must already be at the current schema version.
+    pub fn open_readonly<P: AsRef<Path>>(db_path: P, password: &str) -> Result<Self> {
+        let conn = Self::connect(
+            db_path.as_ref(),
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            Some(password),
+        )?;
+
+        let runner = MigrationRunner::new(&conn);
+        runner.check_supported()?;
+        if runner.has_pending()? {
+            return Err(Error::IncompatibleVault(
+                "database schema is out of date; open it normally once to migrate it".to_string(),
+            ));
+        }
+
+        Ok(Self { conn, history_limit: DEFAULT_HISTORY_LIMIT })
+    }
+
+    /// Whether this repository was opened read-only
+    pub fn is_readonly(&self) -> bool {
+        self.conn.is_readonly(rusqlite::DatabaseName::Main).unwrap_or(false)
+    }
+
+    /// Inspect a database file without creating or modifying it
+    pub fn inspect<P: AsRef<Path>>(db_path: P) -> Result<DatabaseState> {
+        let db_path = db_path.as_ref();
+        if !db_path.exists() {
+            return Ok(DatabaseState::Missing);
+        }
+        let len = std::fs::metadata(db_path)?.len();
+        if len == 0 {
+            return Ok(DatabaseState::Empty);
+        }
+        if !Self::is_plaintext_file(db_path)? {
+            // SQLCipher files are whole pages of ciphertext; anything else isn't a database
+            return Ok(if len % 512 == 0 { DatabaseState::Encrypted } else { DatabaseState::Foreign });
+        }
+
+        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let tables = match conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<std::result::Result<Vec<_>, _>>()
+            }) {
+            Ok(tables) => tables,
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::NotADatabase =>
+            {
+                return Ok(DatabaseState::Foreign)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if tables.is_empty() {
+            return Ok(DatabaseState::Empty);
+        }
+
+        let has_schema = ["migrations", "vault_metadata", "password_entries"]
+            .iter()
+            .all(|table| tables.iter().any(|t| t == table));
+        if !has_schema {
+            return Ok(DatabaseState::Foreign);
+        }
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM vault_metadata", [], |row| row.get(0))?;
+        if count > 0 {
+            Ok(DatabaseState::Initialized)
+        } else {
+            Ok(DatabaseState::Uninitialized)
+        }
+    }
+
     /// Initialize vault with master password hash and salt
     pub fn initialize_vault(&self, salt: Vec<u8>, password_hash: Vec<u8>) -> Result<()> {
         let now = Utc::now().to_rfc3339();
         
         self.conn.execute(
-            "INSERT INTO vault_metadata (id, created_at, last_access, schema_version, salt, password_hash)
-             VALUES (1, ?1, ?2, 1, ?3, ?4)",
-            params![now, now, salt, password_hash],
+            "INSERT INTO vault_metadata (id, created_at, last_access, schema_version, salt, password_hash, format_version)
+             VALUES (1, ?1, ?2, 1, ?3, ?4, ?5)",
+            params![now, now, salt, password_hash, crate::crypto::VAULT_FORMAT_VERSION],
         )?;
         
         Ok(())
@@ -54,7 +239,7 @@ impl PasswordRepository {
     /// Get vault metadata
     pub fn get_vault_metadata(&self) -> Result<VaultMetadata> {
         self.conn.query_row(
-            "SELECT created_at, last_access, schema_version, salt, password_hash
+            "SELECT created_at, last_access, schema_version, salt, password_hash, format_version
              FROM vault_metadata WHERE id = 1",
             [],
             |row| {
@@ -68,12 +253,291 @@ impl PasswordRepository {
                     schema_version: row.get(2)?,
                     salt: row.get(3)?,
                     password_hash: row.get(4)?,
+                    format_version: row.get(5)?,
                 })
             },
         )
         .map_err(Error::from)
     }
 
+    /// Store generator defaults for this vault, overriding the global config
+    pub fn set_generator_defaults(&self, defaults: &PasswordGenerationConfig) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE vault_metadata SET generator_defaults = ?1 WHERE id = 1",
+            params![serde_json::to_string(defaults)?],
+        )?;
+
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Generator defaults stored for this vault, if any
+    pub fn get_generator_defaults(&self) -> Result<Option<PasswordGenerationConfig>> {
+        let stored: Option<String> = self.conn
+            .query_row("SELECT generator_defaults FROM vault_metadata WHERE id = 1", [], |row| row.get(0))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+
+        Ok(stored.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    /// This vault's generator defaults, or `fallback` when none are stored
+    pub fn generator_defaults_or(&self, fallback: &PasswordGenerationConfig) -> Result<PasswordGenerationConfig> {
+        Ok(self.get_generator_defaults()?.unwrap_or_else(|| fallback.clone()))
+    }
+
+    /// Record the encryption format the vault's ciphertexts are written in
+    pub fn set_format_version(&self, version: u32) -> Result<()> {
+        self.conn.execute("UPDATE vault_metadata SET format_version = ?1 WHERE id = 1", params![version])?;
+        Ok(())
+    }
+
+    /// The cipher this vault's entries are encrypted with
+    pub fn cipher(&self) -> Result<Cipher> {
+        let cipher: Option<String> = self.conn
+            .query_row("SELECT cipher FROM vault_metadata WHERE id = 1", [], |row| row.get(0))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+
+        cipher.map_or(Ok(Cipher::default()), |cipher| Cipher::parse(&cipher))
+    }
+
+    /// Choose the cipher for a vault that has no secrets yet
+    ///
+    /// Existing ciphertexts would become unreadable, so a vault with entries
+    /// must be migrated with `reencrypt` instead.
+    pub fn set_cipher(&self, cipher: Cipher) -> Result<()> {
+        let entries: i64 = self.conn.query_row("SELECT COUNT(*) FROM password_entries", [], |row| row.get(0))?;
+        if entries > 0 {
+            return Err(Error::InvalidInput(
+                "The vault already has entries; use `reencrypt` to change its cipher".to_string(),
+            ));
+        }
+
+        let updated = self.conn.execute("UPDATE vault_metadata SET cipher = ?1 WHERE id = 1", params![cipher.as_str()])?;
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Argon2 parameters this vault's key is derived with
+    ///
+    /// Vaults created before the parameters were stored use the defaults.
+    pub fn kdf_params(&self) -> Result<Argon2Params> {
+        let stored: Option<String> = self.conn
+            .query_row("SELECT kdf_params FROM vault_metadata WHERE id = 1", [], |row| row.get(0))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+
+        Ok(stored.map(|json| serde_json::from_str(&json)).transpose()?.unwrap_or_default())
+    }
+
+    /// Record the Argon2 parameters for this vault
+    ///
+    /// Only meaningful alongside a fresh salt and hash derived with them.
+    pub fn set_kdf_params(&self, kdf: &Argon2Params) -> Result<()> {
+        kdf.validate()?;
+        let updated = self.conn.execute(
+            "UPDATE vault_metadata SET kdf_params = ?1 WHERE id = 1",
+            params![serde_json::to_string(kdf)?],
+        )?;
+
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Password manager using this vault's Argon2 parameters
+    pub fn password_manager(&self) -> Result<PasswordManager> {
+        PasswordManager::with_params(self.kdf_params()?)
+    }
+
+    /// Encryption manager for this vault's cipher
+    pub fn encryption_manager(&self) -> Result<EncryptionManager> {
+        Ok(EncryptionManager::with_cipher(self.cipher()?))
+    }
+
+    /// Re-encrypt every secret in the vault under another cipher in one transaction
+    ///
+    /// Passwords, TOTP secrets and custom field values are decrypted with the
+    /// vault's current cipher and written back under `to`, which then becomes
+    /// the vault's cipher. Returns the number of ciphertexts rewritten.
+    pub fn reencrypt(&self, key: &[u8], to: Cipher) -> Result<usize> {
+        let from = self.encryption_manager()?;
+        let target = EncryptionManager::with_cipher(to);
+        let tx = self.conn.unchecked_transaction()?;
+
+        let rewritten = Self::rewrap_secrets(&tx, |ciphertext| {
+            let mut plaintext = from.decrypt(key, ciphertext)?;
+            let encrypted = target.encrypt(key, &plaintext);
+            plaintext.zeroize();
+            encrypted
+        })?;
+
+        let updated = tx.execute("UPDATE vault_metadata SET cipher = ?1 WHERE id = 1", params![to.as_str()])?;
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+
+        tx.commit()?;
+        Ok(rewritten)
+    }
+
+    /// Re-encrypt every secret under a new key and store the matching salt and hash
+    ///
+    /// Used when the master password is rehashed or changed; everything happens
+    /// in one transaction so the vault is never left half under each key. When
+    /// `file_password` is given, an encrypted vault file is re-keyed to it in
+    /// the same transaction.
+    pub fn rekey(
+        &self,
+        old_key: &[u8],
+        new_key: &[u8],
+        salt: Vec<u8>,
+        password_hash: Vec<u8>,
+        file_password: Option<&str>,
+    ) -> Result<usize> {
+        let manager = self.encryption_manager()?;
+        let tx = self.conn.unchecked_transaction()?;
+
+        let rewritten = Self::rewrap_secrets(&tx, |ciphertext| {
+            let mut plaintext = manager.decrypt(old_key, ciphertext)?;
+            let encrypted = manager.encrypt(new_key, &plaintext);
+            plaintext.zeroize();
+            encrypted
+        })?;
+
+        let updated = tx.execute(
+            "UPDATE vault_metadata SET salt = ?1, password_hash = ?2, key_check = ?3 WHERE id = 1",
+            params![salt, password_hash, crate::crypto::key_check(new_key)],
+        )?;
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+
+        if let Some(password) = file_password.filter(|_| self.is_file_encrypted()) {
+            // SQLCipher rewrites every page under the new key as part of the open transaction
+            tx.pragma_update(None, "rekey", password)?;
+        }
+
+        tx.commit()?;
+        Ok(rewritten)
+    }
+
+    /// Whether the connection is to a SQLCipher-encrypted vault file
+    fn is_file_encrypted(&self) -> bool {
+        match self.conn.path() {
+            Some(path) if !path.is_empty() => !Self::is_plaintext_file(Path::new(path)).unwrap_or(true),
+            _ => false,
+        }
+    }
+
+    /// Rewrite every stored ciphertext through `rewrap`, returning how many were rewritten
+    fn rewrap_secrets<F>(tx: &rusqlite::Transaction, rewrap: F) -> Result<usize>
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>>,
+    {
+        let mut rewritten = 0;
+
+        let secrets: Vec<(String, Vec<u8>, Option<Vec<u8>>)> = tx
+            .prepare("SELECT id, encrypted_password, totp_secret FROM password_entries")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        for (id, password, totp_secret) in secrets {
+            let totp_secret = totp_secret.as_deref().map(&rewrap).transpose()?;
+            tx.execute(
+                "UPDATE password_entries SET encrypted_password = ?1, totp_secret = ?2 WHERE id = ?3",
+                params![rewrap(&password)?, totp_secret, id],
+            )?;
+            rewritten += 1 + usize::from(totp_secret.is_some());
+        }
+
+        let history: Vec<(i64, Vec<u8>)> = tx
+            .prepare("SELECT id, encrypted_password FROM password_history")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        for (id, password) in history {
+            tx.execute(
+                "UPDATE password_history SET encrypted_password = ?1 WHERE id = ?2",
+                params![rewrap(&password)?, id],
+            )?;
+            rewritten += 1;
+        }
+
+        let fields: Vec<(String, String, Vec<u8>)> = tx
+            .prepare("SELECT entry_id, name, encrypted_value FROM entry_fields")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        for (entry_id, name, value) in fields {
+            tx.execute(
+                "UPDATE entry_fields SET encrypted_value = ?1 WHERE entry_id = ?2 AND name = ?3",
+                params![rewrap(&value)?, entry_id, name],
+            )?;
+            rewritten += 1;
+        }
+
+        Ok(rewritten)
+    }
+
+    /// Store the duress password hash, or remove it with `None`
+    pub fn set_duress_hash(&self, hash: Option<&str>) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE vault_metadata SET duress_password_hash = ?1 WHERE id = 1",
+            params![hash.map(str::as_bytes)],
+        )?;
+
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Store the tag that recognises the vault key (see `crypto::key_check`)
+    pub fn set_key_check(&self, tag: &[u8]) -> Result<()> {
+        let updated = self.conn.execute("UPDATE vault_metadata SET key_check = ?1 WHERE id = 1", params![tag])?;
+
+        if updated == 0 {
+            return Err(Error::VaultNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// The stored key check tag; vaults created before it have none until unlocked
+    pub fn get_key_check(&self) -> Result<Option<Vec<u8>>> {
+        self.conn
+            .query_row("SELECT key_check FROM vault_metadata WHERE id = 1", [], |row| row.get(0))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(Error::from(e)),
+            })
+    }
+
+    /// The duress password hash, if one is set
+    pub fn get_duress_hash(&self) -> Result<Option<String>> {
+        let hash: Option<Vec<u8>> = self.conn
+            .query_row("SELECT duress_password_hash FROM vault_metadata WHERE id = 1", [], |row| row.get(0))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+
+        hash.map(|hash| {
+            String::from_utf8(hash)
+                .map_err(|_| Error::Crypto("Stored duress hash is not valid UTF-8".to_string()))
+        })
+        .transpose()
+    }
+
     /// Update last access time
     pub fn update_last_access(&self) -> Result<()> {
         let now = Utc::now().to_rfc3339();
@@ -88,30 +552,144 @@ impl PasswordRepository {
 
     /// Add a new password entry
     pub fn add_entry(&self, entry: &PasswordEntry, encrypted_password: &[u8]) -> Result<()> {
-        self.conn.execute(
-            "INSERT INTO password_entries 
-             (id, title, username, encrypted_password, url, notes, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![
-                entry.id.to_string(),
-                entry.title,
-                entry.username,
-                encrypted_password,
-                entry.url,
-                entry.notes,
-                entry.created_at.to_rfc3339(),
-                entry.updated_at.to_rfc3339(),
-            ],
-        )?;
-        
+        if self.title_exists(&entry.title)? {
+            return Err(Error::InvalidInput(format!("Entry '{}' already exists", entry.title)));
+        }
+
+        self.in_transaction(|| {
+            self.conn.execute(
+                "INSERT INTO password_entries 
+                 (id, title, username, encrypted_password, url, notes, created_at, updated_at, password_changed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    entry.id.to_string(),
+                    entry.title,
+                    entry.username,
+                    encrypted_password,
+                    entry.url,
+                    entry.notes,
+                    entry.created_at.to_rfc3339(),
+                    entry.updated_at.to_rfc3339(),
+                    entry.password_changed_at.to_rfc3339(),
+                ],
+            )?;
+            self.add_tags(&entry.id, &entry.tags)
+        })
+    }
+
+    /// Tag an entry, creating tags that don't exist yet
+    ///
+    /// Tags are normalized with `PasswordEntry::normalize_tag`; ones the entry
+    /// already has are ignored.
+    pub fn add_tags(&self, entry_id: &Uuid, tags: &[String]) -> Result<()> {
+        self.in_transaction(|| {
+            for tag in tags {
+                let tag = PasswordEntry::normalize_tag(tag)?;
+                self.conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO entry_tags (entry_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+                    params![entry_id.to_string(), tag],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove tags from an entry, dropping tags no entry uses any more
+    pub fn remove_tags(&self, entry_id: &Uuid, tags: &[String]) -> Result<()> {
+        self.in_transaction(|| {
+            for tag in tags {
+                let tag = PasswordEntry::normalize_tag(tag)?;
+                self.conn.execute(
+                    "DELETE FROM entry_tags WHERE entry_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+                    params![entry_id.to_string(), tag],
+                )?;
+            }
+            self.conn.execute("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM entry_tags)", [])?;
+            Ok(())
+        })
+    }
+
+    /// List entries with the given tag, leaving out soft-deleted ones
+    pub fn list_entries_by_tag(&self, tag: &str) -> Result<Vec<PasswordEntry>> {
+        let tag = PasswordEntry::normalize_tag(tag)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM password_entries
+             WHERE deleted_at IS NULL AND id IN (
+                 SELECT entry_id FROM entry_tags JOIN tags ON tags.id = entry_tags.tag_id WHERE tags.name = ?1
+             )
+             ORDER BY title",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map(params![tag], Self::row_to_entry)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Run `f` in one transaction, committing only if it succeeds
+    ///
+    /// Inside another `in_transaction`, `f` joins the outer transaction.
+    /// Methods that open their own transaction, such as `add_custom_fields`,
+    /// can't be called from `f`.
+    pub fn in_transaction<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        if !self.conn.is_autocommit() {
+            return f();
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        let value = f()?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// Store an entry's custom fields, encrypting each value
+    pub fn add_custom_fields(&self, entry_id: &Uuid, fields: &[CustomField], key: &[u8]) -> Result<()> {
+        let encryption_manager = self.encryption_manager()?;
+        let tx = self.conn.unchecked_transaction()?;
+
+        for (position, field) in fields.iter().enumerate() {
+            let encrypted_value = encryption_manager.encrypt(key, field.value.as_str().as_bytes())?;
+            tx.execute(
+                "INSERT INTO entry_fields (entry_id, name, kind, encrypted_value, position)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![entry_id.to_string(), field.name, field.kind.as_str(), encrypted_value, position as i64],
+            )?;
+        }
+
+        tx.commit()?;
         Ok(())
     }
 
-    /// Get a password entry by ID
+    /// Get an entry's custom fields with their values decrypted
+    pub fn get_custom_fields(&self, entry_id: &Uuid, key: &[u8]) -> Result<Vec<CustomField>> {
+        let encryption_manager = self.encryption_manager()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT name, kind, encrypted_value FROM entry_fields WHERE entry_id = ?1 ORDER BY position"
+        )?;
+        let rows = stmt.query_map(params![entry_id.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Vec<u8>>(2)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(name, kind, encrypted_value)| {
+                let kind = FieldKind::parse(&kind)
+                    .ok_or_else(|| Error::InvalidInput(format!("Unknown field kind '{}' for '{}'", kind, name)))?;
+                let value = decrypt_password(&encryption_manager, key, &encrypted_value)?;
+                Ok(CustomField { name, kind, value })
+            })
+            .collect()
+    }
+
+    /// Get a password entry by ID, leaving out soft-deleted ones
     pub fn get_entry_by_id(&self, id: &Uuid) -> Result<(PasswordEntry, Vec<u8>)> {
         self.conn.query_row(
-            "SELECT id, title, username, encrypted_password, url, notes, created_at, updated_at
-             FROM password_entries WHERE id = ?1",
+            &format!("SELECT {} FROM password_entries WHERE id = ?1 AND deleted_at IS NULL", ENTRY_COLUMNS),
             params![id.to_string()],
             Self::row_to_entry_with_encrypted_password,
         )
@@ -121,11 +699,10 @@ impl PasswordRepository {
         })
     }
 
-    /// Get a password entry by title
+    /// Get a password entry by title, leaving out soft-deleted ones
     pub fn get_entry_by_title(&self, title: &str) -> Result<(PasswordEntry, Vec<u8>)> {
         self.conn.query_row(
-            "SELECT id, title, username, encrypted_password, url, notes, created_at, updated_at
-             FROM password_entries WHERE title = ?1",
+            &format!("SELECT {} FROM password_entries WHERE title = ?1 AND deleted_at IS NULL", ENTRY_COLUMNS),
             params![title],
             Self::row_to_entry_with_encrypted_password,
         )
@@ -135,12 +712,117 @@ impl PasswordRepository {
         })
     }
 
-    /// List all password entries (without encrypted passwords)
-    pub fn list_entries(&self) -> Result<Vec<PasswordEntry>> {
+    /// Get the most recently trashed entry with this title
+    pub fn get_trashed_entry(&self, title: &str) -> Result<PasswordEntry> {
+        self.conn.query_row(
+            &format!(
+                "SELECT {} FROM password_entries WHERE title = ?1 AND deleted_at IS NOT NULL
+                 ORDER BY deleted_at DESC LIMIT 1",
+                ENTRY_COLUMNS
+            ),
+            params![title],
+            Self::row_to_entry,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Error::EntryNotFound(format!("{} (not in the trash)", title)),
+            _ => Error::from(e),
+        })
+    }
+
+    /// Resolve an unambiguous prefix of an entry's UUID to the full ID
+    pub fn resolve_id_prefix(&self, prefix: &str) -> Result<Uuid> {
+        let prefix = prefix.to_ascii_lowercase();
+        if !is_id_prefix(&prefix) {
+            return Err(Error::InvalidInput(format!("Invalid entry ID prefix: {}", prefix)));
+        }
+
         let mut stmt = self.conn.prepare(
-            "SELECT id, title, username, encrypted_password, url, notes, created_at, updated_at
-             FROM password_entries ORDER BY title"
+            "SELECT id FROM password_entries WHERE id LIKE ?1 || '%' AND deleted_at IS NULL LIMIT 2"
+        )?;
+        let ids = stmt.query_map(params![prefix], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        match ids.as_slice() {
+            [] => Err(Error::EntryNotFound(prefix)),
+            [id] => Uuid::parse_str(id)
+                .map_err(|_| Error::InvalidInput(format!("Stored entry ID is not a UUID: {}", id))),
+            _ => Err(Error::InvalidInput(format!(
+                "Ambiguous ID prefix '{}' matches multiple entries",
+                prefix
+            ))),
+        }
+    }
+
+    /// Get a password entry by title, falling back to an ID prefix
+    pub fn get_entry(&self, title_or_id: &str) -> Result<(PasswordEntry, Vec<u8>)> {
+        match self.get_entry_by_title(title_or_id) {
+            Err(Error::EntryNotFound(_)) if is_id_prefix(title_or_id) => {
+                let id = self.resolve_id_prefix(title_or_id)?;
+                self.get_entry_by_id(&id)
+            }
+            result => result,
+        }
+    }
+
+    /// Get an entry (by title or ID prefix) with its password decrypted
+    pub fn get_entry_decrypted(&self, title_or_id: &str, key: &[u8]) -> Result<PasswordEntry> {
+        let (mut entry, encrypted_password) = self.get_entry(title_or_id)?;
+        entry.password = decrypt_password(&self.encryption_manager()?, key, &encrypted_password)?;
+
+        Ok(entry)
+    }
+
+    /// Get an entry by its full UUID with its password decrypted
+    pub fn get_entry_decrypted_by_id(&self, id: &Uuid, key: &[u8]) -> Result<PasswordEntry> {
+        let (mut entry, encrypted_password) = self.get_entry_by_id(id)?;
+        entry.password = decrypt_password(&self.encryption_manager()?, key, &encrypted_password)?;
+
+        Ok(entry)
+    }
+
+    /// Check whether a live (not trashed) entry with the given title exists
+    pub fn title_exists(&self, title: &str) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM password_entries WHERE title = ?1 AND deleted_at IS NULL",
+            params![title],
+            |row| row.get(0),
         )?;
+
+        Ok(count > 0)
+    }
+
+    /// Clone an entry (by title or ID prefix) under a new title, re-encrypting its password with a fresh nonce
+    pub fn clone_entry(&self, title: &str, new_title: &str, key: &[u8]) -> Result<PasswordEntry> {
+        if self.title_exists(new_title)? {
+            return Err(Error::InvalidInput(format!("Entry '{}' already exists", new_title)));
+        }
+
+        let (source, encrypted_password) = self.get_entry(title)?;
+
+        let manager = self.encryption_manager()?;
+        let mut password = manager.decrypt(key, &encrypted_password)?;
+        let reencrypted = manager.encrypt(key, &password);
+        password.zeroize();
+        let reencrypted = reencrypted?;
+
+        let entry = PasswordEntry::new(
+            new_title.to_string(),
+            source.username,
+            SecureString::default(),
+            source.url,
+            source.notes,
+        );
+        self.add_entry(&entry, &reencrypted)?;
+
+        Ok(entry)
+    }
+
+    /// List all password entries (without encrypted passwords), leaving out soft-deleted ones
+    pub fn list_entries(&self) -> Result<Vec<PasswordEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM password_entries WHERE deleted_at IS NULL ORDER BY title",
+            ENTRY_COLUMNS
+        ))?;
         
         let entries = stmt.query_map([], |row| {
             Self::row_to_entry(row)
@@ -150,65 +832,522 @@ impl PasswordRepository {
         Ok(entries)
     }
 
-    /// Search entries by query
-    pub fn search_entries(&self, query: &str) -> Result<Vec<PasswordEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT e.id, e.title, e.username, e.encrypted_password, e.url, e.notes, e.created_at, e.updated_at
-             FROM password_entries e
-             WHERE e.title LIKE ?1 OR e.username LIKE ?1 OR e.url LIKE ?1 OR e.notes LIKE ?1
-             ORDER BY e.title"
-        )?;
-        
-        let search_pattern = format!("%{}%", query);
-        let entries = stmt.query_map([&search_pattern], |row| {
-            Self::row_to_entry(row)
-        })?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-        
+    /// List all password entries together with their encrypted passwords, leaving out soft-deleted ones
+    pub fn list_entries_with_passwords(&self) -> Result<Vec<(PasswordEntry, Vec<u8>)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM password_entries WHERE deleted_at IS NULL ORDER BY title",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map([], Self::row_to_entry_with_encrypted_password)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
         Ok(entries)
     }
 
-    /// Update a password entry
-    pub fn update_entry(&self, entry: &PasswordEntry, encrypted_password: &[u8]) -> Result<()> {
-        let updated = self.conn.execute(
-            "UPDATE password_entries 
-             SET title = ?1, username = ?2, encrypted_password = ?3, url = ?4, notes = ?5, updated_at = ?6
-             WHERE id = ?7",
-            params![
-                entry.title,
-                entry.username,
-                encrypted_password,
-                entry.url,
-                entry.notes,
-                entry.updated_at.to_rfc3339(),
-                entry.id.to_string(),
-            ],
-        )?;
-        
-        if updated == 0 {
-            return Err(Error::EntryNotFound(entry.id.to_string()));
-        }
-        
-        Ok(())
+    /// Decrypt every entry's password, yielding a result per entry
+    ///
+    /// A corrupt or undecryptable entry produces an error item rather than
+    /// aborting the iteration.
+    pub fn iter_decrypted<'a>(
+        &self,
+        key: &'a [u8],
+    ) -> Result<impl Iterator<Item = (PasswordEntry, Result<SecureString>)> + 'a> {
+        let manager = self.encryption_manager()?;
+        let entries = self.list_entries_with_passwords()?;
+
+        Ok(entries.into_iter().map(move |(entry, encrypted_password)| {
+            let password = decrypt_password(&manager, key, &encrypted_password);
+            (entry, password)
+        }))
     }
 
-    /// Delete a password entry
-    pub fn delete_entry(&self, id: &Uuid) -> Result<()> {
-        let deleted = self.conn.execute(
-            "DELETE FROM password_entries WHERE id = ?1",
-            params![id.to_string()],
-        )?;
-        
-        if deleted == 0 {
-            return Err(Error::EntryNotFound(id.to_string()));
+    /// Regenerate the passwords of all entries matching a predicate in one transaction
+    ///
+    /// The predicate receives each entry with its decrypted password. Replaced
+    /// passwords go to the history like any other update. An entry whose
+    /// password can't be decrypted is skipped and reported rather than
+    /// stopping the others from being rotated.
+    pub fn rotate_passwords<F>(
+        &self,
+        key: &[u8],
+        generator: &PasswordGenerator,
+        mut predicate: F,
+    ) -> Result<RotationReport>
+    where
+        F: FnMut(&PasswordEntry, &str) -> bool,
+    {
+        let manager = self.encryption_manager()?;
+        self.in_transaction(|| {
+            let mut report = RotationReport::default();
+
+            for (mut entry, encrypted_password) in self.list_entries_with_passwords()? {
+                let mut password = match manager.decrypt(key, &encrypted_password) {
+                    Ok(password) => password,
+                    Err(e) => {
+                        report.failed.push((entry, e));
+                        continue;
+                    }
+                };
+                let selected = predicate(&entry, std::str::from_utf8(&password).unwrap_or_default());
+                password.zeroize();
+                if !selected {
+                    continue;
+                }
+
+                let mut new_password = generator.generate()?;
+                let encrypted = manager.encrypt(key, new_password.as_bytes());
+                new_password.zeroize();
+
+                entry.touch();
+                entry.password_changed_at = entry.updated_at;
+                self.record_history(&entry.id, &encrypted_password, &entry.updated_at)?;
+                self.conn.execute(
+                    "UPDATE password_entries SET encrypted_password = ?1, updated_at = ?2, password_changed_at = ?2
+                     WHERE id = ?3",
+                    params![encrypted?, entry.updated_at.to_rfc3339(), entry.id.to_string()],
+                )?;
+                report.rotated.push(entry);
+            }
+
+            Ok(report)
+        })
+    }
+
+    /// The oldest live entry's encrypted password, used to sanity-check a key
+    /// on vaults without a key check
+    pub fn sample_ciphertext(&self) -> Result<Option<Vec<u8>>> {
+        self.conn
+            .query_row(
+                "SELECT encrypted_password FROM password_entries WHERE deleted_at IS NULL ORDER BY created_at LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(Error::from(e)),
+            })
+    }
+
+    /// Write everything in the WAL back into the database file and empty the WAL
+    ///
+    /// Afterwards the database file alone holds the whole vault, so it can be
+    /// copied. A no-op unless the database is in WAL mode.
+    pub fn checkpoint(&self) -> Result<()> {
+        let busy: i64 = self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+        if busy != 0 {
+            return Err(Error::Database(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                Some("another connection is using the vault; try again once it's done".to_string()),
+            )));
         }
-        
         Ok(())
     }
 
-    /// Delete entry by title
-    pub fn delete_entry_by_title(&self, title: &str) -> Result<()> {
-        let deleted = self.conn.execute(
+    /// Latest migration applied to the database
+    pub fn schema_version(&self) -> Result<u32> {
+        MigrationRunner::new(&self.conn).get_current_version()
+    }
+
+    /// Count the entries in the vault, leaving out soft-deleted ones
+    pub fn count_entries(&self) -> Result<u64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM password_entries WHERE deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    /// Summary counts over all entries, computed in a single query
+    ///
+    /// Nothing is decrypted: an empty password is recognised by the length of
+    /// its ciphertext. Soft-deleted entries are left out.
+    pub fn entry_stats(&self) -> Result<EntryStats> {
+        // Julian days, which SQLite can average, back to UTC timestamps
+        fn from_julian_day(day: Option<f64>) -> Option<DateTime<Utc>> {
+            let millis = (day? - 2_440_587.5) * 86_400_000.0;
+            DateTime::from_timestamp_millis(millis.round() as i64)
+        }
+
+        let mut stats = self.conn.query_row(
+            "SELECT COUNT(*),
+                    COUNT(NULLIF(url, '')),
+                    COUNT(NULLIF(notes, '')),
+                    COUNT(totp_secret),
+                    COUNT(CASE WHEN EXISTS (SELECT 1 FROM entry_fields WHERE entry_id = id) THEN 1 END),
+                    COUNT(CASE WHEN length(encrypted_password) <= ?1 THEN 1 END),
+                    MIN(julianday(updated_at)),
+                    MAX(julianday(updated_at)),
+                    AVG(julianday(COALESCE(password_changed_at, updated_at)))
+             FROM password_entries
+             WHERE deleted_at IS NULL",
+            [crate::crypto::EMPTY_CIPHERTEXT_LEN as i64],
+            |row| {
+                Ok(EntryStats {
+                    total: row.get::<_, i64>(0)? as u64,
+                    with_url: row.get::<_, i64>(1)? as u64,
+                    with_notes: row.get::<_, i64>(2)? as u64,
+                    with_totp: row.get::<_, i64>(3)? as u64,
+                    with_custom_fields: row.get::<_, i64>(4)? as u64,
+                    missing_password: row.get::<_, i64>(5)? as u64,
+                    oldest_update: from_julian_day(row.get(6)?),
+                    newest_update: from_julian_day(row.get(7)?),
+                    mean_password_changed_at: from_julian_day(row.get(8)?),
+                    per_tag: Default::default(),
+                })
+            },
+        )?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT tags.name, COUNT(*)
+             FROM entry_tags
+             JOIN tags ON tags.id = entry_tags.tag_id
+             JOIN password_entries ON password_entries.id = entry_tags.entry_id
+             WHERE password_entries.deleted_at IS NULL
+             GROUP BY tags.name",
+        )?;
+        stats.per_tag = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?
+            .collect::<std::result::Result<_, _>>()?;
+
+        Ok(stats)
+    }
+
+    /// Search entries by query
+    pub fn search_entries(&self, query: &str) -> Result<Vec<PasswordEntry>> {
+        self.search_entries_in(query, &SearchField::ALL)
+    }
+
+    /// Search entries by query, matching only the given fields
+    ///
+    /// Soft-deleted entries are never returned.
+    pub fn search_entries_in(&self, query: &str, fields: &[SearchField]) -> Result<Vec<PasswordEntry>> {
+        if fields.is_empty() {
+            return Err(Error::InvalidInput("No search fields selected".to_string()));
+        }
+
+        // Column names come from SearchField, never from user input
+        let conditions = fields
+            .iter()
+            .map(|field| format!("{} LIKE ?1 ESCAPE '\\'", field.column()))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM password_entries
+             WHERE deleted_at IS NULL AND ({})
+             ORDER BY title",
+            ENTRY_COLUMNS, conditions
+        ))?;
+        
+        // Match the query literally, as SearchField::matches does
+        let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let search_pattern = format!("%{}%", escaped);
+        let entries = stmt.query_map([&search_pattern], |row| {
+            Self::row_to_entry(row)
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+        
+        Ok(entries)
+    }
+
+    /// Whether the full-text index from migration 13 exists
+    pub fn has_fts(&self) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'password_entries_fts'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Full-text search over titles, URLs and notes, best matches first
+    ///
+    /// Each word of the query must match a whole word or word prefix; results
+    /// are ranked with `bm25`, weighting titles over URLs over notes. Falls back
+    /// to `search_entries` (unranked substring matching) without the index.
+    pub fn search_fts(&self, query: &str) -> Result<Vec<PasswordEntry>> {
+        if !self.has_fts()? {
+            return self.search_entries(query);
+        }
+
+        // Quote every word so FTS5 operators in the query are taken literally
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM password_entries
+             JOIN (
+                 SELECT entry_id, bm25(password_entries_fts, 0.0, 10.0, 5.0, 1.0) AS rank
+                 FROM password_entries_fts WHERE password_entries_fts MATCH ?1
+             ) AS matches ON matches.entry_id = password_entries.id
+             WHERE deleted_at IS NULL
+             ORDER BY matches.rank, title",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map(params![terms.join(" ")], Self::row_to_entry)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Search entries with a literal or regex pattern, matching only the given fields
+    ///
+    /// Regexes can't be expressed in SQL, so they are matched in memory
+    /// against every live entry's metadata.
+    pub fn search_entries_matching(&self, pattern: &SearchPattern, fields: &[SearchField]) -> Result<Vec<PasswordEntry>> {
+        match pattern {
+            SearchPattern::Literal(query) => self.search_entries_in(query, fields),
+            SearchPattern::Regex(_) => {
+                if fields.is_empty() {
+                    return Err(Error::InvalidInput("No search fields selected".to_string()));
+                }
+                Ok(self
+                    .list_entries()?
+                    .into_iter()
+                    .filter(|entry| !entry.fields_matching(pattern, fields).is_empty())
+                    .collect())
+            }
+        }
+    }
+
+    /// Search like `search_entries_in`, also matching encrypted custom field contents
+    ///
+    /// Every entry's custom fields are decrypted in memory, which needs the
+    /// vault key and is much slower than a plain search. Secret fields are
+    /// never matched, so a search can't be used to probe their values.
+    pub fn deep_search(&self, query: &str, fields: &[SearchField], key: &[u8]) -> Result<Vec<DeepMatch>> {
+        let plain: Vec<Uuid> = self.search_entries_in(query, fields)?.into_iter().map(|e| e.id).collect();
+        let query = query.to_ascii_lowercase();
+        let mut matches = Vec::new();
+
+        for entry in self.list_entries()? {
+            let encrypted_fields: Vec<String> = self
+                .get_custom_fields(&entry.id, key)?
+                .into_iter()
+                .filter(|field| field.kind != FieldKind::Secret)
+                .filter(|field| field.value.as_str().to_ascii_lowercase().contains(&query))
+                .map(|field| field.name.clone())
+                .collect();
+
+            if !encrypted_fields.is_empty() || plain.contains(&entry.id) {
+                matches.push(DeepMatch { entry, encrypted_fields });
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Update a password entry
+    ///
+    /// `password_changed_at` is bumped to the entry's `updated_at` only when the
+    /// stored ciphertext actually changes.
+    /// The replaced ciphertext is kept in the entry's password history.
+    pub fn update_entry(&self, entry: &PasswordEntry, encrypted_password: &[u8]) -> Result<()> {
+        self.in_transaction(|| {
+            let previous: Option<Vec<u8>> = self.conn
+                .query_row(
+                    "SELECT encrypted_password FROM password_entries WHERE id = ?1",
+                    params![entry.id.to_string()],
+                    |row| row.get(0),
+                )
+                .or_else(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                    e => Err(e),
+                })?;
+            if let Some(previous) = previous.filter(|previous| previous.as_slice() != encrypted_password) {
+                self.record_history(&entry.id, &previous, &entry.updated_at)?;
+            }
+
+            let updated = self.conn.execute(
+                "UPDATE password_entries 
+                 SET title = ?1, username = ?2, url = ?4, notes = ?5, updated_at = ?6,
+                     password_changed_at = CASE WHEN encrypted_password = ?3 THEN password_changed_at ELSE ?6 END,
+                     encrypted_password = ?3
+                 WHERE id = ?7",
+                params![
+                    entry.title,
+                    entry.username,
+                    encrypted_password,
+                    entry.url,
+                    entry.notes,
+                    entry.updated_at.to_rfc3339(),
+                    entry.id.to_string(),
+                ],
+            )?;
+            
+            if updated == 0 {
+                return Err(Error::EntryNotFound(entry.id.to_string()));
+            }
+            Ok(())
+        })
+    }
+
+    /// Number of previous passwords kept per entry (0 keeps none)
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+    }
+
+    /// Keep a replaced password, dropping the oldest beyond the history limit
+    fn record_history(&self, entry_id: &Uuid, encrypted_password: &[u8], changed_at: &DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO password_history (entry_id, encrypted_password, changed_at) VALUES (?1, ?2, ?3)",
+            params![entry_id.to_string(), encrypted_password, changed_at.to_rfc3339()],
+        )?;
+        self.conn.execute(
+            "DELETE FROM password_history WHERE entry_id = ?1 AND id NOT IN (
+                 SELECT id FROM password_history WHERE entry_id = ?1 ORDER BY id DESC LIMIT ?2
+             )",
+            params![entry_id.to_string(), self.history_limit as i64],
+        )?;
+        Ok(())
+    }
+
+    /// An entry's previous passwords, most recently replaced first
+    pub fn password_history(&self, entry_id: &Uuid) -> Result<Vec<PasswordHistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT encrypted_password, changed_at FROM password_history WHERE entry_id = ?1 ORDER BY id DESC",
+        )?;
+        let history = stmt
+            .query_map(params![entry_id.to_string()], |row| {
+                Ok(PasswordHistoryEntry {
+                    encrypted_password: row.get(0)?,
+                    changed_at: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(history)
+    }
+
+    /// Decrypt one of an entry's previous passwords
+    pub fn decrypt_history_password(&self, entry: &PasswordHistoryEntry, key: &[u8]) -> Result<SecureString> {
+        decrypt_password(&self.encryption_manager()?, key, &entry.encrypted_password)
+    }
+
+    /// Set or replace an entry's TOTP secret, or clear it with `None`
+    ///
+    /// The secret is validated as base32 and stored encrypted.
+    pub fn set_totp_secret(&self, id: &Uuid, secret: Option<&str>, key: &[u8]) -> Result<()> {
+        let encrypted_secret = match secret {
+            Some(secret) => {
+                crate::crypto::totp::decode_base32(secret)?;
+                let secret = zeroize::Zeroizing::new(crate::crypto::totp::normalize_secret(secret));
+                Some(self.encryption_manager()?.encrypt(key, secret.as_bytes())?)
+            }
+            None => None,
+        };
+
+        let updated = self.conn.execute(
+            "UPDATE password_entries SET totp_secret = ?1, updated_at = ?2 WHERE id = ?3",
+            params![encrypted_secret, Utc::now().to_rfc3339(), id.to_string()],
+        )?;
+
+        if updated == 0 {
+            return Err(Error::EntryNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Get an entry's decrypted TOTP secret, if it has one
+    pub fn get_totp_secret(&self, id: &Uuid, key: &[u8]) -> Result<Option<SecureString>> {
+        let encrypted_secret: Option<Vec<u8>> = self.conn
+            .query_row(
+                "SELECT totp_secret FROM password_entries WHERE id = ?1",
+                params![id.to_string()],
+                |row| row.get(0),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Error::EntryNotFound(id.to_string()),
+                _ => Error::from(e),
+            })?;
+
+        encrypted_secret
+            .map(|encrypted| decrypt_password(&self.encryption_manager()?, key, &encrypted))
+            .transpose()
+    }
+
+    /// Apply metadata changes to an entry (by title or ID prefix), keeping its password
+    pub fn update_entry_fields(&self, title_or_id: &str, spec: &UpdateSpec) -> Result<PasswordEntry> {
+        let (mut entry, encrypted_password) = self.get_entry(title_or_id)?;
+
+        if let Some(title) = &spec.title {
+            if *title != entry.title && self.title_exists(title)? {
+                return Err(Error::InvalidInput(format!("Entry '{}' already exists", title)));
+            }
+        }
+
+        spec.apply(&mut entry);
+        self.update_entry(&entry, &encrypted_password)?;
+
+        Ok(entry)
+    }
+
+    /// Replace an entry's password, encrypting it under the vault's cipher
+    pub fn change_password(&self, title_or_id: &str, password: &str, key: &[u8]) -> Result<PasswordEntry> {
+        let (mut entry, _) = self.get_entry(title_or_id)?;
+        let encrypted_password = self.encryption_manager()?.encrypt(key, password.as_bytes())?;
+
+        entry.touch();
+        self.update_entry(&entry, &encrypted_password)?;
+
+        Ok(entry)
+    }
+
+    /// Delete a password entry
+    pub fn delete_entry(&self, id: &Uuid) -> Result<()> {
+        let deleted = self.conn.execute(
+            "DELETE FROM password_entries WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        
+        if deleted == 0 {
+            return Err(Error::EntryNotFound(id.to_string()));
+        }
+        
+        Ok(())
+    }
+
+    /// Move an entry (by title or ID prefix) to the trash, keeping its data
+    pub fn soft_delete_entry(&self, title_or_id: &str) -> Result<PasswordEntry> {
+        let (entry, _) = self.get_entry(title_or_id)?;
+        self.conn.execute(
+            "UPDATE password_entries SET deleted_at = ?1 WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), entry.id.to_string()],
+        )?;
+
+        Ok(entry)
+    }
+
+    /// Bring a soft-deleted entry back by title
+    ///
+    /// Refused while a live entry has taken the title, so titles stay unique.
+    pub fn restore_entry(&self, title: &str) -> Result<()> {
+        let entry = self.get_trashed_entry(title)?;
+        if self.title_exists(title)? {
+            return Err(Error::InvalidInput(format!(
+                "Entry '{}' already exists; rename it before restoring the trashed one",
+                title
+            )));
+        }
+
+        self.conn.execute(
+            "UPDATE password_entries SET deleted_at = NULL WHERE id = ?1",
+            params![entry.id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Delete entry by title
+    pub fn delete_entry_by_title(&self, title: &str) -> Result<()> {
+        let deleted = self.conn.execute(
             "DELETE FROM password_entries WHERE title = ?1",
             params![title],
         )?;
@@ -235,6 +1374,14 @@ impl PasswordRepository {
         let updated_at = chrono::DateTime::parse_from_rfc3339(&updated_at_str)
             .map_err(|_| rusqlite::Error::InvalidColumnType(7, "updated_at".to_string(), rusqlite::types::Type::Text))?
             .with_timezone(&Utc);
+
+        // Rows written before password_changed_at existed fall back to updated_at
+        let password_changed_at = match row.get::<_, Option<String>>(8)? {
+            Some(value) => chrono::DateTime::parse_from_rfc3339(&value)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(8, "password_changed_at".to_string(), rusqlite::types::Type::Text))?
+                .with_timezone(&Utc),
+            None => updated_at,
+        };
         
         Ok(PasswordEntry {
             id,
@@ -245,6 +1392,11 @@ impl PasswordRepository {
             notes: row.get(5)?,
             created_at,
             updated_at,
+            password_changed_at,
+            tags: row
+                .get::<_, Option<String>>(9)?
+                .map(|tags| tags.split(TAG_SEPARATOR).map(str::to_string).collect())
+                .unwrap_or_default(),
         })
     }
 
@@ -256,6 +1408,19 @@ impl PasswordRepository {
     }
 }
 
+/// Decrypt a stored password into a secure string
+fn decrypt_password(manager: &EncryptionManager, key: &[u8], encrypted_password: &[u8]) -> Result<SecureString> {
+    let password = manager.decrypt(key, encrypted_password)?;
+    let password = String::from_utf8(password)
+        .map_err(|_| Error::Crypto("Decrypted password is not valid UTF-8".to_string()))?;
+    Ok(SecureString::new(password))
+}
+
+/// Whether a string could be a (partial) entry UUID
+fn is_id_prefix(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,4 +1449,803 @@ mod tests {
         assert_eq!(metadata.salt, salt);
         assert_eq!(metadata.password_hash, password_hash);
     }
+
+    #[test]
+    fn test_clone_entry() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+        let original = PasswordEntry::new(
+            "GitHub".to_string(),
+            "user@example.com".to_string(),
+            SecureString::default(),
+            Some("https://github.com".to_string()),
+            Some("Work account".to_string()),
+        );
+        let encrypted = manager.encrypt(&key, b"hunter2").unwrap();
+        repo.add_entry(&original, &encrypted).unwrap();
+
+        let clone = repo.clone_entry("GitHub", "GitHub (personal)", &key).unwrap();
+        assert_ne!(clone.id, original.id);
+
+        let (cloned, cloned_encrypted) = repo.get_entry_by_title("GitHub (personal)").unwrap();
+        assert_eq!(cloned.username, original.username);
+        assert_eq!(cloned.url, original.url);
+        assert_eq!(cloned.notes, original.notes);
+        assert_ne!(cloned_encrypted, encrypted);
+        assert_eq!(manager.decrypt(&key, &cloned_encrypted).unwrap(), b"hunter2");
+
+        assert!(repo.clone_entry("GitHub", "GitHub (personal)", &key).is_err());
+    }
+
+    #[test]
+    fn test_get_entry_decrypted_by_id_with_duplicate_titles() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+
+        let mut ids = Vec::new();
+        for (title, password) in [("GitHub", "first"), ("GitHub (old)", "second")] {
+            let entry = PasswordEntry::new(
+                title.to_string(),
+                "octocat".to_string(),
+                SecureString::default(),
+                None,
+                None,
+            );
+            repo.add_entry(&entry, &manager.encrypt(&key, password.as_bytes()).unwrap()).unwrap();
+            ids.push(entry.id);
+        }
+        // Vaults written before titles were checked for uniqueness may hold duplicates
+        repo.conn.execute("UPDATE password_entries SET title = 'GitHub'", []).unwrap();
+
+        let entry = repo.get_entry_decrypted_by_id(&ids[1], &key).unwrap();
+        assert_eq!(entry.id, ids[1]);
+        assert_eq!(entry.password.as_str(), "second");
+
+        assert!(matches!(
+            repo.get_entry_decrypted_by_id(&Uuid::new_v4(), &key),
+            Err(Error::EntryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_entries_by_regex() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        for (title, url) in [("GitHub", "https://github.com"), ("Bank", "http://bank.example"), ("Mail", "https://mail.example")] {
+            let entry = PasswordEntry::new(title.to_string(), "alice".to_string(), SecureString::default(), Some(url.to_string()), None);
+            repo.add_entry(&entry, b"ciphertext").unwrap();
+        }
+
+        let pattern = SearchPattern::regex(r"^https://.*\.example$").unwrap();
+        let titles: Vec<String> = repo
+            .search_entries_matching(&pattern, &[SearchField::Url])
+            .unwrap()
+            .into_iter()
+            .map(|e| e.title)
+            .collect();
+        assert_eq!(titles, vec!["Mail".to_string()]);
+
+        assert!(repo.search_entries_matching(&pattern, &[SearchField::Title]).unwrap().is_empty());
+        assert!(matches!(SearchPattern::regex("("), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        for title in ["GitHub", "GitLab"] {
+            let entry = PasswordEntry::new(title.to_string(), "octocat".to_string(), SecureString::default(), None, None);
+            repo.add_entry(&entry, b"ciphertext").unwrap();
+        }
+
+        repo.soft_delete_entry("GitHub").unwrap();
+        let titles: Vec<String> = repo.list_entries().unwrap().into_iter().map(|e| e.title).collect();
+        assert_eq!(titles, vec!["GitLab".to_string()]);
+        assert!(repo.search_entries("Git").unwrap().iter().all(|e| e.title == "GitLab"));
+        assert_eq!(repo.count_entries().unwrap(), 1);
+
+        assert!(matches!(repo.restore_entry("GitLab"), Err(Error::EntryNotFound(_))));
+        repo.restore_entry("GitHub").unwrap();
+        assert_eq!(repo.list_entries().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_trashed_entries_are_hidden_from_live_lookups() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "octocat".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+        repo.soft_delete_entry("GitHub").unwrap();
+
+        assert!(matches!(repo.get_entry_by_title("GitHub"), Err(Error::EntryNotFound(_))));
+        assert!(matches!(repo.get_entry_by_id(&entry.id), Err(Error::EntryNotFound(_))));
+        assert!(matches!(repo.get_entry(&entry.id.to_string()[..8]), Err(Error::EntryNotFound(_))));
+        assert!(repo.list_entries_with_passwords().unwrap().is_empty());
+        assert!(!repo.title_exists("GitHub").unwrap());
+        assert_eq!(repo.get_trashed_entry("GitHub").unwrap().id, entry.id);
+
+        // The title is free again, and the trashed entry can't take it back
+        let replacement = PasswordEntry::new("GitHub".to_string(), "other".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&replacement, b"ciphertext").unwrap();
+        assert_eq!(repo.get_entry_by_title("GitHub").unwrap().0.id, replacement.id);
+        assert!(matches!(repo.restore_entry("GitHub"), Err(Error::InvalidInput(_))));
+
+        repo.delete_entry(&replacement.id).unwrap();
+        repo.restore_entry("GitHub").unwrap();
+        assert_eq!(repo.get_entry_by_title("GitHub").unwrap().0.id, entry.id);
+    }
+
+    #[test]
+    fn test_add_entry_rejects_duplicate_title() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "octocat".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+
+        let duplicate = PasswordEntry::new("GitHub".to_string(), "other".to_string(), SecureString::default(), None, None);
+        assert!(matches!(repo.add_entry(&duplicate, b"ciphertext"), Err(Error::InvalidInput(_))));
+        assert_eq!(repo.count_entries().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_search_entries_in_selected_fields() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        for (title, username) in [("alice's bank", "bob"), ("Forum", "alice")] {
+            let entry = PasswordEntry::new(title.to_string(), username.to_string(), SecureString::default(), None, None);
+            repo.add_entry(&entry, b"ciphertext").unwrap();
+        }
+
+        let all = repo.search_entries("alice").unwrap();
+        assert_eq!(all.len(), 2);
+
+        let by_username = repo.search_entries_in("alice", &[SearchField::Username]).unwrap();
+        assert_eq!(by_username.len(), 1);
+        assert_eq!(by_username[0].title, "Forum");
+
+        assert!(repo.search_entries_in("alice", &[]).is_err());
+    }
+
+    #[test]
+    fn test_custom_fields_round_trip_and_cascade() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+
+        let template = crate::database::templates::find_template("card", &[]).unwrap();
+        let (entry, fields) = template.build("Visa", |field| Ok(format!("{}-value", field.name))).unwrap();
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+        repo.add_custom_fields(&entry.id, &fields, &key).unwrap();
+
+        let stored = repo.get_custom_fields(&entry.id, &key).unwrap();
+        let names: Vec<_> = stored.iter().map(|f| (f.name.as_str(), f.value.as_str())).collect();
+        assert_eq!(names, vec![("number", "number-value"), ("expiry", "expiry-value"), ("cvv", "cvv-value")]);
+
+        repo.delete_entry(&entry.id).unwrap();
+        assert!(repo.get_custom_fields(&entry.id, &key).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_vaults_generate_with_their_own_defaults() {
+        let global = PasswordGenerationConfig::default();
+        let work = PasswordRepository::new(":memory:").unwrap();
+        let personal = PasswordRepository::new(":memory:").unwrap();
+        let untouched = PasswordRepository::new(":memory:").unwrap();
+        for repo in [&work, &personal, &untouched] {
+            repo.initialize_vault(vec![0u8; 32], b"hash".to_vec()).unwrap();
+        }
+
+        work.set_generator_defaults(&PasswordGenerationConfig { default_length: 32, ..global.clone() }).unwrap();
+        personal.set_generator_defaults(&PasswordGenerationConfig {
+            default_length: 12,
+            include_symbols: false,
+            ..global.clone()
+        }).unwrap();
+
+        let generate = |repo: &PasswordRepository| {
+            let defaults = repo.generator_defaults_or(&global).unwrap();
+            PasswordGenerator::with_config(defaults.generator_config()).generate().unwrap()
+        };
+
+        assert_eq!(generate(&work).len(), 32);
+        let personal_password = generate(&personal);
+        assert_eq!(personal_password.len(), 12);
+        assert!(personal_password.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(generate(&untouched).len(), global.default_length as usize);
+    }
+
+    #[test]
+    fn test_set_and_remove_totp_secret() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "octocat".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+
+        assert!(repo.set_totp_secret(&entry.id, Some("not base32!"), &key).is_err());
+
+        repo.set_totp_secret(&entry.id, Some("jbsw y3dp ehpk 3pxp"), &key).unwrap();
+        let secret = repo.get_totp_secret(&entry.id, &key).unwrap().unwrap();
+        assert_eq!(secret.as_str(), "JBSWY3DPEHPK3PXP");
+
+        repo.set_totp_secret(&entry.id, None, &key).unwrap();
+        let stored: Option<Vec<u8>> = repo.conn
+            .query_row("SELECT totp_secret FROM password_entries WHERE id = ?1", params![entry.id.to_string()], |row| row.get(0))
+            .unwrap();
+        assert!(stored.is_none());
+        assert!(repo.get_totp_secret(&entry.id, &key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_count_entries_and_stats() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+        assert_eq!(repo.count_entries().unwrap(), 0);
+
+        let with_url = PasswordEntry::new("A".to_string(), "a".to_string(), SecureString::default(), Some("https://a.example".to_string()), None);
+        let with_notes = PasswordEntry::new("B".to_string(), "b".to_string(), SecureString::default(), None, Some("note".to_string()));
+        let bare = PasswordEntry::new("C".to_string(), "c".to_string(), SecureString::default(), None, Some(String::new()));
+        for entry in [&with_url, &with_notes, &bare] {
+            repo.add_entry(entry, b"ciphertext").unwrap();
+        }
+        repo.set_totp_secret(&with_url.id, Some("JBSWY3DP"), &key).unwrap();
+        let field = CustomField { name: "pin".to_string(), kind: FieldKind::Secret, value: SecureString::from("1234") };
+        repo.add_custom_fields(&with_notes.id, &[field], &key).unwrap();
+
+        assert_eq!(repo.count_entries().unwrap(), 3);
+        let stats = repo.entry_stats().unwrap();
+        assert_eq!(
+            (stats.total, stats.with_url, stats.with_notes, stats.with_totp, stats.with_custom_fields),
+            (3, 1, 1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_entry_stats_counts_entries_per_tag() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let mut ids = Vec::new();
+        for title in ["A", "B", "C", "D"] {
+            let entry = PasswordEntry::new(title.to_string(), "me".to_string(), SecureString::default(), None, None);
+            repo.add_entry(&entry, b"ciphertext").unwrap();
+            ids.push(entry.id);
+        }
+        let tags = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        repo.add_tags(&ids[0], &tags(&["work", "email"])).unwrap();
+        repo.add_tags(&ids[1], &tags(&["work"])).unwrap();
+        repo.add_tags(&ids[2], &tags(&["work", "email"])).unwrap();
+        repo.add_tags(&ids[3], &tags(&["email"])).unwrap();
+        // Trashed entries don't count
+        repo.soft_delete_entry("D").unwrap();
+
+        let stats = repo.entry_stats().unwrap();
+        let per_tag: Vec<_> = stats.per_tag.iter().map(|(tag, count)| (tag.as_str(), *count)).collect();
+        assert_eq!(per_tag, [("email", 2), ("work", 3)]);
+    }
+
+    #[test]
+    fn test_entry_stats_dates_and_missing_passwords() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+        assert_eq!(repo.entry_stats().unwrap(), EntryStats::default());
+
+        let day = chrono::Duration::days(1);
+        let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        for (i, (title, password)) in [("A", "pw"), ("B", "x"), ("C", ""), ("D", "")].into_iter().enumerate() {
+            let mut entry = PasswordEntry::new(title.to_string(), "me".to_string(), SecureString::default(), None, None);
+            entry.updated_at = start + day * (i as i32 * 2);
+            entry.password_changed_at = start + day * i as i32;
+            repo.add_entry(&entry, &manager.encrypt(&key, password.as_bytes()).unwrap()).unwrap();
+        }
+        repo.soft_delete_entry("D").unwrap();
+
+        let stats = repo.entry_stats().unwrap();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.missing_password, 1);
+        assert_eq!(stats.oldest_update, Some(start));
+        assert_eq!(stats.newest_update, Some(start + day * 4));
+        assert_eq!(stats.mean_password_changed_at, Some(start + day));
+    }
+
+    #[test]
+    fn test_update_entry_fields_clears_notes_and_keeps_url() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::default(),
+            Some("https://github.com".to_string()),
+            Some("old notes".to_string()),
+        );
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+
+        let spec = UpdateSpec { notes: FieldUpdate::Clear, ..UpdateSpec::default() };
+        repo.update_entry_fields("GitHub", &spec).unwrap();
+
+        let (updated, encrypted_password) = repo.get_entry_by_id(&entry.id).unwrap();
+        assert_eq!(updated.notes, None);
+        assert_eq!(updated.url.as_deref(), Some("https://github.com"));
+        assert_eq!(encrypted_password, b"ciphertext");
+        assert_eq!(updated.password_changed_at, entry.password_changed_at);
+    }
+
+    #[test]
+    fn test_search_explains_matched_fields() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let entry = PasswordEntry::new(
+            "Email".to_string(),
+            "alice".to_string(),
+            SecureString::default(),
+            None,
+            Some("Recovery phrase is in the Safe".to_string()),
+        );
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+        let other = PasswordEntry::new("100%_done".to_string(), "bob".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&other, b"ciphertext").unwrap();
+
+        let results = repo.search_entries("safe").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matched_fields("safe", &SearchField::ALL), vec![SearchField::Notes]);
+
+        // LIKE wildcards in the query are matched literally
+        assert_eq!(repo.search_entries("%_").unwrap().len(), 1);
+        assert!(repo.search_entries("l_ce").unwrap().is_empty());
+    }
+
+    fn add_with_id(repo: &PasswordRepository, id: &str, title: &str) {
+        let mut entry = PasswordEntry::new(
+            title.to_string(),
+            "user".to_string(),
+            SecureString::default(),
+            None,
+            None,
+        );
+        entry.id = Uuid::parse_str(id).unwrap();
+        repo.add_entry(&entry, &[0u8; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_unique_id_prefix() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+        add_with_id(&repo, "abcd1234-0000-4000-8000-000000000001", "First");
+        add_with_id(&repo, "abcd5678-0000-4000-8000-000000000002", "Second");
+
+        let id = repo.resolve_id_prefix("ABCD1").unwrap();
+        assert_eq!(id.to_string(), "abcd1234-0000-4000-8000-000000000001");
+
+        let (entry, _) = repo.get_entry("abcd56").unwrap();
+        assert_eq!(entry.title, "Second");
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_id_prefix() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+        add_with_id(&repo, "abcd1234-0000-4000-8000-000000000001", "First");
+        add_with_id(&repo, "abcd5678-0000-4000-8000-000000000002", "Second");
+
+        assert!(matches!(repo.resolve_id_prefix("abcd"), Err(Error::InvalidInput(_))));
+        assert!(matches!(repo.get_entry("abcd"), Err(Error::InvalidInput(_))));
+        assert!(matches!(repo.resolve_id_prefix("ffff"), Err(Error::EntryNotFound(_))));
+        assert!(matches!(repo.resolve_id_prefix("ab%"), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_rotate_only_flagged_entries() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+        for (title, password) in [("Weak", "letmein"), ("Strong", "Xk9#mP2$vL7@qR4!")] {
+            let entry = PasswordEntry::new(
+                title.to_string(),
+                "user".to_string(),
+                SecureString::default(),
+                None,
+                None,
+            );
+            let encrypted = manager.encrypt(&key, password.as_bytes()).unwrap();
+            repo.add_entry(&entry, &encrypted).unwrap();
+        }
+
+        let report = repo
+            .rotate_passwords(&key, &PasswordGenerator::new(), |_, password| {
+                crate::utils::audit::is_weak(password, 60.0)
+            })
+            .unwrap();
+        assert_eq!(report.rotated.len(), 1);
+        assert_eq!(report.rotated[0].title, "Weak");
+        assert!(report.failed.is_empty());
+
+        let decrypt = |title: &str| {
+            let (_, encrypted) = repo.get_entry_by_title(title).unwrap();
+            String::from_utf8(manager.decrypt(&key, &encrypted).unwrap()).unwrap()
+        };
+        let weak = decrypt("Weak");
+        assert_ne!(weak, "letmein");
+        assert_eq!(weak.len(), 16);
+        assert_eq!(decrypt("Strong"), "Xk9#mP2$vL7@qR4!");
+    }
+
+    #[test]
+    fn test_rotate_skips_entries_that_fail_to_decrypt() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+        let entry = |title: &str| PasswordEntry::new(title.to_string(), "user".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry("Before"), &manager.encrypt(&key, b"letmein").unwrap()).unwrap();
+        repo.add_entry(&entry("Corrupt"), b"not a ciphertext").unwrap();
+        repo.add_entry(&entry("After"), &manager.encrypt(&key, b"123456").unwrap()).unwrap();
+
+        let report = repo.rotate_passwords(&key, &PasswordGenerator::new(), |_, _| true).unwrap();
+        let mut rotated: Vec<_> = report.rotated.iter().map(|entry| entry.title.as_str()).collect();
+        rotated.sort_unstable();
+        assert_eq!(rotated, ["After", "Before"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0.title, "Corrupt");
+
+        // The corrupt entry is left as it was
+        let (_, encrypted) = repo.get_entry_by_title("Corrupt").unwrap();
+        assert_eq!(encrypted, b"not a ciphertext");
+        assert!(repo.password_history(&report.failed[0].0.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rotate_keeps_the_replaced_password_in_history() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut repo = PasswordRepository::new(temp_file.path()).unwrap();
+        repo.set_history_limit(1);
+
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+        let entry = PasswordEntry::new("Bank".to_string(), "user".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &manager.encrypt(&key, b"letmein").unwrap()).unwrap();
+
+        repo.rotate_passwords(&key, &PasswordGenerator::new(), |_, _| true).unwrap();
+        let history = repo.password_history(&entry.id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(repo.decrypt_history_password(&history[0], &key).unwrap().as_str(), "letmein");
+
+        // The history limit still applies
+        let (_, first_rotation) = repo.get_entry_by_title("Bank").unwrap();
+        repo.rotate_passwords(&key, &PasswordGenerator::new(), |_, _| true).unwrap();
+        let history = repo.password_history(&entry.id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].encrypted_password, first_rotation);
+    }
+
+    #[test]
+    fn test_init_refused_over_initialized_vault() {
+        let temp_file = NamedTempFile::new().unwrap();
+        assert_eq!(PasswordRepository::inspect(temp_file.path()).unwrap(), DatabaseState::Empty);
+
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+        let state = PasswordRepository::inspect(temp_file.path()).unwrap();
+        assert_eq!(state, DatabaseState::Uninitialized);
+        assert!(state.check_initializable(false).is_ok());
+
+        repo.initialize_vault(vec![1, 2, 3], vec![4, 5, 6]).unwrap();
+        let state = PasswordRepository::inspect(temp_file.path()).unwrap();
+        assert_eq!(state, DatabaseState::Initialized);
+        assert!(matches!(state.check_initializable(false), Err(Error::VaultAlreadyExists)));
+        assert!(state.check_initializable(true).is_ok());
+    }
+
+    #[test]
+    fn test_init_refused_over_foreign_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        std::fs::write(temp_file.path(), b"definitely not a sqlite database, just some text").unwrap();
+        let state = PasswordRepository::inspect(temp_file.path()).unwrap();
+        assert_eq!(state, DatabaseState::Foreign);
+        assert!(matches!(state.check_initializable(true), Err(Error::InvalidInput(_))));
+
+        let other_db = NamedTempFile::new().unwrap();
+        let conn = Connection::open(other_db.path()).unwrap();
+        conn.execute("CREATE TABLE notes (body TEXT)", []).unwrap();
+        drop(conn);
+        let before = std::fs::read(other_db.path()).unwrap();
+
+        let state = PasswordRepository::inspect(other_db.path()).unwrap();
+        assert_eq!(state, DatabaseState::Foreign);
+        assert!(state.check_initializable(false).is_err());
+        assert_eq!(std::fs::read(other_db.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn test_password_changed_at_tracks_password_only() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+
+        let mut entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::default(),
+            None,
+            None,
+        );
+        let original_ciphertext = vec![1u8; 32];
+        repo.add_entry(&entry, &original_ciphertext).unwrap();
+        let created = entry.password_changed_at;
+
+        entry.url = Some("https://github.com".to_string());
+        entry.updated_at += chrono::Duration::seconds(5);
+        repo.update_entry(&entry, &original_ciphertext).unwrap();
+
+        let (stored, _) = repo.get_entry_by_id(&entry.id).unwrap();
+        assert_eq!(stored.url.as_deref(), Some("https://github.com"));
+        assert_eq!(stored.password_changed_at, created);
+        assert!(stored.updated_at > created);
+
+        entry.updated_at += chrono::Duration::seconds(5);
+        repo.update_entry(&entry, &[2u8; 32]).unwrap();
+
+        let (stored, encrypted) = repo.get_entry_by_id(&entry.id).unwrap();
+        assert_eq!(encrypted, vec![2u8; 32]);
+        assert_eq!(stored.password_changed_at, entry.updated_at);
+    }
+
+    #[test]
+    fn test_iter_decrypted_flags_corrupt_entry() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let repo = PasswordRepository::new(temp_file.path()).unwrap();
+
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+        for title in ["Intact", "Corrupt"] {
+            let entry = PasswordEntry::new(
+                title.to_string(),
+                "user".to_string(),
+                SecureString::default(),
+                None,
+                None,
+            );
+            let encrypted = manager.encrypt(&key, b"secret").unwrap();
+            repo.add_entry(&entry, &encrypted).unwrap();
+        }
+
+        let (_, mut encrypted) = repo.get_entry_by_title("Corrupt").unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0xff;
+        repo.conn
+            .execute(
+                "UPDATE password_entries SET encrypted_password = ?1 WHERE title = 'Corrupt'",
+                params![encrypted],
+            )
+            .unwrap();
+
+        let results: Vec<_> = repo.iter_decrypted(&key).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        for (entry, password) in results {
+            match entry.title.as_str() {
+                "Intact" => assert_eq!(password.unwrap().as_str(), "secret"),
+                "Corrupt" => assert!(password.is_err()),
+                other => panic!("unexpected entry {}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reencrypt_to_aes_gcm() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        repo.initialize_vault(vec![1, 2, 3, 4], vec![5, 6, 7, 8]).unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+        assert_eq!(repo.cipher().unwrap(), Cipher::ChaCha20Poly1305);
+
+        let manager = repo.encryption_manager().unwrap();
+        for (title, password) in [("GitHub", "hunter2"), ("Bank", "correct horse")] {
+            let entry = PasswordEntry::new(title.to_string(), "me".to_string(), SecureString::default(), None, None);
+            repo.add_entry(&entry, &manager.encrypt(&key, password.as_bytes()).unwrap()).unwrap();
+        }
+        let github = repo.get_entry("GitHub").unwrap().0;
+        repo.set_totp_secret(&github.id, Some("JBSWY3DP"), &key).unwrap();
+        let pin = CustomField { name: "pin".to_string(), kind: FieldKind::Secret, value: SecureString::from("1234") };
+        repo.add_custom_fields(&github.id, std::slice::from_ref(&pin), &key).unwrap();
+        repo.change_password("Bank", "tr0ub4dor", &key).unwrap();
+        repo.change_password("Bank", "correct horse", &key).unwrap();
+
+        assert_eq!(repo.reencrypt(&key, Cipher::Aes256Gcm).unwrap(), 6);
+        assert_eq!(repo.cipher().unwrap(), Cipher::Aes256Gcm);
+
+        let aes = EncryptionManager::with_cipher(Cipher::Aes256Gcm);
+        for (_, encrypted_password) in repo.list_entries_with_passwords().unwrap() {
+            assert!(aes.decrypt(&key, &encrypted_password).is_ok());
+            assert_eq!(encrypted_password[1], Cipher::Aes256Gcm.id());
+        }
+        assert_eq!(repo.get_entry_decrypted("Bank", &key).unwrap().password.as_str(), "correct horse");
+        assert_eq!(repo.get_totp_secret(&github.id, &key).unwrap().unwrap().as_str(), "JBSWY3DP");
+        assert_eq!(repo.get_custom_fields(&github.id, &key).unwrap()[0].value.as_str(), "1234");
+        let bank = repo.get_entry("Bank").unwrap().0;
+        let history = repo.password_history(&bank.id).unwrap();
+        assert_eq!(repo.decrypt_history_password(&history[0], &key).unwrap().as_str(), "tr0ub4dor");
+    }
+
+    #[test]
+    fn test_vault_file_is_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.db");
+        let repo = PasswordRepository::open(&path, "master").unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "octocat".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+        drop(repo);
+
+        let contents = std::fs::read(&path).unwrap();
+        assert!(!contents.starts_with(SQLITE_HEADER));
+        assert!(!contents.windows(7).any(|w| w == b"octocat"));
+        assert_eq!(PasswordRepository::inspect(&path).unwrap(), DatabaseState::Encrypted);
+
+        assert!(matches!(PasswordRepository::open(&path, "wrong"), Err(Error::Authentication(_))));
+        assert!(PasswordRepository::new(&path).is_err());
+        assert_eq!(PasswordRepository::open(&path, "master").unwrap().count_entries().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_encrypt_plaintext_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.db");
+        let repo = PasswordRepository::new(&path).unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "octocat".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, b"ciphertext").unwrap();
+        drop(repo);
+
+        // Legacy plaintext vaults still open, ignoring the password
+        assert!(PasswordRepository::is_plaintext_file(&path).unwrap());
+        assert_eq!(PasswordRepository::open(&path, "anything").unwrap().count_entries().unwrap(), 1);
+
+        PasswordRepository::encrypt_file(&path, "master").unwrap();
+        assert!(!PasswordRepository::is_plaintext_file(&path).unwrap());
+        assert!(PasswordRepository::open(&path, "wrong").is_err());
+        let repo = PasswordRepository::open(&path, "master").unwrap();
+        assert_eq!(repo.list_entries().unwrap()[0].title, "GitHub");
+        assert!(PasswordRepository::encrypt_file(&path, "master").is_err());
+    }
+
+    #[test]
+    fn test_open_readonly_alongside_writer() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let writer = PasswordRepository::open(temp_file.path(), "master").unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "me".to_string(), SecureString::default(), None, None);
+        writer.add_entry(&entry, &EncryptionManager::new().encrypt(&key, b"hunter2").unwrap()).unwrap();
+
+        let reader = PasswordRepository::open_readonly(temp_file.path(), "master").unwrap();
+        assert!(reader.is_readonly());
+        assert!(!writer.is_readonly());
+        let titles: Vec<String> = reader.list_entries().unwrap().into_iter().map(|e| e.title).collect();
+        assert_eq!(titles, vec!["GitHub".to_string()]);
+        assert_eq!(reader.get_entry_decrypted("GitHub", &key).unwrap().password.as_str(), "hunter2");
+
+        let other = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        assert!(reader.add_entry(&other, b"ciphertext").is_err());
+        writer.add_entry(&other, b"ciphertext").unwrap();
+        assert_eq!(reader.count_entries().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_deep_search_matches_encrypted_fields() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+        let manager = repo.encryption_manager().unwrap();
+        for title in ["Router", "Bank"] {
+            let entry = PasswordEntry::new(title.to_string(), "admin".to_string(), SecureString::default(), None, None);
+            repo.add_entry(&entry, &manager.encrypt(&key, b"hunter2").unwrap()).unwrap();
+        }
+        let router = repo.get_entry("Router").unwrap().0;
+        let bank = repo.get_entry("Bank").unwrap().0;
+        let field = |name: &str, kind, value: &str| CustomField { name: name.to_string(), kind, value: SecureString::from(value) };
+        repo.add_custom_fields(&router.id, &[field("memo", FieldKind::Text, "Firmware pinned to v2.3")], &key).unwrap();
+        repo.add_custom_fields(&bank.id, &[field("pin", FieldKind::Secret, "firmware")], &key).unwrap();
+
+        assert!(repo.search_entries_in("firmware", &SearchField::ALL).unwrap().is_empty());
+
+        let matches = repo.deep_search("firmware", &SearchField::ALL, &key).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].entry.title, "Router");
+        assert_eq!(matches[0].encrypted_fields, vec!["memo".to_string()]);
+
+        let matches = repo.deep_search("admin", &SearchField::ALL, &key).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.encrypted_fields.is_empty()));
+    }
+
+    #[test]
+    fn test_kdf_params_default_for_older_vaults() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        repo.initialize_vault(vec![1, 2, 3, 4], vec![5, 6, 7, 8]).unwrap();
+        assert_eq!(repo.kdf_params().unwrap(), Argon2Params::default());
+
+        let kdf = Argon2Params { m_cost: 64 * 1024, t_cost: 3, p_cost: 2 };
+        repo.set_kdf_params(&kdf).unwrap();
+        assert_eq!(repo.kdf_params().unwrap(), kdf);
+
+        assert!(repo.set_kdf_params(&Argon2Params { t_cost: 0, ..kdf }).is_err());
+    }
+
+    #[test]
+    fn test_password_history_is_capped_and_deleted_with_entry() {
+        let mut repo = PasswordRepository::new(":memory:").unwrap();
+        repo.set_history_limit(2);
+        let key = EncryptionManager::new().generate_key().unwrap();
+        let entry = PasswordEntry::new("GitHub".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"first").unwrap()).unwrap();
+
+        repo.update_entry_fields("GitHub", &UpdateSpec { username: Some("octocat".to_string()), ..Default::default() }).unwrap();
+        assert!(repo.password_history(&entry.id).unwrap().is_empty());
+
+        for password in ["second", "third", "fourth"] {
+            repo.change_password("GitHub", password, &key).unwrap();
+        }
+        let history = repo.password_history(&entry.id).unwrap();
+        let previous: Vec<_> = history
+            .iter()
+            .map(|h| repo.decrypt_history_password(h, &key).unwrap().as_str().to_string())
+            .collect();
+        assert_eq!(previous, vec!["third", "second"]);
+        assert!(history[0].changed_at >= history[1].changed_at);
+
+        repo.delete_entry(&entry.id).unwrap();
+        assert!(repo.password_history(&entry.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tags_filter_entries_and_are_pruned() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let mut github = PasswordEntry::new("GitHub".to_string(), "me".to_string(), SecureString::default(), None, None);
+        github.tags = vec!["Work".to_string(), " dev ".to_string()];
+        let bank = PasswordEntry::new("Bank".to_string(), "me".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&github, &[]).unwrap();
+        repo.add_entry(&bank, &[]).unwrap();
+
+        let (read, _) = repo.get_entry("GitHub").unwrap();
+        assert_eq!(read.tags, vec!["dev", "work"]);
+        assert!(repo.list_entries().unwrap().iter().any(|e| e.title == "Bank" && e.tags.is_empty()));
+
+        repo.add_tags(&bank.id, &["work".to_string()]).unwrap();
+        let titles = |tag| repo.list_entries_by_tag(tag).unwrap().into_iter().map(|e| e.title).collect::<Vec<_>>();
+        assert_eq!(titles("WORK"), vec!["Bank", "GitHub"]);
+
+        repo.remove_tags(&github.id, &["dev".to_string(), "work".to_string()]).unwrap();
+        assert_eq!(titles("work"), vec!["Bank"]);
+        assert!(titles("dev").is_empty());
+        let remaining: i64 = repo.conn.query_row("SELECT COUNT(*) FROM tags", [], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 1);
+        assert!(repo.add_tags(&bank.id, &["  ".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_search_fts_ranks_and_stays_in_sync() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        assert!(repo.has_fts().unwrap());
+        for (title, url, notes) in [
+            ("Mail", None, Some("backup codes for github")),
+            ("GitHub", Some("https://github.com"), None),
+            ("Bank", None, Some("branch office")),
+        ] {
+            let entry = PasswordEntry::new(
+                title.to_string(),
+                "me".to_string(),
+                SecureString::default(),
+                url.map(str::to_string),
+                notes.map(str::to_string),
+            );
+            repo.add_entry(&entry, &[]).unwrap();
+        }
+        let titles = |query| repo.search_fts(query).unwrap().into_iter().map(|e| e.title).collect::<Vec<_>>();
+
+        assert_eq!(titles("github"), vec!["GitHub", "Mail"]);
+        assert_eq!(titles("bran"), vec!["Bank"]);
+        assert_eq!(titles("\"unbalanced OR"), Vec::<String>::new());
+
+        let spec = UpdateSpec { title: Some("Savings".to_string()), notes: FieldUpdate::Clear, ..Default::default() };
+        repo.update_entry_fields("Bank", &spec).unwrap();
+        assert!(titles("branch").is_empty());
+        assert_eq!(titles("savings"), vec!["Savings"]);
+
+        repo.soft_delete_entry("Mail").unwrap();
+        assert_eq!(titles("github"), vec!["GitHub"]);
+        repo.delete_entry_by_title("GitHub").unwrap();
+        assert!(titles("github").is_empty());
+        let indexed: i64 = repo.conn.query_row("SELECT COUNT(*) FROM password_entries_fts", [], |row| row.get(0)).unwrap();
+        assert_eq!(indexed, 2);
+    }
 }
@@ -0,0 +1,79 @@
+use crate::database::PasswordRepository;
+use crate::Result;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// A repository that can be shared between threads and async tasks
+///
+/// `rusqlite::Connection` is `Send` but not `Sync`, so the repository sits
+/// behind a mutex and callers take turns. Database calls block, so async code
+/// should go through `run`, which moves them onto tokio's blocking pool
+/// instead of stalling the executor.
+#[derive(Clone)]
+pub struct SharedRepository {
+    inner: Arc<Mutex<PasswordRepository>>,
+}
+
+impl SharedRepository {
+    pub fn new(repo: PasswordRepository) -> Self {
+        Self { inner: Arc::new(Mutex::new(repo)) }
+    }
+
+    /// Run `f` with exclusive access to the repository on the blocking pool
+    ///
+    /// A panic in `f` is propagated to the caller.
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&PasswordRepository) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        tokio::task::spawn_blocking(move || f(&Self::lock_inner(&inner)))
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+
+    /// Run `f` with exclusive access to the repository on the current thread
+    pub fn blocking<T>(&self, f: impl FnOnce(&PasswordRepository) -> Result<T>) -> Result<T> {
+        f(&Self::lock_inner(&self.inner))
+    }
+
+    // A panic mid-call leaves no half-applied state behind: open transactions
+    // are rolled back when they're dropped, so a poisoned lock is still usable
+    fn lock_inner(inner: &Mutex<PasswordRepository>) -> MutexGuard<'_, PasswordRepository> {
+        inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{PasswordEntry, SecureString};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[tokio::test]
+    async fn test_concurrent_tasks_share_one_repository() {
+        assert_send_sync::<SharedRepository>();
+        let shared = SharedRepository::new(PasswordRepository::new(":memory:").unwrap());
+
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    shared
+                        .run(move |repo| {
+                            let entry = PasswordEntry::new(format!("Entry {}", i), "me".to_string(), SecureString::default(), None, None);
+                            repo.add_entry(&entry, &[])
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(shared.run(|repo| repo.count_entries()).await.unwrap(), 8);
+        assert_eq!(shared.blocking(|repo| repo.list_entries()).unwrap().len(), 8);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::database::{CustomField, FieldKind, PasswordEntry, SecureString};
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Fields stored on the entry itself rather than as custom fields
+const STANDARD_FIELDS: [&str; 4] = ["username", "password", "url", "notes"];
+
+/// Field prompted for when creating an entry from a template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub name: String,
+    #[serde(default = "default_kind")]
+    pub kind: FieldKind,
+}
+
+/// Named set of fields for a common kind of account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryTemplate {
+    pub name: String,
+    pub fields: Vec<TemplateField>,
+}
+
+fn default_kind() -> FieldKind {
+    FieldKind::Text
+}
+
+impl TemplateField {
+    fn new(name: &str, kind: FieldKind) -> Self {
+        Self { name: name.to_string(), kind }
+    }
+
+    /// Whether this field maps onto a standard entry field
+    pub fn is_standard(&self) -> bool {
+        STANDARD_FIELDS.contains(&self.name.as_str())
+    }
+}
+
+impl EntryTemplate {
+    /// Build an entry, asking `value_for` for each field in order
+    ///
+    /// `username`, `password`, `url` and `notes` fill the entry itself; every
+    /// other field becomes a custom field. Empty optional values are skipped.
+    pub fn build<F>(&self, title: &str, mut value_for: F) -> Result<(PasswordEntry, Vec<CustomField>)>
+    where
+        F: FnMut(&TemplateField) -> Result<String>,
+    {
+        let mut entry = PasswordEntry::new(title.to_string(), String::new(), SecureString::default(), None, None);
+        let mut custom_fields: Vec<CustomField> = Vec::new();
+
+        for (i, field) in self.fields.iter().enumerate() {
+            if self.fields[..i].iter().any(|f| f.name == field.name) {
+                return Err(Error::InvalidInput(format!(
+                    "Template '{}' defines field '{}' more than once",
+                    self.name, field.name
+                )));
+            }
+
+            let value = value_for(field)?;
+            match field.name.as_str() {
+                "username" => entry.username = value,
+                "password" => entry.password = SecureString::new(value),
+                "url" => entry.url = Some(value).filter(|v| !v.is_empty()),
+                "notes" => entry.notes = Some(value).filter(|v| !v.is_empty()),
+                _ if value.is_empty() => {}
+                _ => custom_fields.push(CustomField {
+                    name: field.name.clone(),
+                    kind: field.kind,
+                    value: SecureString::new(value),
+                }),
+            }
+        }
+
+        Ok((entry, custom_fields))
+    }
+}
+
+/// Templates shipped with passman
+pub fn builtin_templates() -> Vec<EntryTemplate> {
+    vec![
+        EntryTemplate {
+            name: "website".to_string(),
+            fields: vec![
+                TemplateField::new("url", FieldKind::Text),
+                TemplateField::new("username", FieldKind::Text),
+                TemplateField::new("password", FieldKind::Secret),
+            ],
+        },
+        EntryTemplate {
+            name: "card".to_string(),
+            fields: vec![
+                TemplateField::new("number", FieldKind::Secret),
+                TemplateField::new("expiry", FieldKind::Text),
+                TemplateField::new("cvv", FieldKind::Secret),
+            ],
+        },
+        EntryTemplate {
+            name: "ssh-key".to_string(),
+            fields: vec![
+                TemplateField::new("username", FieldKind::Text),
+                TemplateField::new("private_key", FieldKind::File),
+                TemplateField::new("password", FieldKind::Secret),
+            ],
+        },
+    ]
+}
+
+/// Look up a template by name, preferring user-defined templates over built-ins
+pub fn find_template(name: &str, user_templates: &[EntryTemplate]) -> Result<EntryTemplate> {
+    let builtins = builtin_templates();
+    user_templates
+        .iter()
+        .chain(builtins.iter())
+        .find(|template| template.name == name)
+        .cloned()
+        .ok_or_else(|| {
+            let mut names: Vec<&str> = Vec::new();
+            for template in user_templates.iter().chain(builtins.iter()) {
+                if !names.contains(&template.name.as_str()) {
+                    names.push(&template.name);
+                }
+            }
+            Error::InvalidInput(format!("Unknown template '{}' (available: {})", name, names.join(", ")))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_template_creates_custom_fields() {
+        let template = find_template("card", &[]).unwrap();
+        let (entry, fields) = template
+            .build("Visa", |field| {
+                Ok(match field.name.as_str() {
+                    "number" => "4111111111111111",
+                    "expiry" => "12/30",
+                    "cvv" => "123",
+                    other => panic!("unexpected field {}", other),
+                }
+                .to_string())
+            })
+            .unwrap();
+
+        assert_eq!(entry.title, "Visa");
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.kind, f.value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("number", FieldKind::Secret, "4111111111111111"),
+                ("expiry", FieldKind::Text, "12/30"),
+                ("cvv", FieldKind::Secret, "123"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_user_template_overrides_builtin() {
+        let custom = EntryTemplate {
+            name: "website".to_string(),
+            fields: vec![TemplateField::new("username", FieldKind::Text)],
+        };
+
+        let user_templates = vec![custom];
+        assert_eq!(find_template("website", &user_templates).unwrap(), user_templates[0]);
+        assert!(find_template("nope", &[]).is_err());
+    }
+}
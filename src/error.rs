@@ -39,6 +39,14 @@ pub enum Error {
     #[error("Clipboard error: {0}")]
     Clipboard(String),
 
+    /// No clipboard is available on this system (e.g. headless or CI)
+    #[error("Clipboard unavailable: {0}")]
+    ClipboardUnavailable(String),
+
+    /// Network errors, e.g. from the breach check
+    #[error("Network error: {0}")]
+    Network(String),
+
     /// Password generation errors
     #[error("Password generation error: {0}")]
     PasswordGeneration(String),
@@ -47,6 +55,14 @@ pub enum Error {
     #[error("Vault not initialized. Run 'passman init' first")]
     VaultNotInitialized,
 
+    /// Vault was written in an encryption format this build cannot read
+    #[error("Incompatible vault: {0}")]
+    IncompatibleVault(String),
+
+    /// The master password verified but the derived key can't decrypt the vault
+    #[error("Master password accepted but the vault cannot be decrypted; its metadata (salt or KDF parameters) may be corrupt")]
+    KeyMismatch,
+
     /// Vault already exists
     #[error("Vault already exists")]
     VaultAlreadyExists,
@@ -0,0 +1,198 @@
+//! Import of unencrypted Bitwarden JSON exports
+//!
+//! Only login items are imported. Bitwarden has no tags, so an item's folder
+//! is kept as a `Folder: <name>` line at the top of its notes.
+
+use super::ExportedEntry;
+use crate::crypto::totp;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use zeroize::Zeroize;
+
+/// Bitwarden's item type for logins; notes, cards and identities are skipped
+const LOGIN_ITEM_TYPE: u32 = 1;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenExport {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<BitwardenFolder>,
+    #[serde(default)]
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenFolder {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenItem {
+    #[serde(rename = "type")]
+    kind: u32,
+    name: String,
+    notes: Option<String>,
+    folder_id: Option<String>,
+    login: Option<BitwardenLogin>,
+    creation_date: Option<DateTime<Utc>>,
+    revision_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    #[serde(default)]
+    uris: Vec<BitwardenUri>,
+}
+
+impl Drop for BitwardenLogin {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.totp.zeroize();
+    }
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+/// Login entries read from a Bitwarden export
+#[derive(Debug, Default)]
+pub struct BitwardenImport {
+    pub entries: Vec<ExportedEntry>,
+    /// Items without a login section, such as secure notes and cards
+    pub skipped_non_login: usize,
+}
+
+/// Parse an unencrypted Bitwarden JSON export
+pub fn parse_bitwarden(data: &[u8]) -> Result<BitwardenImport> {
+    let export: BitwardenExport = serde_json::from_slice(data)?;
+    if export.encrypted {
+        return Err(Error::InvalidInput(
+            "Encrypted Bitwarden exports are not supported; export as unencrypted JSON".to_string(),
+        ));
+    }
+
+    let folders: HashMap<&str, &str> = export
+        .folders
+        .iter()
+        .map(|folder| (folder.id.as_str(), folder.name.as_str()))
+        .collect();
+
+    let mut import = BitwardenImport::default();
+    for item in &export.items {
+        let login = match &item.login {
+            Some(login) if item.kind == LOGIN_ITEM_TYPE => login,
+            _ => {
+                log::warn!("Skipping '{}': not a login item", item.name);
+                import.skipped_non_login += 1;
+                continue;
+            }
+        };
+
+        let folder = item.folder_id.as_deref().and_then(|id| folders.get(id));
+        let notes = match (folder, item.notes.as_deref().filter(|n| !n.is_empty())) {
+            (Some(folder), Some(notes)) => Some(format!("Folder: {}\n{}", folder, notes)),
+            (Some(folder), None) => Some(format!("Folder: {}", folder)),
+            (None, notes) => notes.map(str::to_string),
+        };
+        let now = Utc::now();
+
+        import.entries.push(ExportedEntry {
+            title: item.name.clone(),
+            username: login.username.clone().unwrap_or_default(),
+            password: login.password.clone().unwrap_or_default(),
+            url: login.uris.iter().find_map(|uri| uri.uri.clone()),
+            notes,
+            created_at: item.creation_date.unwrap_or(now),
+            updated_at: item.revision_date.unwrap_or(now),
+            totp_secret: login.totp.as_deref().and_then(|value| totp_secret(&item.name, value)),
+            custom_fields: Vec::new(),
+        });
+    }
+
+    Ok(import)
+}
+
+/// Extract a base32 secret from a TOTP value, which may be an `otpauth://` URI
+fn totp_secret(title: &str, value: &str) -> Option<String> {
+    let secret = match value.strip_prefix("otpauth://") {
+        Some(uri) => uri
+            .split_once('?')?
+            .1
+            .split('&')
+            .find_map(|param| param.strip_prefix("secret="))?,
+        None => value,
+    };
+
+    match totp::decode_base32(secret) {
+        Ok(_) => Some(secret.to_string()),
+        Err(_) => {
+            log::warn!("Dropping the TOTP secret of '{}': it is not valid base32", title);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{
+        "encrypted": false,
+        "folders": [{ "id": "f1", "name": "Work" }],
+        "items": [
+            {
+                "type": 1,
+                "name": "GitHub",
+                "folderId": "f1",
+                "notes": "2FA on",
+                "login": {
+                    "username": "octocat",
+                    "password": "hunter2",
+                    "totp": "otpauth://totp/GitHub:octocat?secret=JBSWY3DPEHPK3PXP&issuer=GitHub",
+                    "uris": [{ "match": null, "uri": "https://github.com" }]
+                },
+                "revisionDate": "2024-01-02T03:04:05.000Z"
+            },
+            { "type": 1, "name": "Bare", "folderId": null, "login": { "username": null, "password": "x", "uris": [] } },
+            { "type": 2, "name": "Recovery codes", "notes": "1234", "secureNote": { "type": 0 } },
+            { "type": 3, "name": "Visa", "card": { "number": "4111" } }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_bitwarden_export() {
+        let import = parse_bitwarden(EXPORT.as_bytes()).unwrap();
+        assert_eq!(import.skipped_non_login, 2);
+        assert_eq!(import.entries.len(), 2);
+
+        let github = &import.entries[0];
+        assert_eq!(github.title, "GitHub");
+        assert_eq!(github.username, "octocat");
+        assert_eq!(github.password, "hunter2");
+        assert_eq!(github.url.as_deref(), Some("https://github.com"));
+        assert_eq!(github.notes.as_deref(), Some("Folder: Work\n2FA on"));
+        assert_eq!(github.totp_secret.as_deref(), Some("JBSWY3DPEHPK3PXP"));
+        assert_eq!(github.updated_at.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+
+        let bare = &import.entries[1];
+        assert_eq!(bare.username, "");
+        assert_eq!((bare.url.as_deref(), bare.notes.as_deref()), (None, None));
+    }
+
+    #[test]
+    fn test_encrypted_bitwarden_export_is_rejected() {
+        let result = parse_bitwarden(br#"{ "encrypted": true, "items": [] }"#);
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+}
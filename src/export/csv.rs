@@ -0,0 +1,129 @@
+//! Import of `title,username,password,url,notes` CSV files
+//!
+//! Rows are validated individually so one malformed row is reported by line
+//! number instead of aborting the import; the valid rows are then stored with
+//! `store_imported`.
+
+use super::ExportedEntry;
+use crate::utils::csv::parse_records;
+use chrono::Utc;
+
+/// Columns expected in a CSV import, in order; `url` and `notes` may be omitted
+pub const CSV_COLUMNS: [&str; 5] = ["title", "username", "password", "url", "notes"];
+
+/// A row that couldn't be imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Entries read from a CSV file, with the rows that were rejected
+#[derive(Debug, Default)]
+pub struct CsvImport {
+    pub entries: Vec<ExportedEntry>,
+    pub errors: Vec<RowError>,
+}
+
+/// Parse CSV text into entries, skipping a leading header row
+pub fn parse_csv(text: &str) -> CsvImport {
+    let mut import = CsvImport::default();
+
+    for (index, record) in parse_records(text).into_iter().enumerate() {
+        let result = record.fields.and_then(|fields| {
+            if index == 0 && is_header(&fields) {
+                return Ok(None);
+            }
+            row_to_entry(fields).map(Some)
+        });
+
+        match result {
+            Ok(Some(entry)) => import.entries.push(entry),
+            Ok(None) => {}
+            Err(message) => import.errors.push(RowError { line: record.line, message }),
+        }
+    }
+
+    import
+}
+
+fn is_header(fields: &[String]) -> bool {
+    fields.len() >= 3
+        && fields
+            .iter()
+            .zip(CSV_COLUMNS)
+            .all(|(field, column)| field.trim().eq_ignore_ascii_case(column))
+}
+
+fn row_to_entry(mut fields: Vec<String>) -> std::result::Result<ExportedEntry, String> {
+    if !(3..=CSV_COLUMNS.len()).contains(&fields.len()) {
+        return Err(format!(
+            "expected 3 to {} fields ({}), found {}",
+            CSV_COLUMNS.len(),
+            CSV_COLUMNS.join(","),
+            fields.len()
+        ));
+    }
+    if fields[0].trim().is_empty() {
+        return Err("title is empty".to_string());
+    }
+
+    fields.resize(CSV_COLUMNS.len(), String::new());
+    let optional = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+    let now = Utc::now();
+
+    Ok(ExportedEntry {
+        title: fields[0].trim().to_string(),
+        username: fields[1].clone(),
+        password: std::mem::take(&mut fields[2]),
+        url: optional(&fields[3]),
+        notes: optional(&fields[4]),
+        created_at: now,
+        updated_at: now,
+        totp_secret: None,
+        custom_fields: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::EncryptionManager;
+    use crate::database::{PasswordEntry, PasswordRepository, SecureString};
+    use crate::export::{store_imported, ImportSummary};
+
+    #[test]
+    fn test_csv_import() {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let key = EncryptionManager::new().generate_key().unwrap();
+        let existing = PasswordEntry::new("GitHub".to_string(), "old".to_string(), SecureString::default(), None, None);
+        repo.add_entry(&existing, &EncryptionManager::new().encrypt(&key, b"old password").unwrap())
+            .unwrap();
+
+        let text = "title,username,password,url,notes\n\
+                    GitHub,octocat,hunter2,https://github.com,\n\
+                    only-two,fields\n\
+                    Mail,me@example.com,\"pa,ss\"\n\
+                    ,nobody,secret\n";
+        let import = parse_csv(text);
+
+        assert_eq!(import.entries.len(), 2);
+        assert_eq!(
+            import.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+        assert_eq!(import.entries[1].password, "pa,ss");
+        assert_eq!(import.entries[1].url, None);
+
+        let summary = store_imported(&repo, &key, &import.entries, false).unwrap();
+        assert_eq!(summary, ImportSummary { imported: 1, overwritten: 0, skipped: 1 });
+        assert_eq!(repo.get_entry_decrypted("GitHub", &key).unwrap().username, "old");
+
+        let summary = store_imported(&repo, &key, &import.entries, true).unwrap();
+        assert_eq!(summary, ImportSummary { imported: 0, overwritten: 2, skipped: 0 });
+        let github = repo.get_entry_decrypted("GitHub", &key).unwrap();
+        assert_eq!(github.username, "octocat");
+        assert_eq!(github.password.as_str(), "hunter2");
+        assert_eq!(repo.get_entry_decrypted("Mail", &key).unwrap().password.as_str(), "pa,ss");
+    }
+}
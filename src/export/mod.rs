@@ -0,0 +1,442 @@
+//! Encrypted vault export and import
+//!
+//! An export is a JSON envelope whose payload is the serialized entries
+//! encrypted under a key derived from a separate export passphrase, so backups
+//! don't depend on the vault's master password.
+
+use crate::crypto::{EncryptionManager, PasswordManager};
+use crate::database::{CustomField, FieldKind, PasswordEntry, PasswordRepository, SecureString};
+use crate::{Error, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use zeroize::{Zeroize, Zeroizing};
+
+pub mod bitwarden;
+pub mod csv;
+
+/// Envelope format version written by this build
+pub const FORMAT_VERSION: u32 = 1;
+
+/// File formats `export` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// JSON list of entries, encrypted under an export passphrase unless `--plaintext`
+    Json,
+}
+
+/// File formats `import` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// Encrypted backup written by `export --format json`
+    #[value(alias = "json")]
+    Backup,
+    /// Plain `title,username,password,url,notes` rows
+    Csv,
+    /// Unencrypted JSON export from Bitwarden
+    Bitwarden,
+}
+
+/// Outer, unencrypted part of an export file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportEnvelope {
+    pub format_version: u32,
+    /// Base64 salt for deriving the export key from the passphrase
+    pub kdf_salt: String,
+    /// Whether the payload was gzip-compressed before encryption
+    #[serde(default)]
+    pub compressed: bool,
+    /// Base64 encrypted payload
+    pub payload: String,
+}
+
+/// Custom field as stored in an export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub value: String,
+}
+
+/// Entry with its secrets in plaintext, only ever held in memory or encrypted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedEntry {
+    pub title: String,
+    pub username: String,
+    pub password: String,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    #[serde(default)]
+    pub custom_fields: Vec<ExportedField>,
+}
+
+impl ExportedEntry {
+    /// Whether both entries hold the same data, ignoring timestamps
+    pub fn same_content(&self, other: &ExportedEntry) -> bool {
+        self.title == other.title
+            && self.username == other.username
+            && self.password == other.password
+            && self.url == other.url
+            && self.notes == other.notes
+            && self.totp_secret == other.totp_secret
+            && self.custom_fields == other.custom_fields
+    }
+}
+
+impl Drop for ExportedEntry {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.totp_secret.zeroize();
+        for field in &mut self.custom_fields {
+            field.value.zeroize();
+        }
+    }
+}
+
+/// Decrypt every entry in the vault into its exportable form
+pub fn collect_entries(repo: &PasswordRepository, key: &[u8]) -> Result<Vec<ExportedEntry>> {
+    repo.iter_decrypted(key)?
+        .map(|(entry, password)| exported_entry(repo, key, entry, password?))
+        .collect()
+}
+
+/// Decrypt a single entry, by title or ID prefix, into its exportable form
+pub fn collect_entry(repo: &PasswordRepository, key: &[u8], title_or_id: &str) -> Result<ExportedEntry> {
+    let mut entry = repo.get_entry_decrypted(title_or_id, key)?;
+    let password = std::mem::take(&mut entry.password);
+    exported_entry(repo, key, entry, password)
+}
+
+fn exported_entry(
+    repo: &PasswordRepository,
+    key: &[u8],
+    entry: PasswordEntry,
+    password: SecureString,
+) -> Result<ExportedEntry> {
+    let totp_secret = repo.get_totp_secret(&entry.id, key)?;
+    let custom_fields = repo
+        .get_custom_fields(&entry.id, key)?
+        .into_iter()
+        .map(|field| ExportedField {
+            value: field.value.as_str().to_string(),
+            name: field.name,
+            kind: field.kind,
+        })
+        .collect();
+
+    Ok(ExportedEntry {
+        title: entry.title,
+        username: entry.username,
+        password: password.as_str().to_string(),
+        url: entry.url,
+        notes: entry.notes,
+        created_at: entry.created_at,
+        updated_at: entry.updated_at,
+        totp_secret: totp_secret.map(|secret| secret.as_str().to_string()),
+        custom_fields,
+    })
+}
+
+/// Serialize and encrypt entries into an export file, optionally gzip-compressed
+pub fn export_entries(entries: &[ExportedEntry], passphrase: &str, compress: bool) -> Result<Vec<u8>> {
+    let password_manager = PasswordManager::new();
+    let salt = password_manager.generate_salt()?;
+    let key = Zeroizing::new(password_manager.derive_key(passphrase, &salt)?);
+
+    let mut payload = Zeroizing::new(serde_json::to_vec(entries)?);
+    if compress {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&payload)?;
+        payload = Zeroizing::new(encoder.finish()?);
+    }
+
+    let encrypted = EncryptionManager::new().encrypt(&key, &payload)?;
+    let base64 = base64::engine::general_purpose::STANDARD;
+    let envelope = ExportEnvelope {
+        format_version: FORMAT_VERSION,
+        kdf_salt: base64.encode(&salt),
+        compressed: compress,
+        payload: base64.encode(encrypted),
+    };
+
+    Ok(serde_json::to_vec_pretty(&envelope)?)
+}
+
+/// Serialize entries as unencrypted JSON
+pub fn export_plaintext(entries: &[ExportedEntry]) -> Result<Zeroizing<Vec<u8>>> {
+    Ok(Zeroizing::new(serde_json::to_vec_pretty(entries)?))
+}
+
+/// Decrypt an export file back into entries, decompressing if needed
+pub fn import_entries(data: &[u8], passphrase: &str) -> Result<Vec<ExportedEntry>> {
+    let envelope: ExportEnvelope = serde_json::from_slice(data)?;
+    if envelope.format_version > FORMAT_VERSION {
+        return Err(Error::InvalidInput(format!(
+            "Export format version {} is newer than this build supports ({}); please upgrade passman",
+            envelope.format_version, FORMAT_VERSION
+        )));
+    }
+
+    let base64 = base64::engine::general_purpose::STANDARD;
+    let decode = |field: &str, value: &str| {
+        base64
+            .decode(value)
+            .map_err(|e| Error::InvalidInput(format!("Export {} is not valid base64: {}", field, e)))
+    };
+    let salt = decode("salt", &envelope.kdf_salt)?;
+    let encrypted = decode("payload", &envelope.payload)?;
+
+    let key = Zeroizing::new(PasswordManager::new().derive_key(passphrase, &salt)?);
+    let mut payload = Zeroizing::new(
+        EncryptionManager::new()
+            .decrypt(&key, &encrypted)
+            .map_err(|_| Error::Authentication("Wrong export passphrase or corrupted file".to_string()))?,
+    );
+
+    if envelope.compressed {
+        let mut decompressed = Zeroizing::new(Vec::new());
+        GzDecoder::new(payload.as_slice()).read_to_end(&mut decompressed)?;
+        payload = decompressed;
+    }
+
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+/// What importing a set of entries would do to the vault
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Titles not yet in the vault
+    pub new: Vec<String>,
+    /// Titles already in the vault with different contents
+    pub conflicts: Vec<String>,
+    /// Titles already in the vault with identical contents
+    pub duplicates: Vec<String>,
+}
+
+/// Compare entries against the vault without writing anything
+pub fn import_report(repo: &PasswordRepository, key: &[u8], entries: &[ExportedEntry]) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    for exported in entries {
+        let title = exported.title.clone();
+        if !repo.title_exists(&exported.title)? {
+            report.new.push(title);
+        } else if collect_entry(repo, key, &exported.title)?.same_content(exported) {
+            report.duplicates.push(title);
+        } else {
+            report.conflicts.push(title);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Insert imported entries into the vault, skipping titles that already exist
+///
+/// Returns the number of entries imported.
+pub fn store_entries(repo: &PasswordRepository, key: &[u8], entries: &[ExportedEntry]) -> Result<usize> {
+    let encryption_manager = repo.encryption_manager()?;
+    let mut imported = 0;
+
+    for exported in entries {
+        if repo.title_exists(&exported.title)? {
+            log::warn!("Skipping '{}': an entry with that title already exists", exported.title);
+            continue;
+        }
+
+        let mut entry = PasswordEntry::new(
+            exported.title.clone(),
+            exported.username.clone(),
+            SecureString::default(),
+            exported.url.clone(),
+            exported.notes.clone(),
+        );
+        entry.created_at = exported.created_at;
+        entry.updated_at = exported.updated_at;
+
+        let encrypted = encryption_manager.encrypt(key, exported.password.as_bytes())?;
+        repo.add_entry(&entry, &encrypted)?;
+
+        if let Some(secret) = &exported.totp_secret {
+            repo.set_totp_secret(&entry.id, Some(secret), key)?;
+        }
+
+        let fields: Vec<CustomField> = exported
+            .custom_fields
+            .iter()
+            .map(|field| CustomField {
+                name: field.name.clone(),
+                kind: field.kind,
+                value: SecureString::from(field.value.as_str()),
+            })
+            .collect();
+        repo.add_custom_fields(&entry.id, &fields, key)?;
+
+        imported += 1;
+    }
+
+    Ok(imported)
+}
+
+/// What storing the imported entries did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub overwritten: usize,
+    /// Rows skipped because their title already exists
+    pub skipped: usize,
+}
+
+/// Insert plain imported entries (no custom fields) in one transaction
+///
+/// Entries whose title already exists are skipped, or with `overwrite` have
+/// their username, password, URL and notes replaced. TOTP secrets are set
+/// when the imported entry has one.
+pub fn store_imported(
+    repo: &PasswordRepository,
+    key: &[u8],
+    entries: &[ExportedEntry],
+    overwrite: bool,
+) -> Result<ImportSummary> {
+    let encryption_manager = repo.encryption_manager()?;
+
+    repo.in_transaction(|| {
+        let mut summary = ImportSummary::default();
+
+        for imported in entries {
+            let encrypted = encryption_manager.encrypt(key, imported.password.as_bytes())?;
+
+            if !repo.title_exists(&imported.title)? {
+                let entry = PasswordEntry::new(
+                    imported.title.clone(),
+                    imported.username.clone(),
+                    SecureString::default(),
+                    imported.url.clone(),
+                    imported.notes.clone(),
+                );
+                repo.add_entry(&entry, &encrypted)?;
+                if let Some(secret) = &imported.totp_secret {
+                    repo.set_totp_secret(&entry.id, Some(secret), key)?;
+                }
+                summary.imported += 1;
+            } else if overwrite {
+                let (mut entry, _) = repo.get_entry_by_title(&imported.title)?;
+                entry.username = imported.username.clone();
+                entry.url = imported.url.clone();
+                entry.notes = imported.notes.clone();
+                entry.touch();
+                repo.update_entry(&entry, &encrypted)?;
+                if let Some(secret) = &imported.totp_secret {
+                    repo.set_totp_secret(&entry.id, Some(secret), key)?;
+                }
+                summary.overwritten += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+
+        Ok(summary)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_with_entries() -> (PasswordRepository, Vec<u8>) {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let manager = EncryptionManager::new();
+        let key = manager.generate_key().unwrap();
+
+        for i in 0..20 {
+            let entry = PasswordEntry::new(
+                format!("Site {}", i),
+                "alice@example.com".to_string(),
+                SecureString::default(),
+                Some(format!("https://site{}.example.com", i)),
+                Some("Shared family account, see the recovery codes in the safe".to_string()),
+            );
+            repo.add_entry(&entry, &manager.encrypt(&key, format!("password-{}", i).as_bytes()).unwrap())
+                .unwrap();
+        }
+
+        (repo, key)
+    }
+
+    #[test]
+    fn test_compressed_export_round_trip() {
+        let (repo, key) = vault_with_entries();
+        let entries = collect_entries(&repo, &key).unwrap();
+
+        let compressed = export_entries(&entries, "backup passphrase", true).unwrap();
+        let plain = export_entries(&entries, "backup passphrase", false).unwrap();
+        assert!(compressed.len() < plain.len());
+
+        let imported = import_entries(&compressed, "backup passphrase").unwrap();
+        assert_eq!(imported, entries);
+
+        let fresh = PasswordRepository::new(":memory:").unwrap();
+        let fresh_key = EncryptionManager::new().generate_key().unwrap();
+        assert_eq!(store_entries(&fresh, &fresh_key, &imported).unwrap(), 20);
+        assert_eq!(collect_entries(&fresh, &fresh_key).unwrap(), entries);
+
+        assert!(import_entries(&compressed, "wrong passphrase").is_err());
+    }
+
+    #[test]
+    fn test_export_header_records_salt_and_version() {
+        let (repo, key) = vault_with_entries();
+        let entries = collect_entries(&repo, &key).unwrap();
+
+        let data = export_entries(&entries, "backup passphrase", false).unwrap();
+        let envelope: ExportEnvelope = serde_json::from_slice(&data).unwrap();
+        assert_eq!(envelope.format_version, FORMAT_VERSION);
+        assert!(!envelope.compressed);
+        let salt = base64::engine::general_purpose::STANDARD.decode(&envelope.kdf_salt).unwrap();
+        assert!(!salt.is_empty());
+
+        // Passwords only appear once the payload is decrypted
+        assert!(!String::from_utf8_lossy(&data).contains("password-1"));
+        assert!(String::from_utf8_lossy(&export_plaintext(&entries).unwrap()).contains("password-1"));
+    }
+
+    #[test]
+    fn test_import_report_leaves_vault_untouched() {
+        let (repo, key) = vault_with_entries();
+        let mut entries = vec![
+            collect_entry(&repo, &key, "Site 1").unwrap(),
+            collect_entry(&repo, &key, "Site 2").unwrap(),
+            collect_entry(&repo, &key, "Site 3").unwrap(),
+        ];
+        entries[1].password = "changed elsewhere".to_string();
+        entries[2].title = "Site 99".to_string();
+        let before = collect_entries(&repo, &key).unwrap();
+
+        let report = import_report(&repo, &key, &entries).unwrap();
+        assert_eq!(report.duplicates, vec!["Site 1".to_string()]);
+        assert_eq!(report.conflicts, vec!["Site 2".to_string()]);
+        assert_eq!(report.new, vec!["Site 99".to_string()]);
+        assert_eq!(collect_entries(&repo, &key).unwrap(), before);
+    }
+
+    #[test]
+    fn test_single_entry_export_round_trip() {
+        let (repo, key) = vault_with_entries();
+        let entry = collect_entry(&repo, &key, "Site 7").unwrap();
+
+        let data = export_entries(std::slice::from_ref(&entry), "one-off", false).unwrap();
+        let imported = import_entries(&data, "one-off").unwrap();
+        assert_eq!(imported, vec![entry.clone()]);
+
+        let fresh = PasswordRepository::new(":memory:").unwrap();
+        let fresh_key = EncryptionManager::new().generate_key().unwrap();
+        assert_eq!(store_entries(&fresh, &fresh_key, &imported).unwrap(), 1);
+        assert_eq!(collect_entries(&fresh, &fresh_key).unwrap(), vec![entry]);
+    }
+}
@@ -12,13 +12,22 @@
 //! - **Clipboard integration**: Copy passwords directly to clipboard
 //! - **Cross-platform**: Works on Linux, macOS, and Windows
 
+#[cfg(all(feature = "agent", unix))]
+pub mod agent;
 pub mod cli;
 pub mod config;
 pub mod crypto;
 pub mod database;
 pub mod error;
+pub mod export;
 pub mod utils;
 
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+#[cfg(feature = "tui")]
+pub mod tui;
+
 #[cfg(feature = "web-ui")]
 pub mod web;
 
@@ -32,3 +41,18 @@ pub const DEFAULT_DB_NAME: &str = "passman.db";
 
 /// Configuration file name
 pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Environment variable (also read at build time) overriding the config directory namespace
+pub const NAMESPACE_ENV: &str = "PASSMAN_NAMESPACE";
+
+/// Environment variable selecting the profile when `--profile` isn't given
+pub const PROFILE_ENV: &str = "PASSMAN_PROFILE";
+
+/// Environment variable overriding the vault database path, like `--vault`
+pub const VAULT_ENV: &str = "PASSMAN_VAULT";
+
+/// Environment variable holding the master password for unattended runs
+pub const PASSWORD_ENV: &str = "PASSMAN_PASSWORD";
+
+/// Profile whose config and database live directly in the config directory
+pub const DEFAULT_PROFILE: &str = "default";
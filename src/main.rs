@@ -1,9 +1,22 @@
 use anyhow::Result;
 use clap::Parser;
-use passman_cli::cli::{Cli, Commands};
+use passman_cli::cli::session::{self, Session};
+use passman_cli::cli::{AuditAction, Cli, Commands, ConfigAction, DuressAction, GlobalArgs, ProfileAction, TotpAction};
+use passman_cli::config::Config;
+use passman_cli::crypto::LockedKey;
+use passman_cli::database::PasswordRepository;
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
+    // While this is still the only thread; see `take_password_env`
+    session::take_password_env();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async_main())
+}
+
+async fn async_main() -> Result<()> {
     // Initialize logging
     env_logger::init();
 
@@ -12,72 +25,1526 @@ async fn main() -> Result<()> {
 
     // Execute the command
     match cli.command {
-        Commands::Init { force } => {
-            println!("Initializing vault...");
-            // TODO: Implement vault initialization
+        Commands::Shell => shell(&cli.global),
+        #[cfg(feature = "web-ui")]
+        Commands::Web { port, bind, allow_remote } => {
+            use passman_cli::web::WebServer;
+
+            // Before asking for the master password
+            WebServer::check_bind(bind, allow_remote)?;
+            let config = cli.global.load_config_without_create()?;
+            if !config.database_path.exists() {
+                return Err(vault_missing(&config.database_path));
+            }
+            let (repo, key) = session::unlock(&config)?;
+            let server = WebServer::new(port, repo, key)?
+                .with_bind(bind)
+                .with_allow_remote(allow_remote);
+            server.serve().await?;
+            Ok(())
+        }
+        command => run(command, &cli.global, &mut Vault { session: &mut None, shell: false }),
+    }
+}
+
+/// Hands out the unlocked vault to commands, unlocking it on first use
+///
+/// A one-shot command unlocks once; the shell keeps the session across
+/// commands until `security.session_timeout` has passed.
+struct Vault<'a> {
+    session: &'a mut Option<Session>,
+    shell: bool,
+}
+
+impl Vault<'_> {
+    fn unlock(&mut self, config: &Config) -> Result<(&PasswordRepository, &LockedKey)> {
+        self.open(config, session::unlock)
+    }
+
+    /// Like `unlock`, but read-only for one-shot commands; the shell's session stays writable
+    fn unlock_readonly(&mut self, config: &Config) -> Result<(&PasswordRepository, &LockedKey)> {
+        match self.shell {
+            true => self.open(config, session::unlock),
+            false => self.open(config, session::unlock_readonly),
+        }
+    }
+
+    /// Whether the shell already holds an unexpired session
+    fn is_unlocked(&mut self) -> bool {
+        session::expire_session(self.session);
+        self.session.is_some()
+    }
+
+    fn open(
+        &mut self,
+        config: &Config,
+        unlock: fn(&Config) -> passman_cli::Result<(PasswordRepository, LockedKey)>,
+    ) -> Result<(&PasswordRepository, &LockedKey)> {
+        use passman_cli::utils::idle::SystemClock;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        if session::expire_session(self.session) {
+            eprintln!("Session timed out; unlock the vault again");
+        }
+
+        let session = match self.session.take() {
+            Some(session) => session,
+            None => {
+                if !config.database_path.exists() {
+                    return Err(vault_missing(&config.database_path));
+                }
+                let (repo, key) = unlock(config)?;
+                let timeout = Duration::from_secs(config.security.session_timeout * 60);
+                Session::new(repo, key, timeout, Arc::new(SystemClock))
+            }
+        };
+        let session = self.session.insert(session);
+        Ok((session.repo(), session.key()))
+    }
+}
+
+/// The clipboard for a command run against `vault`
+///
+/// Outside the shell the process exits right after copying, so it waits for
+/// the clipboard timeout instead of leaving the clear to a thread.
+fn clipboard_for(config: &Config, vault: &Vault<'_>) -> passman_cli::utils::ClipboardManager {
+    passman_cli::utils::ClipboardManager::from_config(config).with_wait(!vault.shell)
+}
+
+/// `Error::VaultNotInitialized` naming the vault file that doesn't exist
+fn vault_missing(path: &std::path::Path) -> anyhow::Error {
+    anyhow::Error::new(passman_cli::Error::VaultNotInitialized).context(format!("No vault at {}", path.display()))
+}
+
+/// Whether a command can run inside `passman shell`
+fn available_in_shell(command: &Commands) -> bool {
+    match command {
+        // These replace or serve the vault the shell holds open
+        Commands::Init { .. } | Commands::Shell => false,
+        #[cfg(feature = "rpc")]
+        Commands::Serve => false,
+        #[cfg(feature = "web-ui")]
+        Commands::Web { .. } => false,
+        _ => true,
+    }
+}
+
+/// Read commands from stdin and run them against one unlocked session
+///
+/// The key is dropped as soon as the session times out, even while the
+/// shell is waiting for input.
+fn shell(global: &GlobalArgs) -> Result<()> {
+    use std::io::{BufRead, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use zeroize::Zeroizing;
+
+    let session: Arc<Mutex<Option<Session>>> = Arc::new(Mutex::new(None));
+
+    let watched = Arc::downgrade(&session);
+    std::thread::spawn(move || {
+        while let Some(session) = watched.upgrade() {
+            if session::expire_session(&mut session.lock().unwrap()) {
+                eprintln!("\nSession timed out; vault locked");
+            }
+            drop(session);
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    });
+
+    println!("passman shell: run commands without the `passman` prefix; `lock` forgets the key, `exit` quits");
+    let mut input = std::io::stdin().lock();
+    loop {
+        print!("passman> ");
+        std::io::stdout().flush()?;
+
+        let mut line = Zeroizing::new(String::new());
+        if input.read_line(&mut line)? == 0 {
+            println!();
+            break;
+        }
+        let Some(words) = shlex::split(&line) else {
+            eprintln!("Unbalanced quotes");
+            continue;
+        };
+        match words.first().map(String::as_str) {
+            None => continue,
+            Some("exit" | "quit") => break,
+            Some("lock") => {
+                session.lock().unwrap().take();
+                println!("Locked");
+                continue;
+            }
+            _ => {}
+        }
+
+        let cli = match Cli::try_parse_from(std::iter::once("passman".to_string()).chain(words)) {
+            Ok(cli) => cli,
+            Err(e) => {
+                e.print()?;
+                continue;
+            }
+        };
+        if !available_in_shell(&cli.command) {
+            eprintln!("That command isn't available in the shell");
+            continue;
+        }
+        if cli.global != *global {
+            eprintln!("The shell is bound to one profile and vault; start another shell to switch");
+            continue;
+        }
+        // These re-key the vault file, so the open session would be stale
+        let rekeys = matches!(cli.command, Commands::Passwd | Commands::RehashMaster | Commands::Migrate { .. });
+
+        let mut guard = session.lock().unwrap();
+        let result = run(cli.command, global, &mut Vault { session: &mut guard, shell: true });
+        if rekeys {
+            guard.take();
+        }
+        drop(guard);
+
+        if let Err(e) = result {
+            eprintln!("Error: {:#}", e);
+        }
+    }
+
+    session.lock().unwrap().take();
+    Ok(())
+}
+
+/// Look up every password in the HIBP range API, warning about requests that fail
+#[cfg(feature = "network")]
+fn audit_pwned(global: &GlobalArgs, vault: &mut Vault<'_>) -> Result<()> {
+    let config = global.load_config_without_create()?;
+    let (repo, key) = vault.unlock_readonly(&config)?;
+
+    let (mut breached, mut unchecked) = (0, 0);
+    for (entry, count) in breach_counts(repo, key)? {
+        match count {
+            None => unchecked += 1,
+            Some(0) => println!("{}: not found", entry.title),
+            Some(count) => {
+                breached += 1;
+                println!("{}: seen {} times in breaches", entry.title, count);
+            }
+        }
+    }
+
+    if unchecked > 0 {
+        eprintln!("Warning: {} entr{} could not be checked", unchecked, if unchecked == 1 { "y" } else { "ies" });
+    }
+    if breached > 0 {
+        anyhow::bail!("{} breached password{} found", breached, if breached == 1 { "" } else { "s" });
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "network"))]
+fn audit_pwned(_global: &GlobalArgs, _vault: &mut Vault<'_>) -> Result<()> {
+    anyhow::bail!("This build has no network support; rebuild with `--features network`")
+}
+
+/// How often each non-empty password appears in HIBP, or `None` where the lookup failed
+#[cfg(feature = "network")]
+fn breach_counts(
+    repo: &passman_cli::database::PasswordRepository,
+    key: &[u8],
+) -> Result<Vec<(passman_cli::database::PasswordEntry, Option<u64>)>> {
+    use passman_cli::utils::pwned;
+    use std::collections::HashMap;
+
+    // Entries sharing a hash prefix need only one request
+    let mut ranges: HashMap<String, Option<String>> = HashMap::new();
+    let mut counts = Vec::new();
+    for (entry, password) in repo.iter_decrypted(key)? {
+        let password = match password {
+            Ok(password) if password.is_empty() => continue,
+            Ok(password) => password,
+            Err(e) => {
+                eprintln!("Skipping '{}': {}", entry.title, e);
+                continue;
+            }
+        };
+        let (prefix, suffix) = pwned::hash_prefix_suffix(password.as_str());
+        drop(password);
+
+        let range = ranges.entry(prefix).or_insert_with_key(|prefix| match pwned::fetch_range(prefix) {
+            Ok(range) => Some(range),
+            Err(e) => {
+                eprintln!("Warning: couldn't check '{}': {}", entry.title, e);
+                None
+            }
+        });
+        let count = range.as_deref().map(|range| pwned::breach_count(range, &suffix));
+        counts.push((entry, count));
+    }
+    Ok(counts)
+}
+
+#[cfg(not(feature = "network"))]
+fn breach_counts(
+    _repo: &passman_cli::database::PasswordRepository,
+    _key: &[u8],
+) -> Result<Vec<(passman_cli::database::PasswordEntry, Option<u64>)>> {
+    anyhow::bail!("This build has no network support; rebuild with `--features network`")
+}
+
+/// Report on, or store, entries parsed from a CSV or Bitwarden export
+///
+/// `left_out` names and counts the items the parser couldn't turn into entries.
+fn import_parsed(
+    repo: &PasswordRepository,
+    key: &[u8],
+    entries: &[passman_cli::export::ExportedEntry],
+    left_out: (&str, usize),
+    report_only: bool,
+    overwrite: bool,
+) -> Result<()> {
+    use passman_cli::export;
+
+    let (label, count) = left_out;
+    if report_only {
+        let report = export::import_report(repo, key, entries)?;
+        println!("New entries:          {}", report.new.len());
+        println!("Existing titles:      {}", report.conflicts.len() + report.duplicates.len());
+        println!("{:<22}{}", format!("{}:", label), count);
+        println!("Nothing was imported");
+        return Ok(());
+    }
+
+    let summary = export::store_imported(repo, key, entries, overwrite)?;
+    println!("Imported:        {}", summary.imported);
+    println!("Overwritten:     {}", summary.overwritten);
+    println!("Skipped:         {} (title already exists)", summary.skipped);
+    println!("{:<17}{}", format!("{}:", label), count);
+    Ok(())
+}
+
+fn run(command: Commands, global: &GlobalArgs, vault: &mut Vault<'_>) -> Result<()> {
+    match command {
+        Commands::Init { force, cipher, kdf_memory, kdf_iterations, kdf_parallelism } => {
+            use passman_cli::crypto::{read_password_with_confirmation, Argon2Params};
+            use passman_cli::database::PasswordRepository;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config()?;
+            let defaults = config.security.kdf_params();
+            let kdf = Argon2Params {
+                m_cost: kdf_memory.map_or(Ok(defaults.m_cost), |mib| {
+                    mib.checked_mul(1024)
+                        .ok_or_else(|| passman_cli::Error::InvalidInput("--kdf-memory is too large".to_string()))
+                })?,
+                t_cost: kdf_iterations.unwrap_or(defaults.t_cost),
+                p_cost: kdf_parallelism.unwrap_or(defaults.p_cost),
+            };
+            kdf.validate()?;
+            let options = session::VaultOptions { cipher, kdf };
+
+            config.prepare_database_dir()?;
+            // Fail before prompting if the vault can't be initialized anyway
+            PasswordRepository::inspect(&config.database_path)?.check_initializable(force)?;
+
+            let password = Zeroizing::new(read_password_with_confirmation("New master password: ")?);
+            session::init_vault_with(&config.database_path, &password, force, &options)?;
+
+            println!("Initialized vault at {} ({})", config.database_path.display(), cipher.display_name());
+            Ok(())
+        }
+        Commands::Add { name, url, notes, edit_with_editor, template, generate, tags } => {
+            use passman_cli::cli::{editor, prompt};
+            use passman_cli::database::templates;
+
+            let config = global.load_config_without_create()?;
+            let notes = if edit_with_editor {
+                let command = editor::resolve_editor(config.editor.as_deref());
+                Some(editor::edit_text("", &command)?)
+            } else {
+                notes
+            };
+
+            if let Some(template) = template {
+                let template = templates::find_template(&template, &config.entry_templates)?;
+                let (repo, key) = vault.unlock(&config)?;
+
+                let (mut entry, fields) = template.build(&name, prompt::template_field)?;
+                entry.url = url.or(entry.url);
+                entry.notes = notes.or(entry.notes);
+                entry.tags = tags;
+
+                let encrypted = repo.encryption_manager()?.encrypt(key, entry.password.as_str().as_bytes())?;
+                repo.add_entry(&entry, &encrypted)?;
+                repo.add_custom_fields(&entry.id, &fields, key)?;
+
+                println!("Added '{}' from template '{}'", entry.title, template.name);
+                return Ok(());
+            }
+
+            use passman_cli::crypto::read_password;
+            use passman_cli::database::{PasswordEntry, SecureString};
+            use passman_cli::utils::PasswordGenerator;
+
+            let (repo, key) = vault.unlock(&config)?;
+            if repo.title_exists(&name)? {
+                anyhow::bail!("Entry '{}' already exists", name);
+            }
+
+            let username = prompt::read_line("Username: ")?;
+            let password = if generate {
+                let defaults = repo.generator_defaults_or(&config.password_generation)?;
+                SecureString::new(PasswordGenerator::with_config(defaults.generator_config()).generate()?)
+            } else {
+                SecureString::new(read_password("Password: ")?)
+            };
+
+            let mut entry = PasswordEntry::new(name, username, password, url, notes);
+            entry.tags = tags;
+            let encrypted = repo.encryption_manager()?.encrypt(key, entry.password.as_str().as_bytes())?;
+            repo.add_entry(&entry, &encrypted)?;
+
+            if generate {
+                println!("Added '{}' with a generated {}-character password", entry.title, entry.password.as_str().len());
+            } else {
+                println!("Added '{}'", entry.title);
+            }
+            Ok(())
+        }
+        Commands::Get { name, id, field, show } => {
+            use passman_cli::cli::view;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let entry = match id {
+                Some(id) => repo.get_entry_decrypted_by_id(&id, key)?,
+                None => repo.get_entry_decrypted(&name.unwrap_or_default(), key)?,
+            };
+
+            match field {
+                Some(field) => {
+                    let value = field
+                        .value(&entry)
+                        .ok_or_else(|| anyhow::anyhow!("'{}' has no {}", entry.title, field.name()))?;
+                    println!("{}", value);
+                }
+                None => println!("{}", view::format_entry(&entry, show)),
+            }
+            Ok(())
+        }
+        Commands::List { count_only: true, .. } => {
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+
+            println!("{}", repo.count_entries()?);
+            Ok(())
+        }
+        Commands::List { stats: true, .. } => {
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+
+            let stats = repo.entry_stats()?;
+            println!("Entries:            {}", stats.total);
+            println!("With URL:           {}", stats.with_url);
+            println!("With notes:         {}", stats.with_notes);
+            println!("With TOTP:          {}", stats.with_totp);
+            println!("With custom fields: {}", stats.with_custom_fields);
+            for (tag, count) in &stats.per_tag {
+                println!("Tag {:<15} {}", format!("{}:", tag), count);
+            }
+            Ok(())
+        }
+        Commands::List { verify: true, .. } => {
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock_readonly(&config)?;
+
+            let mut corrupt = 0;
+            for (entry, password) in repo.iter_decrypted(key)? {
+                match password {
+                    Ok(_) => println!("{:<8} {} OK", entry.short_id(config.short_id_length), entry.title),
+                    Err(e) => {
+                        corrupt += 1;
+                        println!("{:<8} {} CORRUPT ({})", entry.short_id(config.short_id_length), entry.title, e);
+                    }
+                }
+            }
+
+            if corrupt > 0 {
+                anyhow::bail!("{} corrupt entr{} found", corrupt, if corrupt == 1 { "y" } else { "ies" });
+            }
             Ok(())
         }
-        Commands::Add { name, url, notes } => {
-            println!("Adding new entry: {}", name);
-            // TODO: Implement add functionality
+        Commands::List { format, tags, .. } => {
+            use passman_cli::cli::view;
+            use passman_cli::database::PasswordEntry;
+
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+
+            let entries = match tags.split_first() {
+                None => repo.list_entries()?,
+                Some((first, rest)) => {
+                    let rest = rest.iter().map(|tag| PasswordEntry::normalize_tag(tag)).collect::<Result<Vec<_>, _>>()?;
+                    let mut entries = repo.list_entries_by_tag(first)?;
+                    entries.retain(|entry| rest.iter().all(|tag| entry.tags.contains(tag)));
+                    entries
+                }
+            };
+
+            let output = view::format_list(&entries, format, config.short_id_length)?;
+            print!("{}", output);
+            if !output.ends_with('\n') {
+                println!();
+            }
             Ok(())
         }
-        Commands::Get { name } => {
-            println!("Getting entry: {}", name);
-            // TODO: Implement get functionality
+        Commands::Edit { name, editor: true, .. } => {
+            use passman_cli::cli::editor;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let (entry, _) = repo.get_entry(&name)?;
+            let edit = editor::edit_entry(&entry, &editor::resolve_editor(config.editor.as_deref()))?;
+            if edit.is_empty() {
+                println!("Nothing to change for '{}'", entry.title);
+                return Ok(());
+            }
+
+            let id = entry.id.to_string();
+            let updated = repo.in_transaction(|| {
+                let mut updated = entry.clone();
+                if !edit.spec.is_empty() {
+                    updated = repo.update_entry_fields(&id, &edit.spec)?;
+                }
+                if let Some(password) = &edit.password {
+                    updated = repo.change_password(&id, password, key)?;
+                }
+                repo.remove_tags(&entry.id, &edit.remove_tags)?;
+                repo.add_tags(&entry.id, &edit.add_tags)?;
+                Ok(updated)
+            })?;
+            println!("Updated '{}'", updated.title);
             Ok(())
         }
-        Commands::List => {
-            println!("Listing all entries...");
-            // TODO: Implement list functionality
+        Commands::Edit { name, title, username, url, notes, clear_url, clear_notes, edit_with_editor, password, tags, remove_tags, .. } => {
+            use passman_cli::cli::{editor, prompt};
+            use passman_cli::crypto::read_password;
+            use passman_cli::database::{FieldUpdate, UpdateSpec};
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let optional = |value: Option<String>, clear: bool| match (value, clear) {
+                (_, true) => FieldUpdate::Clear,
+                (Some(value), false) => FieldUpdate::Set(value),
+                (None, false) => FieldUpdate::Keep,
+            };
+            let mut spec = UpdateSpec {
+                title,
+                username,
+                url: optional(url, clear_url),
+                notes: optional(notes, clear_notes),
+            };
+
+            if edit_with_editor {
+                let (entry, _) = repo.get_entry(&name)?;
+                let command = editor::resolve_editor(config.editor.as_deref());
+                spec.notes = FieldUpdate::Set(editor::edit_text(entry.notes.as_deref().unwrap_or(""), &command)?);
+            }
+
+            let retag = !tags.is_empty() || !remove_tags.is_empty();
+            let new_password = if spec.is_empty() && !password && !retag {
+                // Nothing given on the command line, so ask for each field
+                let (entry, _) = repo.get_entry(&name)?;
+                println!("Editing '{}' (leave blank to keep the current value)", entry.title);
+                spec = prompt::edit_spec(&entry)?;
+                prompt::read_optional_secret("New password (leave blank to keep): ")?.map(Zeroizing::new)
+            } else if password {
+                Some(Zeroizing::new(read_password("New password: ")?))
+            } else {
+                None
+            };
+
+            if spec.is_empty() && new_password.is_none() && !retag {
+                println!("Nothing to change for '{}'", name);
+                return Ok(());
+            }
+
+            let (entry, _) = repo.get_entry(&name)?;
+            let id = entry.id.to_string();
+            let mut updated = entry;
+            if !spec.is_empty() {
+                updated = repo.update_entry_fields(&id, &spec)?;
+            }
+            if let Some(new_password) = new_password {
+                updated = repo.change_password(&id, &new_password, key)?;
+            }
+            if retag {
+                repo.in_transaction(|| {
+                    repo.remove_tags(&updated.id, &remove_tags)?;
+                    repo.add_tags(&updated.id, &tags)
+                })?;
+            }
+            println!("Updated '{}'", updated.title);
             Ok(())
         }
-        Commands::Edit { name } => {
-            println!("Editing entry: {}", name);
-            // TODO: Implement edit functionality
+        Commands::Delete { name, force, soft } => {
+            use passman_cli::cli::prompt;
+
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+
+            // Without --soft, a title only found in the trash empties it from there
+            let (entry, trashed) = match repo.get_entry(&name) {
+                Ok((entry, _)) => (entry, false),
+                Err(passman_cli::Error::EntryNotFound(_)) if !soft => match repo.get_trashed_entry(&name) {
+                    Ok(entry) => (entry, true),
+                    Err(_) => return Err(passman_cli::Error::EntryNotFound(name).into()),
+                },
+                Err(e) => return Err(e.into()),
+            };
+            let question = match trashed {
+                true => format!("Permanently delete '{}' from the trash?", entry.title),
+                false => format!("Delete '{}'?", entry.title),
+            };
+            if !force && !prompt::confirm(&question)? {
+                println!("Cancelled");
+                return Ok(());
+            }
+
+            if soft {
+                repo.soft_delete_entry(&entry.id.to_string())?;
+                println!("Moved '{}' to the trash; bring it back with `passman restore`", entry.title);
+            } else {
+                repo.delete_entry(&entry.id)?;
+                println!("Deleted '{}'", entry.title);
+            }
             Ok(())
         }
-        Commands::Delete { name, force } => {
-            println!("Deleting entry: {}", name);
-            // TODO: Implement delete functionality
+        Commands::Restore { name } => {
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+
+            repo.restore_entry(&name)?;
+            println!("Restored '{}'", name);
             Ok(())
         }
-        Commands::Generate { length, no_symbols, no_numbers } => {
-            use passman_cli::utils::{PasswordGenerator, GeneratorConfig};
-            
-            let mut config = GeneratorConfig::default();
-            config.length = length;
-            config.include_symbols = !no_symbols;
-            config.include_numbers = !no_numbers;
-            
+        Commands::Generate {
+            check: true,
+            min_length,
+            min_lowercase,
+            min_uppercase,
+            min_digits,
+            min_symbols,
+            min_entropy,
+            ..
+        } => {
+            use passman_cli::crypto::read_password;
+            use passman_cli::utils::policy::{self, PasswordPolicy};
+            use std::io::IsTerminal;
+            use zeroize::Zeroizing;
+
+            let password = if std::io::stdin().is_terminal() {
+                Zeroizing::new(read_password("Password to check: ")?)
+            } else {
+                let mut line = Zeroizing::new(String::new());
+                std::io::stdin().read_line(&mut line)?;
+                Zeroizing::new(line.trim_end_matches(['\r', '\n']).to_string())
+            };
+
+            let policy = PasswordPolicy {
+                min_length: min_length.unwrap_or(PasswordPolicy::default().min_length),
+                min_lowercase,
+                min_uppercase,
+                min_digits,
+                min_symbols,
+                min_entropy,
+            };
+            let result = policy::check(&password, &policy);
+            if !result.passed() {
+                for failure in &result.failures {
+                    println!("FAIL: {}", failure);
+                }
+                anyhow::bail!("Password does not satisfy the policy");
+            }
+
+            println!("Password satisfies the policy");
+            Ok(())
+        }
+        Commands::Generate {
+            length,
+            no_symbols,
+            no_numbers,
+            safe_for,
+            exclude,
+            no_ambiguous,
+            symbols,
+            charset,
+            mask,
+            preview_charset,
+            save_default,
+            vault_defaults,
+            output,
+            words,
+            separator,
+            capitalize,
+            append_digit,
+            min_lowercase,
+            min_uppercase,
+            min_digits,
+            min_symbols,
+            ..
+        } => {
+            use passman_cli::utils::output::write_private_file;
+            use passman_cli::utils::{
+                validate_symbol_set, PassphraseConfig, PassphraseGenerator, PasswordGenerator, Strength,
+            };
+            use zeroize::Zeroizing;
+
+            if let Some(words) = words {
+                let config = PassphraseConfig {
+                    words,
+                    separator,
+                    capitalize,
+                    append_digit,
+                };
+                let passphrase = Zeroizing::new(PassphraseGenerator::with_config(config).generate()?);
+
+                if let Some(output) = output {
+                    eprintln!("Warning: the passphrase is stored unencrypted in {}", output.display());
+                    write_private_file(&output, format!("{}\n", *passphrase).as_bytes())?;
+                    println!("Wrote a {}-word passphrase to {}", words, output.display());
+                    return Ok(());
+                }
+
+                println!("Generated passphrase: {}", *passphrase);
+                return Ok(());
+            }
+
+            let app_config = global.load_config_without_create()?;
+            // Only prompt for the master password when asked to use the vault
+            let mut defaults = if save_default || vault_defaults || vault.is_unlocked() {
+                let (repo, _key) = match save_default {
+                    true => vault.unlock(&app_config)?,
+                    false => vault.unlock_readonly(&app_config)?,
+                };
+                repo.generator_defaults_or(&app_config.password_generation)?
+            } else {
+                app_config.password_generation.clone()
+            };
+            if let Some(length) = length {
+                defaults.default_length = length;
+            }
+            defaults.include_symbols &= !no_symbols;
+            defaults.include_numbers &= !no_numbers;
+            if let Some(symbols) = symbols {
+                validate_symbol_set(&symbols)?;
+                defaults.symbol_set = symbols;
+            }
+
+            if save_default {
+                let (repo, _key) = vault.unlock(&app_config)?;
+                repo.set_generator_defaults(&defaults)?;
+                println!("Saved generator defaults for this vault");
+            }
+
+            let mut config = defaults.generator_config();
+            config.exclude_chars = exclude.unwrap_or_default();
+            config.exclude_ambiguous = no_ambiguous;
+            config.min_lowercase = min_lowercase as u32;
+            config.min_uppercase = min_uppercase as u32;
+            config.min_numbers = min_digits as u32;
+            config.min_symbols = min_symbols as u32;
+            config.custom_charset = charset;
+            config.mask = mask;
+            if let Some(context) = safe_for {
+                config.exclude_for(context);
+            }
+
             let generator = PasswordGenerator::with_config(config);
-            let password = generator.generate()?;
-            
-            println!("Generated password: {}", password);
+            if preview_charset {
+                let charset = generator.effective_charset()?;
+                println!("Charset ({} characters): {}", charset.chars().count(), charset);
+                return Ok(());
+            }
+
+            let password = Zeroizing::new(generator.generate()?);
+
+            if let Some(output) = output {
+                eprintln!("Warning: the password is stored unencrypted in {}", output.display());
+                write_private_file(&output, format!("{}\n", *password).as_bytes())?;
+                println!("Wrote a {}-character password to {}", password.len(), output.display());
+                return Ok(());
+            }
+
+            let bits = generator.estimated_entropy(&password)?;
+
+            println!("Generated password: {}", *password);
             println!("Password length: {}", password.len());
+            println!("Estimated strength: {:.1} bits ({})", bits, Strength::from_bits(bits).label());
+            Ok(())
+        }
+        Commands::Clone { name, new_name } => {
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let entry = repo.clone_entry(&name, &new_name, key)?;
+            println!("Cloned '{}' to '{}' ({})", name, entry.title, entry.id);
             Ok(())
         }
-        Commands::Copy { name } => {
-            use passman_cli::utils::copy_password;
-            
-            // For demo, generate a test password
-            let test_password = "demo-password-123";
-            println!("Copying password for '{}' to clipboard...", name);
-            copy_password(test_password)?;
+        Commands::Copy { name, field } => {
+            let config = global.load_config_without_create()?;
+            let clipboard = clipboard_for(&config, vault);
+            let (repo, key) = vault.unlock(&config)?;
+
+            let entry = repo.get_entry_decrypted(&name, key)?;
+            let value = field
+                .value(&entry)
+                .ok_or_else(|| anyhow::anyhow!("'{}' has no {}", entry.title, field.name()))?;
+
+            println!("Copying {} for '{}' to clipboard...", field.name(), entry.title);
+            if field.is_sensitive() {
+                clipboard.copy_with_timeout(value)?;
+            } else {
+                clipboard.copy_held(value)?;
+            }
             Ok(())
         }
-        Commands::Search { query } => {
-            println!("Searching for: {}", query);
-            // TODO: Implement search functionality
+        Commands::Open { name, copy } => {
+            use passman_cli::utils::browser;
+
+            let config = global.load_config_without_create()?;
+            let clipboard = clipboard_for(&config, vault);
+            let (repo, key) = vault.unlock(&config)?;
+
+            let (entry, _) = repo.get_entry(&name)?;
+            let url = entry
+                .url
+                .as_deref()
+                .ok_or_else(|| passman_cli::Error::InvalidInput(format!("'{}' has no URL", entry.title)))?;
+            browser::open_url(url)?;
+            println!("Opened {}", url);
+
+            if copy {
+                let entry = repo.get_entry_decrypted_by_id(&entry.id, key)?;
+                clipboard.copy_with_timeout(entry.password.as_str())?;
+            }
             Ok(())
         }
-        #[cfg(feature = "web-ui")]
-        Commands::Web { port } => {
-            use passman_cli::web::WebServer;
-            
-            let server = WebServer::new(port);
-            server.serve().await?;
+        #[cfg(feature = "tui")]
+        Commands::Tui => {
+            let config = global.load_config_without_create()?;
+            let clipboard = clipboard_for(&config, vault);
+            let (repo, key) = vault.unlock(&config)?;
+
+            let entries = repo.list_entries()?;
+            let reveal = |entry: &passman_cli::database::PasswordEntry| {
+                repo.get_entry_decrypted_by_id(&entry.id, key).map(|entry| entry.password.clone())
+            };
+            let Some(entry) = passman_cli::tui::pick(entries, reveal)? else {
+                return Ok(());
+            };
+
+            let entry = repo.get_entry_decrypted_by_id(&entry.id, key)?;
+            println!("Copying password for '{}' to clipboard...", entry.title);
+            clipboard.copy_with_timeout(entry.password.as_str())?;
+            Ok(())
+        }
+        Commands::History { name, reveal } => {
+            use passman_cli::utils::time::format_timestamp;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let (entry, _) = repo.get_entry(&name)?;
+            let history = repo.password_history(&entry.id)?;
+
+            if let Some(n) = reveal {
+                let previous = history
+                    .get(n as usize - 1)
+                    .ok_or_else(|| anyhow::anyhow!("'{}' has {} previous password(s)", entry.title, history.len()))?;
+                println!("{}", repo.decrypt_history_password(previous, key)?.as_str());
+                return Ok(());
+            }
+
+            if history.is_empty() {
+                println!("No previous passwords for '{}'", entry.title);
+                return Ok(());
+            }
+            for (i, previous) in history.iter().enumerate() {
+                println!("{:>3}  replaced {}", i + 1, format_timestamp(&previous.changed_at, config.display_local_time));
+            }
+            Ok(())
+        }
+        Commands::CopyLogin { name } => {
+            use passman_cli::utils::copy_login;
+
+            let config = global.load_config_without_create()?;
+            let clipboard = clipboard_for(&config, vault);
+            let (repo, key) = vault.unlock(&config)?;
+
+            let entry = repo.get_entry_decrypted(&name, key)?;
+            copy_login(&clipboard, &config.login_template, &entry)?;
+            Ok(())
+        }
+        Commands::Share { name } => {
+            use passman_cli::cli::{prompt, share};
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let entry = repo.get_entry_decrypted(&name, key)?;
+            let reveal = prompt::confirm(&format!("Reveal password for '{}'?", entry.title))?;
+
+            let totp_secret = repo.get_totp_secret(&entry.id, key)?;
+            println!(
+                "{}",
+                share::format_share(&entry, reveal, totp_secret.as_ref().map(|s| s.as_str()))?
+            );
+            Ok(())
+        }
+        Commands::Duress { action } => {
+            use passman_cli::crypto::read_password_with_confirmation;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+            let decoy_path = config.decoy_path();
+
+            match action {
+                DuressAction::Set => {
+                    let password = Zeroizing::new(read_password_with_confirmation("Duress password: ")?);
+                    session::set_duress_password(repo, &password, &decoy_path)?;
+                    println!("Duress password set");
+                }
+                DuressAction::Remove => {
+                    repo.set_duress_hash(None)?;
+                    if decoy_path.exists() {
+                        std::fs::remove_file(&decoy_path)?;
+                    }
+                    println!("Duress password removed");
+                }
+            }
+            Ok(())
+        }
+        Commands::Totp { action } => {
+            use passman_cli::crypto::read_password;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            match action {
+                TotpAction::Set { name, secret } => {
+                    let (entry, _) = repo.get_entry(&name)?;
+                    let secret = match secret {
+                        Some(secret) => Zeroizing::new(secret),
+                        None => Zeroizing::new(read_password("TOTP secret (base32): ")?),
+                    };
+                    repo.set_totp_secret(&entry.id, Some(&secret), key)?;
+                    println!("TOTP secret set for '{}'", entry.title);
+                }
+                TotpAction::Remove { name } => {
+                    let (entry, _) = repo.get_entry(&name)?;
+                    repo.set_totp_secret(&entry.id, None, key)?;
+                    println!("TOTP secret removed from '{}'", entry.title);
+                }
+            }
+            Ok(())
+        }
+        Commands::Otp { name } => {
+            use passman_cli::crypto::totp;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let (entry, _) = repo.get_entry(&name)?;
+            let secret = repo
+                .get_totp_secret(&entry.id, key)?
+                .ok_or_else(|| anyhow::anyhow!("'{}' has no TOTP secret; add one with `passman totp set`", entry.title))?;
+            let secret = Zeroizing::new(totp::decode_base32(secret.as_str())?);
+
+            let now = chrono::Utc::now().timestamp() as u64;
+            println!("{} ({}s remaining)", totp::totp_code(&secret, now), totp::seconds_remaining(now));
+            Ok(())
+        }
+        Commands::Search { query, fields, explain, deep, regex, ranked, format } => {
+            use passman_cli::cli::view::{self, ListFormat};
+            use passman_cli::database::{DeepMatch, SearchField, SearchPattern};
+            use std::io::IsTerminal;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let fields = if fields.is_empty() { SearchField::ALL.to_vec() } else { fields };
+            let pattern = if regex { SearchPattern::regex(&query)? } else { SearchPattern::Literal(query.clone()) };
+            let matches = if deep {
+                repo.deep_search(&query, &fields, key)?
+            } else if ranked {
+                repo.search_fts(&query)?
+                    .into_iter()
+                    .map(|entry| DeepMatch { entry, encrypted_fields: Vec::new() })
+                    .collect()
+            } else {
+                repo.search_entries_matching(&pattern, &fields)?
+                    .into_iter()
+                    .map(|entry| DeepMatch { entry, encrypted_fields: Vec::new() })
+                    .collect()
+            };
+
+            let entries: Vec<_> = matches.iter().map(|m| m.entry.clone()).collect();
+            let highlight = (format == ListFormat::Table && std::io::stdout().is_terminal()).then_some(&pattern);
+            let output = view::format_list_highlighted(&entries, format, config.short_id_length, highlight)?;
+            print!("{}", output);
+            if !output.ends_with('\n') {
+                println!();
+            }
+
+            if format == ListFormat::Table {
+                for DeepMatch { entry, encrypted_fields } in &matches {
+                    if explain {
+                        let matched: Vec<String> = entry
+                            .fields_matching(&pattern, &fields)
+                            .into_iter()
+                            .map(|field| field.column().to_string())
+                            .chain(encrypted_fields.iter().cloned())
+                            .collect();
+                        println!("{}: matched {}", entry.title, matched.join(", "));
+                    } else if !encrypted_fields.is_empty() {
+                        println!("{}: [encrypted match] {}", entry.title, encrypted_fields.join(", "));
+                    }
+                }
+            }
+            Ok(())
+        }
+        Commands::Stats => {
+            use passman_cli::utils::time::format_timestamp;
+
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock_readonly(&config)?;
+
+            let stats = repo.entry_stats()?;
+            let timestamp = |timestamp: Option<chrono::DateTime<chrono::Utc>>| {
+                timestamp.map_or_else(|| "-".to_string(), |t| format_timestamp(&t, config.display_local_time))
+            };
+            println!("Entries:              {}", stats.total);
+            println!("With URL:             {}", stats.with_url);
+            println!("With notes:           {}", stats.with_notes);
+            println!("Missing password:     {}", stats.missing_password);
+            println!("Oldest update:        {}", timestamp(stats.oldest_update));
+            println!("Newest update:        {}", timestamp(stats.newest_update));
+            match stats.mean_password_changed_at {
+                Some(mean) => println!("Average password age: {} days", (chrono::Utc::now() - mean).num_days()),
+                None => println!("Average password age: -"),
+            }
+            Ok(())
+        }
+        Commands::Audit { action: AuditAction::Reuse } => {
+            use passman_cli::utils::audit;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock_readonly(&config)?;
+
+            let passwords = repo.iter_decrypted(key)?.filter_map(|(entry, password)| match password {
+                Ok(password) => Some((entry, password)),
+                Err(e) => {
+                    eprintln!("Skipping '{}': {}", entry.title, e);
+                    None
+                }
+            });
+            let groups = audit::find_reused(passwords);
+
+            if groups.is_empty() {
+                println!("No reused passwords found");
+                return Ok(());
+            }
+            for group in &groups {
+                let titles: Vec<&str> = group.iter().map(|entry| entry.title.as_str()).collect();
+                println!("Shared by {} entries: {}", group.len(), titles.join(", "));
+            }
+            let reused: usize = groups.iter().map(Vec::len).sum();
+            anyhow::bail!("{} entries reuse a password ({} group{})", reused, groups.len(), if groups.len() == 1 { "" } else { "s" });
+        }
+        Commands::Audit { action: AuditAction::Weak { min_bits } } => {
+            use passman_cli::utils::{audit, Strength};
+
+            let config = global.load_config_without_create()?;
+            let min_bits = min_bits.unwrap_or(config.security.weak_password_min_bits);
+            let (repo, key) = vault.unlock_readonly(&config)?;
+
+            let passwords = repo.iter_decrypted(key)?.filter_map(|(entry, password)| match password {
+                Ok(password) => Some((entry, password)),
+                Err(e) => {
+                    eprintln!("Skipping '{}': {}", entry.title, e);
+                    None
+                }
+            });
+            let weak = audit::find_weak(passwords, min_bits);
+
+            if weak.is_empty() {
+                println!("No passwords below {:.0} bits", min_bits);
+                return Ok(());
+            }
+            for (entry, bits) in &weak {
+                println!("{}: {:.1} bits ({})", entry.title, bits, Strength::from_bits(*bits).label());
+            }
+            anyhow::bail!("{} password{} below {:.0} bits", weak.len(), if weak.len() == 1 { "" } else { "s" }, min_bits);
+        }
+        Commands::Audit { action: AuditAction::Pwned { online } } => {
+            if !online {
+                anyhow::bail!("This check sends hash prefixes to api.pwnedpasswords.com; pass --online to allow it");
+            }
+            audit_pwned(global, vault)
+        }
+        Commands::Rotate { weak, breached, online, min_bits } => {
+            use passman_cli::utils::{audit, PasswordGenerator};
+            use std::collections::HashSet;
+
+            if !weak && !breached {
+                anyhow::bail!("Nothing to rotate; pass --weak or --breached to select entries");
+            }
+            if breached && !online {
+                anyhow::bail!("--breached sends hash prefixes to api.pwnedpasswords.com; pass --online to allow it");
+            }
+
+            let config = global.load_config_without_create()?;
+            let min_bits = min_bits.unwrap_or(config.security.weak_password_min_bits);
+            let (repo, key) = vault.unlock(&config)?;
+            // Looked up before rotating, so no request is made inside the transaction
+            let breached_ids: HashSet<_> = match breached {
+                true => breach_counts(repo, key)?
+                    .into_iter()
+                    .filter(|(_, count)| count.is_some_and(|count| count > 0))
+                    .map(|(entry, _)| entry.id)
+                    .collect(),
+                false => HashSet::new(),
+            };
+            let defaults = repo.generator_defaults_or(&config.password_generation)?;
+            let generator = PasswordGenerator::with_config(defaults.generator_config());
+
+            let report = repo.rotate_passwords(key, &generator, |entry, password| {
+                (weak && audit::is_weak(password, min_bits)) || breached_ids.contains(&entry.id)
+            })?;
+
+            if report.rotated.is_empty() {
+                println!("No passwords needed rotating");
+            }
+            for entry in &report.rotated {
+                println!("Rotated password for '{}'", entry.title);
+            }
+            for (entry, e) in &report.failed {
+                eprintln!("Skipping '{}': {}", entry.title, e);
+            }
+            Ok(())
+        }
+        Commands::Export { file, format, compress, entry, plaintext } => {
+            use colored::Colorize;
+            use passman_cli::cli::prompt;
+            use passman_cli::crypto::read_password_with_confirmation;
+            use passman_cli::export::{self, ExportFormat};
+            use passman_cli::utils::guard_bulk_reveal;
+            use passman_cli::utils::output::write_private_file;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock_readonly(&config)?;
+
+            let entries = match entry {
+                Some(name) => vec![export::collect_entry(repo, key, &name)?],
+                None => export::collect_entries(repo, key)?,
+            };
+
+            match format {
+                ExportFormat::Json if plaintext => {
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "WARNING: {} will contain every password in plain text. Anyone who can read the file can read your vault.",
+                            file.display()
+                        )
+                        .red()
+                        .bold()
+                    );
+                    guard_bulk_reveal(entries.len(), config.security.max_secrets_per_operation, |count| {
+                        prompt::confirm(&format!("This reveals {} secrets at once. Are you sure?", count))
+                    })?;
+                    let question = format!("Write {} unencrypted passwords to {}?", entries.len(), file.display());
+                    if !prompt::confirm(&question)? {
+                        anyhow::bail!("Export cancelled");
+                    }
+                    write_private_file(&file, &export::export_plaintext(&entries)?)?;
+                }
+                ExportFormat::Json => {
+                    let passphrase = Zeroizing::new(read_password_with_confirmation("Export passphrase: ")?);
+                    write_private_file(&file, &export::export_entries(&entries, &passphrase, compress)?)?;
+                }
+            }
+
+            println!("Exported {} entries to {}", entries.len(), file.display());
             Ok(())
         }
+        Commands::Backup { out, force } => {
+            let config = global.load_config_without_create()?;
+            let (repo, _key) = vault.unlock(&config)?;
+
+            let out = out.unwrap_or_else(|| config.backup_path(chrono::Utc::now()));
+            if out.exists() && !force {
+                anyhow::bail!("{} already exists; pass --force to replace it", out.display());
+            }
+
+            // The file is still encrypted under the master password, so the copy is too
+            repo.checkpoint()?;
+            std::fs::copy(&config.database_path, &out)?;
+            println!("Backed up vault to {}", out.display());
+            Ok(())
+        }
+        Commands::Import { file, format, report_only, overwrite } => {
+            use passman_cli::crypto::read_password;
+            use passman_cli::export::{self, ImportFormat};
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            if overwrite && format == ImportFormat::Backup {
+                anyhow::bail!("--overwrite is only supported with --format csv or bitwarden");
+            }
+            let data = Zeroizing::new(std::fs::read(&file)?);
+            let (repo, key) = vault.unlock(&config)?;
+
+            if format == ImportFormat::Bitwarden {
+                let import = export::bitwarden::parse_bitwarden(&data)?;
+                if import.skipped_non_login > 0 {
+                    eprintln!("Warning: skipped {} items without a login (notes, cards, identities)", import.skipped_non_login);
+                }
+                let left_out = ("Non-login items", import.skipped_non_login);
+                return import_parsed(repo, key, &import.entries, left_out, report_only, overwrite);
+            }
+
+            if format == ImportFormat::Csv {
+                let text = std::str::from_utf8(&data)
+                    .map_err(|_| anyhow::anyhow!("{} is not valid UTF-8", file.display()))?;
+                let import = export::csv::parse_csv(text);
+                for error in &import.errors {
+                    eprintln!("Line {}: {}", error.line, error.message);
+                }
+                let left_out = ("Malformed rows", import.errors.len());
+                return import_parsed(repo, key, &import.entries, left_out, report_only, overwrite);
+            }
+
+            let passphrase = Zeroizing::new(read_password("Export passphrase: ")?);
+            let entries = export::import_entries(&data, &passphrase)?;
+
+            if report_only {
+                let report = export::import_report(repo, key, &entries)?;
+                println!("New entries:          {}", report.new.len());
+                println!("Title conflicts:      {}", report.conflicts.len());
+                for title in &report.conflicts {
+                    println!("  {}", title);
+                }
+                println!("Identical duplicates: {}", report.duplicates.len());
+                println!("Nothing was imported");
+                return Ok(());
+            }
+
+            let imported = export::store_entries(repo, key, &entries)?;
+
+            println!("Imported {} of {} entries from {}", imported, entries.len(), file.display());
+            Ok(())
+        }
+        Commands::Diff { other_vault } => {
+            use passman_cli::database::diff;
+
+            let config = global.load_config_without_create()?;
+            if !other_vault.exists() {
+                anyhow::bail!("Vault {} does not exist", other_vault.display());
+            }
+
+            let (repo, key) = vault.unlock(&config)?;
+            let (other, other_key) = session::unlock_path(
+                &other_vault,
+                &format!("Master password for {}: ", other_vault.display()),
+                &config.security,
+            )?;
+
+            let diff = diff::diff_vaults(repo, key, &other, &other_key)?;
+            if diff.is_empty() {
+                println!("Vaults are identical");
+                return Ok(());
+            }
+
+            for title in &diff.only_in_left {
+                println!("- {} (only in this vault)", title);
+            }
+            for title in &diff.only_in_right {
+                println!("+ {} (only in {})", title, other_vault.display());
+            }
+            for entry in &diff.changed {
+                println!("~ {} (differs: {})", entry.title, entry.fields.join(", "));
+            }
+            Ok(())
+        }
+        Commands::RehashMaster => {
+            use passman_cli::crypto::read_password;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let lockout = session::Lockout::for_vault(&config.security, &config.database_path);
+            lockout.check()?;
+            let password = Zeroizing::new(read_password("Master password: ")?);
+            let (repo, _key) = session::open_with_lockout(session::open_initialized, &config.database_path, &password, &lockout)?;
+
+            session::rehash_master(&repo, &password)?;
+            println!("Rehashed the master password and re-encrypted {} entries", repo.count_entries()?);
+            Ok(())
+        }
+        Commands::Passwd => {
+            use passman_cli::crypto::{read_password, read_password_with_confirmation};
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            let lockout = session::Lockout::for_vault(&config.security, &config.database_path);
+            lockout.check()?;
+            let current = Zeroizing::new(read_password("Current master password: ")?);
+            let (repo, _key) = session::open_with_lockout(session::open_initialized, &config.database_path, &current, &lockout)?;
+
+            let new = Zeroizing::new(read_password_with_confirmation("New master password: ")?);
+            session::change_master_password(&repo, &current, &new)?;
+            println!("Changed the master password and re-encrypted {} entries", repo.count_entries()?);
+            Ok(())
+        }
+        Commands::Reencrypt { to } => {
+            let config = global.load_config_without_create()?;
+            let (repo, key) = vault.unlock(&config)?;
+
+            let from = repo.cipher()?;
+            if from == to {
+                println!("Vault is already encrypted with {}", to.display_name());
+                return Ok(());
+            }
+
+            let rewritten = repo.reencrypt(key, to)?;
+            println!(
+                "Re-encrypted {} secrets from {} to {}",
+                rewritten,
+                from.display_name(),
+                to.display_name()
+            );
+            Ok(())
+        }
+        Commands::Debug { metadata, output } => {
+            use passman_cli::cli::diagnostics::VaultDiagnostics;
+            use passman_cli::utils::output::write_private_file;
+            use passman_cli::database::{DatabaseState, PasswordRepository};
+
+            if !metadata {
+                anyhow::bail!("Nothing to show; pass --metadata");
+            }
+
+            let config = global.load_config_without_create()?;
+            let plaintext;
+            let repo = match PasswordRepository::inspect(&config.database_path)? {
+                DatabaseState::Missing | DatabaseState::Empty => return Err(vault_missing(&config.database_path)),
+                DatabaseState::Foreign => anyhow::bail!("{} is not a passman vault", config.database_path.display()),
+                DatabaseState::Uninitialized | DatabaseState::Initialized => {
+                    plaintext = PasswordRepository::new(&config.database_path)?;
+                    &plaintext
+                }
+                // The metadata of an encrypted vault can only be read once it's unlocked
+                DatabaseState::Encrypted => vault.unlock(&config)?.0,
+            };
+
+            let report = VaultDiagnostics::collect(repo, &config.database_path)?.render();
+            match output {
+                Some(output) => {
+                    write_private_file(&output, format!("{}\n", report).as_bytes())?;
+                    println!("Wrote diagnostics to {}", output.display());
+                }
+                None => println!("{}", report),
+            }
+            Ok(())
+        }
+        Commands::Capabilities => {
+            use passman_cli::cli::capabilities::capabilities;
+
+            println!("{}", serde_json::to_string_pretty(&capabilities())?);
+            Ok(())
+        }
+        Commands::SelfTest => {
+            let report = passman_cli::crypto::self_test();
+            for result in &report.results {
+                match &result.detail {
+                    None => println!("PASS  {}", result.name),
+                    Some(detail) => println!("FAIL  {}: {}", result.name, detail),
+                }
+            }
+
+            if !report.all_passed() {
+                anyhow::bail!("Crypto self-test failed");
+            }
+            Ok(())
+        }
+        Commands::BenchKdf { target_ms } => {
+            use colored::Colorize;
+            use std::time::Duration;
+
+            let target = Duration::from_millis(target_ms);
+            println!("Timing Argon2id key derivation (target {} ms)...", target_ms);
+            let tuning = passman_cli::crypto::kdf_bench::tune(target)?;
+            for sample in &tuning.samples {
+                println!(
+                    "  m={:>7} KiB  t={:>2}  p={}  {:>6} ms",
+                    sample.params.m_cost,
+                    sample.params.t_cost,
+                    sample.params.p_cost,
+                    sample.elapsed.as_millis()
+                );
+            }
+
+            if tuning.floor_exceeds(target) {
+                println!(
+                    "{}",
+                    "Even the minimum safe settings exceed the target; recommending the minimum.".yellow()
+                );
+            }
+            println!("\nAdd to the [security] section of your config file:\n");
+            print!("{}", tuning.config_snippet());
+            println!("\nThese apply to vaults created afterwards with `passman init`.");
+            Ok(())
+        }
+        Commands::Migrate { repair, encrypt, rollback } => {
+            use passman_cli::crypto::read_password;
+            use passman_cli::database::migrations::MigrationRunner;
+            use passman_cli::database::PasswordRepository;
+            use rusqlite::OpenFlags;
+            use zeroize::Zeroizing;
+
+            let config = global.load_config_without_create()?;
+            if !config.database_path.exists() {
+                return Err(vault_missing(&config.database_path));
+            }
+
+            let plaintext = PasswordRepository::is_plaintext_file(&config.database_path)?;
+            let lockout = session::Lockout::for_vault(&config.security, &config.database_path);
+            if encrypt {
+                if !plaintext {
+                    println!("{} is already encrypted", config.database_path.display());
+                    return Ok(());
+                }
+                lockout.check()?;
+                let password = Zeroizing::new(read_password("Master password: ")?);
+                lockout.record(session::encrypt_vault_file(&config.database_path, &password))?;
+                println!("Encrypted {}", config.database_path.display());
+                return Ok(());
+            }
+
+            let password = match plaintext {
+                true => None,
+                false => {
+                    lockout.check()?;
+                    Some(Zeroizing::new(read_password("Master password: ")?))
+                }
+            };
+            // Open without running migrations so the recorded state is inspected as-is
+            let conn = PasswordRepository::connect(
+                &config.database_path,
+                OpenFlags::default(),
+                password.as_ref().map(|p| p.as_str()),
+            );
+            // Only the encrypted file checks the password here
+            let conn = match password {
+                Some(_) => lockout.record(conn)?,
+                None => conn?,
+            };
+            let runner = MigrationRunner::new(&conn);
+
+            if let Some(version) = rollback {
+                use passman_cli::cli::prompt;
+
+                let current = runner.get_current_version()?;
+                if version >= current {
+                    println!("Schema is at version {}; nothing to roll back", current);
+                    return Ok(());
+                }
+                let question = format!(
+                    "Roll back the schema from version {} to {}? Data stored by the undone migrations is lost",
+                    current, version
+                );
+                if !prompt::confirm(&question)? {
+                    anyhow::bail!("Rollback cancelled");
+                }
+                for migration in runner.rollback_to(version)? {
+                    println!("Rolled back migration {}: {}", migration.version, migration.description);
+                }
+                return Ok(());
+            }
+
+            if repair {
+                let repaired = runner.repair()?;
+                for issue in &repaired {
+                    println!("Repaired: {}", issue);
+                }
+            }
+
+            let issues = runner.verify()?;
+            if issues.is_empty() {
+                println!("Migrations table is consistent with the schema");
+                return Ok(());
+            }
+
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            anyhow::bail!("{} migration issue(s) found", issues.len())
+        }
+        // These work on the config file itself, so `--vault` and friends aren't applied
+        Commands::Config { action: ConfigAction::Get { key } } => {
+            let config = Config::load_without_create(&global.profile)?;
+            match config.get_key(&key)? {
+                Some(toml::Value::String(value)) => println!("{}", value),
+                Some(value) => println!("{}", value),
+                None => {}
+            }
+            Ok(())
+        }
+        Commands::Config { action: ConfigAction::Set { key, value } } => {
+            let mut config = Config::load_without_create(&global.profile)?;
+            config.set_key(&key, &value)?;
+            config.save(&global.profile)?;
+            Ok(())
+        }
+        Commands::Config { action: ConfigAction::Path } => {
+            println!("{}", Config::config_file_path(&global.profile)?.display());
+            Ok(())
+        }
+        Commands::Profile { action: ProfileAction::List } => {
+            for name in passman_cli::config::list_profiles()? {
+                let marker = if name == global.profile { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+            Ok(())
+        }
+        Commands::Profile { action: ProfileAction::New { name } } => {
+            let config = Config::create_profile(&name)?;
+            println!("Created profile '{}' (vault: {})", name, config.database_path.display());
+            println!("Run `passman --profile {} init` to create its vault", name);
+            Ok(())
+        }
+        #[cfg(feature = "rpc")]
+        Commands::Serve => {
+            use passman_cli::rpc::RpcServer;
+
+            let config = global.load_config_without_create()?;
+            config.prepare_database_dir()?;
+
+            // The vault file is keyed by the master password, so it's opened on `unlock`
+            let mut server = RpcServer::open(&config.database_path, &config);
+            server.run(std::io::BufReader::new(std::io::stdin()), std::io::stdout().lock())?;
+            Ok(())
+        }
+        // Handled by `main`, and unavailable in the shell
+        Commands::Shell => anyhow::bail!("Already in a shell"),
+        #[cfg(feature = "web-ui")]
+        Commands::Web { .. } => anyhow::bail!("The web UI can't be started from the shell"),
     }
 }
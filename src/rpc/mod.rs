@@ -0,0 +1,561 @@
+//! Line-delimited JSON-RPC interface for long-running integrations
+//!
+//! Each line read from the input is a JSON-RPC 2.0 request and each response
+//! is written as a single line. The derived key stays in memory between
+//! requests until `lock` is called, the session times out, or input ends.
+//! An idle session is locked even while no requests arrive.
+
+use crate::cli::session;
+use crate::config::Config;
+use crate::crypto::LockedKey;
+use crate::database::{PasswordEntry, PasswordRepository, SecureString};
+use crate::utils::idle::{Clock, IdleTimer, SystemClock};
+use crate::utils::ClipboardManager;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// How often an idle session is checked against its timeout
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Invalid JSON was received
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON sent is not a valid request object
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters
+pub const INVALID_PARAMS: i64 = -32602;
+/// Application error while executing the method
+pub const APPLICATION_ERROR: i64 = -32000;
+/// The vault must be unlocked first
+pub const VAULT_LOCKED: i64 = -32001;
+
+/// JSON-RPC request
+#[derive(Debug, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: Option<String>,
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// JSON-RPC error object
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// JSON-RPC response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct UnlockParams {
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct GetParams {
+    title: String,
+    #[serde(default)]
+    reveal: bool,
+}
+
+#[derive(Deserialize)]
+struct AddParams {
+    title: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CopyParams {
+    title: String,
+}
+
+/// Error raised while dispatching a method
+enum MethodError {
+    InvalidParams(String),
+    Locked,
+    App(Error),
+}
+
+impl From<Error> for MethodError {
+    fn from(err: Error) -> Self {
+        MethodError::App(err)
+    }
+}
+
+type MethodResult = std::result::Result<Value, MethodError>;
+
+/// JSON-RPC server holding a single unlocked session
+pub struct RpcServer {
+    repo: Option<PasswordRepository>,
+    /// Vault file reopened on each unlock, when the server owns the connection
+    path: Option<PathBuf>,
+    key: Option<LockedKey>,
+    idle: IdleTimer,
+    lock_memory: bool,
+    lockout: session::Lockout,
+    clipboard: ClipboardManager,
+}
+
+impl RpcServer {
+    /// Create a locked server over the given repository
+    pub fn new(repo: PasswordRepository, config: &Config) -> Self {
+        Self::with_repo(Some(repo), None, config)
+    }
+
+    /// Create a locked server for the vault file at `path`
+    ///
+    /// The encrypted file is only opened by `unlock`, since opening it needs
+    /// the master password, and is closed again by `lock`.
+    pub fn open(path: &Path, config: &Config) -> Self {
+        Self::with_repo(None, Some(path.to_path_buf()), config)
+    }
+
+    fn with_repo(repo: Option<PasswordRepository>, path: Option<PathBuf>, config: &Config) -> Self {
+        Self {
+            repo,
+            key: None,
+            idle: IdleTimer::new(
+                Duration::from_secs(config.security.session_timeout * 60),
+                Arc::new(SystemClock),
+            ),
+            lock_memory: config.security.lock_memory,
+            // A server handed an open repository has no vault file to keep the lockout state next to
+            lockout: match &path {
+                Some(path) => session::Lockout::for_vault(&config.security, path),
+                None => session::Lockout::disabled(),
+            },
+            // OSC 52 output would be interleaved with responses on stdout
+            clipboard: ClipboardManager::from_config(config).with_osc52(None),
+            path,
+        }
+    }
+
+    /// Use a different clock for the idle timeout, e.g. in tests
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.idle = IdleTimer::new(self.idle.timeout(), clock);
+        self
+    }
+
+    /// Whether the session currently holds a key
+    pub fn is_unlocked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Time since the last request
+    pub fn idle_duration(&self) -> Duration {
+        self.idle.idle_for()
+    }
+
+    /// Lock the session if it has been idle past `security.session_timeout`
+    ///
+    /// Returns whether a key was dropped.
+    pub fn lock_if_idle(&mut self) -> bool {
+        if self.key.is_some() && self.idle.is_expired() {
+            self.lock();
+            return true;
+        }
+        false
+    }
+
+    /// Process requests line by line until the input is exhausted
+    ///
+    /// Input is read on a separate thread so the idle timeout is enforced
+    /// while waiting for the next request.
+    pub fn run<R, W>(&mut self, input: R, mut output: W) -> Result<()>
+    where
+        R: BufRead + Send + 'static,
+        W: Write,
+    {
+        let (lines_tx, lines_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in input.lines() {
+                if lines_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        loop {
+            let line = match lines_rx.recv_timeout(IDLE_POLL_INTERVAL) {
+                Ok(line) => Zeroizing::new(line?),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.lock_if_idle();
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = self.handle_line(&line);
+            serde_json::to_writer(&mut output, &response)?;
+            output.write_all(b"\n")?;
+            output.flush()?;
+        }
+
+        self.lock();
+        Ok(())
+    }
+
+    /// Parse and handle a single request line
+    pub fn handle_line(&mut self, line: &str) -> RpcResponse {
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => return RpcResponse::failure(Value::Null, PARSE_ERROR, e.to_string()),
+        };
+
+        let id = value.get("id").cloned().unwrap_or(Value::Null);
+        match serde_json::from_value::<RpcRequest>(value) {
+            Ok(request) => self.handle(request),
+            Err(e) => RpcResponse::failure(id, INVALID_REQUEST, e.to_string()),
+        }
+    }
+
+    /// Dispatch a parsed request
+    pub fn handle(&mut self, request: RpcRequest) -> RpcResponse {
+        self.lock_if_idle();
+        self.idle.touch();
+
+        let result = match request.method.as_str() {
+            "unlock" => self.unlock(request.params),
+            "lock" => {
+                self.lock();
+                Ok(json!({ "locked": true }))
+            }
+            "status" => Ok(json!({
+                "unlocked": self.is_unlocked(),
+                "timeout_seconds": self.idle.timeout().as_secs(),
+            })),
+            "list" => self.list(),
+            "get" => self.get(request.params),
+            "add" => self.add(request.params),
+            "copy" => self.copy(request.params),
+            method => {
+                return RpcResponse::failure(
+                    request.id,
+                    METHOD_NOT_FOUND,
+                    format!("Unknown method: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(value) => RpcResponse::success(request.id, value),
+            Err(MethodError::InvalidParams(message)) => {
+                RpcResponse::failure(request.id, INVALID_PARAMS, message)
+            }
+            Err(MethodError::Locked) => {
+                RpcResponse::failure(request.id, VAULT_LOCKED, "Vault is locked")
+            }
+            Err(MethodError::App(err)) => {
+                RpcResponse::failure(request.id, APPLICATION_ERROR, err.to_string())
+            }
+        }
+    }
+
+    /// Drop the in-memory key, and close the vault file if the server opened it
+    pub fn lock(&mut self) {
+        self.key = None;
+        if self.path.is_some() {
+            self.repo = None;
+        }
+    }
+
+    fn key(&self) -> std::result::Result<&[u8], MethodError> {
+        self.key.as_deref().ok_or(MethodError::Locked)
+    }
+
+    fn repo(&self) -> std::result::Result<&PasswordRepository, MethodError> {
+        self.repo.as_ref().ok_or(MethodError::Locked)
+    }
+
+    fn unlock(&mut self, params: Value) -> MethodResult {
+        let params: UnlockParams = parse_params(params)?;
+        let password = Zeroizing::new(params.password);
+
+        let key = match (&self.path, self.repo.take()) {
+            (_, Some(repo)) => {
+                let unlocked = session::unlock_with_lockout(&repo, &password, &self.lockout);
+                self.repo = Some(repo);
+                unlocked?
+            }
+            (Some(path), None) => {
+                let (repo, key) = session::open_with_lockout(session::open_initialized, path, &password, &self.lockout)?;
+                self.repo = Some(repo);
+                key
+            }
+            (None, None) => return Err(MethodError::Locked),
+        };
+        self.key = Some(LockedKey::new(key, self.lock_memory));
+
+        Ok(json!({ "unlocked": true }))
+    }
+
+    fn list(&self) -> MethodResult {
+        self.key()?;
+        let entries = self.repo()?.list_entries()?;
+        Ok(serde_json::to_value(entries).map_err(Error::from)?)
+    }
+
+    fn get(&self, params: Value) -> MethodResult {
+        let params: GetParams = parse_params(params)?;
+        let key = self.key()?;
+
+        let entry = self.repo()?.get_entry_decrypted(&params.title, key)?;
+        let mut value = serde_json::to_value(&entry).map_err(Error::from)?;
+
+        if params.reveal {
+            value["password"] = Value::String(entry.password.as_str().to_string());
+        }
+
+        Ok(value)
+    }
+
+    fn add(&self, params: Value) -> MethodResult {
+        let params: AddParams = parse_params(params)?;
+        let password = Zeroizing::new(params.password);
+        let key = self.key()?;
+        let repo = self.repo()?;
+
+        if repo.title_exists(&params.title)? {
+            return Err(Error::InvalidInput(format!("Entry '{}' already exists", params.title)).into());
+        }
+
+        let encrypted_password = repo.encryption_manager()?.encrypt(key, password.as_bytes())?;
+        let entry = PasswordEntry::new(
+            params.title,
+            params.username,
+            SecureString::default(),
+            params.url,
+            params.notes,
+        );
+        repo.add_entry(&entry, &encrypted_password)?;
+
+        Ok(json!({ "id": entry.id }))
+    }
+
+    fn copy(&self, params: Value) -> MethodResult {
+        let params: CopyParams = parse_params(params)?;
+        let key = self.key()?;
+
+        // Without a real clipboard the fallback would print the secret onto the response stream
+        if !cfg!(feature = "clipboard-support") {
+            return Err(Error::ClipboardUnavailable("clipboard support is not compiled in".to_string()).into());
+        }
+
+        let entry = self.repo()?.get_entry_decrypted(&params.title, key)?;
+        self.clipboard.copy_with_timeout(entry.password.as_str())?;
+
+        Ok(json!({ "copied": true }))
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> std::result::Result<T, MethodError> {
+    serde_json::from_value(params).map_err(|e| MethodError::InvalidParams(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::PasswordManager;
+    use std::io::Cursor;
+
+    fn vault(master: &str) -> PasswordRepository {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let password_manager = PasswordManager::new();
+        let (hash, _) = password_manager.hash_password(master).unwrap();
+        let salt = password_manager.generate_salt().unwrap();
+        repo.initialize_vault(salt, hash.into_bytes()).unwrap();
+        repo
+    }
+
+    fn run(server: &mut RpcServer, requests: &[Value]) -> Vec<RpcResponse> {
+        let input = requests
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut output = Vec::new();
+        server.run(Cursor::new(input), &mut output).unwrap();
+
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    #[test]
+    fn test_rpc_session() {
+        let mut server = RpcServer::new(vault("master"), &Config::default());
+
+        let responses = run(
+            &mut server,
+            &[
+                request(1, "list", Value::Null),
+                request(2, "unlock", json!({ "password": "wrong" })),
+                request(3, "unlock", json!({ "password": "master" })),
+                request(
+                    4,
+                    "add",
+                    json!({ "title": "GitHub", "username": "octocat", "password": "hunter2" }),
+                ),
+                request(5, "list", Value::Null),
+                request(6, "get", json!({ "title": "GitHub" })),
+                request(7, "get", json!({ "title": "GitHub", "reveal": true })),
+                request(8, "lock", Value::Null),
+                request(9, "get", json!({ "title": "GitHub", "reveal": true })),
+                request(10, "frobnicate", Value::Null),
+            ],
+        );
+
+        assert_eq!(responses.len(), 10);
+        assert_eq!(responses[0].error.as_ref().unwrap().code, VAULT_LOCKED);
+        assert_eq!(responses[1].error.as_ref().unwrap().code, APPLICATION_ERROR);
+        assert_eq!(responses[2].result, Some(json!({ "unlocked": true })));
+        assert!(responses[3].result.as_ref().unwrap()["id"].is_string());
+
+        let listed = responses[4].result.as_ref().unwrap().as_array().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0]["title"], "GitHub");
+        assert!(listed[0].get("password").is_none());
+
+        let hidden = responses[5].result.as_ref().unwrap();
+        assert_eq!(hidden["username"], "octocat");
+        assert!(hidden.get("password").is_none());
+
+        let revealed = responses[6].result.as_ref().unwrap();
+        assert_eq!(revealed["password"], "hunter2");
+
+        assert_eq!(responses[7].result, Some(json!({ "locked": true })));
+        assert_eq!(responses[8].error.as_ref().unwrap().code, VAULT_LOCKED);
+        assert_eq!(responses[9].error.as_ref().unwrap().code, METHOD_NOT_FOUND);
+        assert_eq!(responses[9].id, json!(10));
+    }
+
+    #[test]
+    fn test_rpc_opens_encrypted_vault_on_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passman.db");
+        session::init_vault(&path, "master", false).unwrap();
+
+        let mut server = RpcServer::open(&path, &Config::default());
+        let responses = run(
+            &mut server,
+            &[
+                request(1, "list", Value::Null),
+                request(2, "unlock", json!({ "password": "wrong" })),
+                request(3, "unlock", json!({ "password": "master" })),
+                request(4, "list", Value::Null),
+                request(5, "lock", Value::Null),
+                request(6, "list", Value::Null),
+            ],
+        );
+
+        assert_eq!(responses[0].error.as_ref().unwrap().code, VAULT_LOCKED);
+        assert_eq!(responses[1].error.as_ref().unwrap().code, APPLICATION_ERROR);
+        assert_eq!(responses[2].result, Some(json!({ "unlocked": true })));
+        assert_eq!(responses[3].result, Some(json!([])));
+        assert_eq!(responses[5].error.as_ref().unwrap().code, VAULT_LOCKED);
+    }
+
+    #[test]
+    fn test_rpc_locks_at_eof() {
+        let mut server = RpcServer::new(vault("master"), &Config::default());
+        run(&mut server, &[request(1, "unlock", json!({ "password": "master" }))]);
+
+        assert!(!server.is_unlocked());
+    }
+
+    #[test]
+    fn test_rpc_locks_when_idle() {
+        use crate::utils::idle::ManualClock;
+
+        let clock = ManualClock::new();
+        let mut server = RpcServer::new(vault("master"), &Config::default()).with_clock(Arc::new(clock.clone()));
+        let call = |server: &mut RpcServer, id, method, params| {
+            server.handle(serde_json::from_value(request(id, method, params)).unwrap())
+        };
+
+        assert!(call(&mut server, 1, "unlock", json!({ "password": "master" })).error.is_none());
+        clock.advance(Duration::from_secs(14 * 60));
+        assert!(!server.lock_if_idle());
+        assert_eq!(server.idle_duration(), Duration::from_secs(14 * 60));
+        assert!(call(&mut server, 2, "list", Value::Null).error.is_none());
+
+        clock.advance(Duration::from_secs(15 * 60 + 1));
+        assert!(server.lock_if_idle());
+        assert!(!server.is_unlocked());
+
+        let response = call(&mut server, 3, "list", Value::Null);
+        assert_eq!(response.error.unwrap().code, VAULT_LOCKED);
+        let status = call(&mut server, 4, "status", Value::Null).result.unwrap();
+        assert_eq!(status["unlocked"], false);
+    }
+
+    #[test]
+    fn test_rpc_malformed_input() {
+        let mut server = RpcServer::new(vault("master"), &Config::default());
+
+        let response = server.handle_line("{not json");
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+
+        let response = server.handle_line(r#"{"id": 3, "params": {}}"#);
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+        assert_eq!(response.id, json!(3));
+
+        let response = server.handle_line(r#"{"id": 4, "method": "get", "params": {}}"#);
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,272 @@
+//! Interactive entry picker for `passman tui`
+
+use crate::database::{PasswordEntry, SecureString};
+use crate::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use uuid::Uuid;
+
+/// What a key press asks the picker loop to do
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    /// Decrypt the selected entry's password and show it
+    Reveal,
+    /// Pick the selected entry
+    Select,
+    Quit,
+}
+
+/// Filterable list of entries, with at most one password revealed
+struct Picker {
+    entries: Vec<PasswordEntry>,
+    query: String,
+    /// Indices into `entries` whose title matches `query`
+    matches: Vec<usize>,
+    list: ListState,
+    revealed: Option<(Uuid, SecureString)>,
+    message: Option<String>,
+}
+
+impl Picker {
+    fn new(entries: Vec<PasswordEntry>) -> Self {
+        let mut picker = Self {
+            entries,
+            query: String::new(),
+            matches: Vec::new(),
+            list: ListState::default(),
+            revealed: None,
+            message: None,
+        };
+        picker.refilter();
+        picker
+    }
+
+    fn selected(&self) -> Option<&PasswordEntry> {
+        let index = *self.matches.get(self.list.selected()?)?;
+        Some(&self.entries[index])
+    }
+
+    fn refilter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.matches = (0..self.entries.len())
+            .filter(|&i| self.entries[i].title.to_lowercase().contains(&query))
+            .collect();
+        self.list.select(if self.matches.is_empty() { None } else { Some(0) });
+        self.hide();
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let Some(current) = self.list.selected() else { return };
+        let next = if down {
+            (current + 1).min(self.matches.len() - 1)
+        } else {
+            current.saturating_sub(1)
+        };
+        if next != current {
+            self.list.select(Some(next));
+            self.hide();
+        }
+    }
+
+    /// Forget the revealed password, e.g. once the selection moves off it
+    fn hide(&mut self) {
+        self.revealed = None;
+        self.message = None;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind != KeyEventKind::Press {
+            return Action::None;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => Action::Quit,
+            KeyCode::Char('c') if ctrl => Action::Quit,
+            KeyCode::Char('r') if ctrl => match &self.revealed {
+                Some(_) => {
+                    self.hide();
+                    Action::None
+                }
+                None if self.selected().is_some() => Action::Reveal,
+                None => Action::None,
+            },
+            KeyCode::Enter if self.selected().is_some() => Action::Select,
+            KeyCode::Up => {
+                self.move_selection(false);
+                Action::None
+            }
+            KeyCode::Down => {
+                self.move_selection(true);
+                Action::None
+            }
+            KeyCode::Backspace => {
+                if self.query.pop().is_some() {
+                    self.refilter();
+                }
+                Action::None
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refilter();
+                Action::None
+            }
+            _ => Action::None,
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search, list, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+
+        let query = Paragraph::new(self.query.as_str()).block(Block::default().borders(Borders::ALL).title("Search"));
+        frame.render_widget(query, search);
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&i| ListItem::new(self.entries[i].title.as_str()))
+            .collect();
+        let title = format!("Entries ({}/{})", self.matches.len(), self.entries.len());
+        let entries = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(entries, list, &mut self.list);
+
+        let line = match (&self.revealed, &self.message, self.selected()) {
+            (Some((id, password)), _, Some(entry)) if *id == entry.id => {
+                Line::from(format!("{} / {}", entry.username, password.as_str()))
+            }
+            (_, Some(message), _) => Line::from(message.as_str()),
+            _ => Line::from("↑/↓ move · Enter copy password · Ctrl-R reveal · Esc quit"),
+        };
+        let help = Paragraph::new(line).block(Block::default().borders(Borders::ALL));
+        frame.render_widget(help, status);
+    }
+}
+
+/// Let the user search `entries` by title and pick one
+///
+/// Only metadata is shown; `reveal` is called to decrypt the selected
+/// entry's password when the user asks to see it. Returns `None` if the
+/// user quit without picking.
+pub fn pick<F>(entries: Vec<PasswordEntry>, mut reveal: F) -> Result<Option<PasswordEntry>>
+where
+    F: FnMut(&PasswordEntry) -> Result<SecureString>,
+{
+    let mut picker = Picker::new(entries);
+    let mut terminal = ratatui::try_init()?;
+    let _restore = RestoreTerminal;
+
+    loop {
+        terminal.draw(|frame| picker.draw(frame))?;
+
+        let Event::Key(key) = event::read()? else { continue };
+        match picker.handle_key(key) {
+            Action::None => {}
+            Action::Reveal => {
+                let entry = picker.selected().expect("reveal needs a selection");
+                match reveal(entry) {
+                    Ok(password) => picker.revealed = Some((entry.id, password)),
+                    Err(e) => picker.message = Some(format!("Can't decrypt '{}': {}", entry.title, e)),
+                }
+            }
+            Action::Select => {
+                let index = picker.matches[picker.list.selected().expect("select needs a selection")];
+                return Ok(Some(picker.entries.swap_remove(index)));
+            }
+            Action::Quit => return Ok(None),
+        }
+    }
+}
+
+/// Leaves the alternate screen and raw mode however the picker loop ends
+struct RestoreTerminal;
+
+impl Drop for RestoreTerminal {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str) -> PasswordEntry {
+        PasswordEntry::new(title.to_string(), "me".to_string(), SecureString::default(), None, None)
+    }
+
+    fn press(picker: &mut Picker, code: KeyCode) -> Action {
+        picker.handle_key(KeyEvent::from(code))
+    }
+
+    fn ctrl(picker: &mut Picker, c: char) -> Action {
+        picker.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    #[test]
+    fn test_typing_filters_titles() {
+        let mut picker = Picker::new(vec![entry("GitHub"), entry("Gmail"), entry("Bank")]);
+        assert_eq!(picker.matches.len(), 3);
+
+        press(&mut picker, KeyCode::Char('g'));
+        assert_eq!(picker.matches.len(), 2);
+        press(&mut picker, KeyCode::Char('M'));
+        assert_eq!(picker.selected().unwrap().title, "Gmail");
+
+        press(&mut picker, KeyCode::Char('x'));
+        assert!(picker.selected().is_none());
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::None);
+
+        press(&mut picker, KeyCode::Backspace);
+        press(&mut picker, KeyCode::Backspace);
+        assert_eq!(picker.matches.len(), 2);
+    }
+
+    #[test]
+    fn test_keys_map_to_actions() {
+        let mut picker = Picker::new(vec![entry("GitHub"), entry("Gmail")]);
+
+        press(&mut picker, KeyCode::Down);
+        press(&mut picker, KeyCode::Down);
+        assert_eq!(picker.selected().unwrap().title, "Gmail");
+        press(&mut picker, KeyCode::Up);
+        assert_eq!(picker.selected().unwrap().title, "GitHub");
+
+        assert_eq!(press(&mut picker, KeyCode::Enter), Action::Select);
+        assert_eq!(press(&mut picker, KeyCode::Esc), Action::Quit);
+        assert_eq!(ctrl(&mut picker, 'c'), Action::Quit);
+        // Ctrl-R isn't typed into the search
+        assert_eq!(ctrl(&mut picker, 'r'), Action::Reveal);
+        assert_eq!(picker.query, "");
+    }
+
+    #[test]
+    fn test_revealed_password_is_hidden_again() {
+        let mut picker = Picker::new(vec![entry("GitHub"), entry("Gmail")]);
+        let id = picker.selected().unwrap().id;
+
+        picker.revealed = Some((id, SecureString::from("hunter2")));
+        assert_eq!(ctrl(&mut picker, 'r'), Action::None);
+        assert!(picker.revealed.is_none());
+
+        picker.revealed = Some((id, SecureString::from("hunter2")));
+        press(&mut picker, KeyCode::Down);
+        assert!(picker.revealed.is_none());
+
+        picker.revealed = Some((id, SecureString::from("hunter2")));
+        press(&mut picker, KeyCode::Char('g'));
+        assert!(picker.revealed.is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::database::{PasswordEntry, SecureString};
+use crate::utils::generator::estimate_entropy;
+use std::collections::HashMap;
+
+/// Estimate a password's entropy in bits from the character classes it uses
+pub fn password_entropy(password: &str) -> f64 {
+    let mut charset_size = 0usize;
+
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        charset_size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        charset_size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        charset_size += 10;
+    }
+    if password.chars().any(|c| !c.is_ascii_alphanumeric()) {
+        charset_size += 32;
+    }
+
+    estimate_entropy(password, charset_size)
+}
+
+/// Check whether a password falls below the given entropy threshold
+pub fn is_weak(password: &str, min_bits: f64) -> bool {
+    password_entropy(password) < min_bits
+}
+
+/// Entries whose password falls below `min_bits`, with its estimated entropy
+///
+/// Entries without a password are ignored.
+pub fn find_weak<I>(entries: I, min_bits: f64) -> Vec<(PasswordEntry, f64)>
+where
+    I: IntoIterator<Item = (PasswordEntry, SecureString)>,
+{
+    entries
+        .into_iter()
+        .filter(|(_, password)| !password.is_empty())
+        .map(|(entry, password)| (entry, password_entropy(password.as_str())))
+        .filter(|(_, bits)| *bits < min_bits)
+        .collect()
+}
+
+/// Group entries that share a password, keeping only groups of two or more
+///
+/// Passwords are compared by their SHA-256 digest, so the scan never holds
+/// more than one plaintext at a time. Entries without a password are ignored.
+/// Groups are ordered by their first entry, entries keep the input order.
+pub fn find_reused<I>(entries: I) -> Vec<Vec<PasswordEntry>>
+where
+    I: IntoIterator<Item = (PasswordEntry, SecureString)>,
+{
+    let mut groups: Vec<Vec<PasswordEntry>> = Vec::new();
+    let mut by_digest: HashMap<Vec<u8>, usize> = HashMap::new();
+
+    for (entry, password) in entries {
+        if password.is_empty() {
+            continue;
+        }
+        let digest = ring::digest::digest(&ring::digest::SHA256, password.as_str().as_bytes());
+        drop(password);
+
+        match by_digest.get(digest.as_ref()) {
+            Some(&index) => groups[index].push(entry),
+            None => {
+                by_digest.insert(digest.as_ref().to_vec(), groups.len());
+                groups.push(vec![entry]);
+            }
+        }
+    }
+
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_detection() {
+        assert!(is_weak("password", 60.0));
+        assert!(!is_weak("Xk9#mP2$vL7@qR4!", 60.0));
+        assert_eq!(password_entropy(""), 0.0);
+    }
+
+    #[test]
+    fn test_find_reused_groups_identical_passwords() {
+        let entry = |title: &str, password: &str| {
+            let entry = PasswordEntry::new(title.to_string(), "me".to_string(), SecureString::default(), None, None);
+            (entry, SecureString::from(password))
+        };
+        let groups = find_reused(vec![
+            entry("Bank", "hunter2"),
+            entry("Blank", ""),
+            entry("Email", "correct horse"),
+            entry("Forum", "hunter2"),
+            entry("Empty", ""),
+            entry("Shop", "hunter2"),
+            entry("Unique", "Hunter2"),
+        ]);
+
+        let titles: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|group| group.iter().map(|entry| entry.title.as_str()).collect())
+            .collect();
+        assert_eq!(titles, vec![vec!["Bank", "Forum", "Shop"]]);
+    }
+
+    #[test]
+    fn test_find_weak_reports_bits_below_threshold() {
+        let entry = |title: &str, password: &str| {
+            let entry = PasswordEntry::new(title.to_string(), "me".to_string(), SecureString::default(), None, None);
+            (entry, SecureString::from(password))
+        };
+        let weak = find_weak(vec![entry("Pin", "1234"), entry("Blank", ""), entry("Good", "Xk9#mP2$vL7@qR4!")], 60.0);
+
+        assert_eq!(weak.len(), 1);
+        assert_eq!(weak[0].0.title, "Pin");
+        assert!((weak[0].1 - 4.0 * 10f64.log2()).abs() < 1e-9);
+    }
+}
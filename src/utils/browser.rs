@@ -0,0 +1,69 @@
+use crate::{Error, Result};
+use std::process::Command;
+
+/// Normalize an entry URL for opening, defaulting to https
+///
+/// Only web URLs are opened; anything else (`file:`, `javascript:`, ...) is
+/// refused so an imported entry can't launch arbitrary handlers.
+pub fn web_url(url: &str) -> Result<String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err(Error::InvalidInput("URL is empty".to_string()));
+    }
+
+    match url.split_once("://") {
+        Some((scheme, _)) if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") => {
+            Ok(url.to_string())
+        }
+        Some((scheme, _)) => Err(Error::InvalidInput(format!("Refusing to open a '{}' URL", scheme))),
+        None => match url.split_once(':') {
+            // `host:port` is fine, `mailto:` or `javascript:` is not
+            Some((scheme, rest)) if !rest.starts_with(|c: char| c.is_ascii_digit()) => {
+                Err(Error::InvalidInput(format!("Refusing to open a '{}' URL", scheme)))
+            }
+            _ => Ok(format!("https://{}", url)),
+        },
+    }
+}
+
+/// Open a URL in the default browser
+pub fn open_url(url: &str) -> Result<()> {
+    let url = web_url(url)?;
+
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // Avoids `cmd /C start`, which would interpret `&` in the URL
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    let program = format!("{:?}", command.get_program());
+    let status = command
+        .arg(&url)
+        .status()
+        .map_err(|e| Error::InvalidInput(format!("Failed to launch {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(Error::InvalidInput(format!("{} exited with {}", program, status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_url_adds_https_and_refuses_other_schemes() {
+        assert_eq!(web_url("github.com/login").unwrap(), "https://github.com/login");
+        assert_eq!(web_url(" HTTP://example.com ").unwrap(), "HTTP://example.com");
+        assert_eq!(web_url("example.com:8443/a?b=1&c=2").unwrap(), "https://example.com:8443/a?b=1&c=2");
+        assert!(web_url("file:///etc/passwd").is_err());
+        assert_eq!(web_url("localhost:8080").unwrap(), "https://localhost:8080");
+        assert!(web_url("javascript:alert(1)").is_err());
+        assert!(web_url("").is_err());
+    }
+}
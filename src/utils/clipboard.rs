@@ -1,12 +1,47 @@
+use crate::config::Config;
+use crate::database::PasswordEntry;
+use crate::utils::template;
 use crate::{Error, Result};
 
-#[cfg(feature = "clipboard-support")]
-use clipboard::{ClipboardContext, ClipboardProvider};
+use base64::Engine;
+use std::io::Write;
 use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Default maximum clipboard payload in bytes
+pub const DEFAULT_MAX_PAYLOAD: usize = 100_000;
+
+/// Handle on the system clipboard
+///
+/// On X11 copied text is served by the process that set it, so the handle
+/// that copied a secret is kept alive until the secret is cleared.
+#[cfg(feature = "clipboard-support")]
+type SystemClipboard = arboard::Clipboard;
+#[cfg(not(feature = "clipboard-support"))]
+type SystemClipboard = ();
+
+/// Destination for secrets copied to the clipboard
+pub trait ClipboardSink {
+    /// Copy a secret, clearing it again according to the sink's policy
+    fn copy_secret(&self, text: &str) -> Result<()>;
+}
 
 /// Clipboard manager for secure password copying
 pub struct ClipboardManager {
     timeout: Duration,
+    max_payload: usize,
+    osc52: Option<Osc52Mode>,
+    wait: bool,
+}
+
+/// How text is written when copying through the terminal with OSC 52
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Osc52Mode {
+    /// A single escape sequence
+    Single,
+    /// Passthrough sequences carrying at most this many encoded bytes each,
+    /// for terminal multiplexers that cap sequence length
+    Chunked(usize),
 }
 
 impl ClipboardManager {
@@ -14,45 +49,120 @@ impl ClipboardManager {
     pub fn new(timeout_seconds: u64) -> Self {
         Self {
             timeout: Duration::from_secs(timeout_seconds),
+            max_payload: DEFAULT_MAX_PAYLOAD,
+            osc52: None,
+            wait: false,
+        }
+    }
+
+    /// Create a clipboard manager from the application configuration
+    pub fn from_config(config: &Config) -> Self {
+        let osc52 = match (config.clipboard_osc52, config.osc52_chunk_size) {
+            (false, _) => None,
+            (true, 0) => Some(Osc52Mode::Single),
+            (true, size) => Some(Osc52Mode::Chunked(size)),
+        };
+
+        Self::new(config.clipboard_timeout)
+            .with_max_payload(config.clipboard_max_bytes)
+            .with_osc52(osc52)
+    }
+
+    /// Set the maximum payload size in bytes
+    pub fn with_max_payload(mut self, max_payload: usize) -> Self {
+        self.max_payload = max_payload;
+        self
+    }
+
+    /// Copy through the terminal with OSC 52 instead of the system clipboard
+    pub fn with_osc52(mut self, osc52: Option<Osc52Mode>) -> Self {
+        self.osc52 = osc52;
+        self
+    }
+
+    /// Block until the timeout instead of clearing from a background thread
+    ///
+    /// A one-shot command needs this: the system clipboard handle, and the
+    /// thread that would clear it, only live as long as the process.
+    pub fn with_wait(mut self, wait: bool) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Reject payloads larger than the configured maximum
+    pub fn check_payload(&self, text: &str) -> Result<()> {
+        if text.len() > self.max_payload {
+            return Err(Error::Clipboard(format!(
+                "Payload of {} bytes exceeds the clipboard limit of {} bytes",
+                text.len(),
+                self.max_payload
+            )));
         }
+        Ok(())
     }
 
     /// Copy text to clipboard
     pub fn copy(&self, text: &str) -> Result<()> {
+        self.copy_holding(text).map(drop)
+    }
+
+    /// Copy text, returning the system clipboard handle that now holds it
+    fn copy_holding(&self, text: &str) -> Result<Option<SystemClipboard>> {
+        self.check_payload(text)?;
+
+        if let Some(mode) = self.osc52 {
+            let mut stdout = std::io::stdout().lock();
+            for sequence in osc52_sequences(text, mode) {
+                stdout.write_all(sequence.as_bytes())?;
+            }
+            stdout.flush()?;
+            return Ok(None);
+        }
+
         #[cfg(feature = "clipboard-support")]
         {
-            let mut ctx: ClipboardContext = ClipboardProvider::new()
-                .map_err(|e| Error::Clipboard(format!("Failed to access clipboard: {}", e)))?;
+            let mut clipboard = open_system_clipboard()?;
             
-            ctx.set_contents(text.to_string())
+            clipboard.set_text(text)
                 .map_err(|e| Error::Clipboard(format!("Failed to copy to clipboard: {}", e)))?;
             
-            Ok(())
+            Ok(Some(clipboard))
         }
         #[cfg(not(feature = "clipboard-support"))]
         {
             println!("Text to copy: {}", text);
             println!("Note: Clipboard support not available. The text is displayed above.");
-            Ok(())
+            Ok(None)
         }
     }
 
     /// Copy text to clipboard with auto-clear
+    ///
+    /// The copying handle is kept until the clear, since on X11 the process
+    /// that set the clipboard is the one serving it.
     pub fn copy_with_timeout(&self, text: &str) -> Result<()> {
-        self.copy(text)?;
+        let held = self.copy_holding(text)?;
         
         if self.timeout.as_secs() > 0 {
             println!("Password copied to clipboard (will be cleared in {} seconds)", 
                      self.timeout.as_secs());
-            
-            // Spawn a thread to clear clipboard after timeout
-            let timeout = self.timeout;
-            
-            std::thread::spawn(move || {
-                std::thread::sleep(timeout);
-                // Simply clear the clipboard after timeout
-                let _ = Self::clear_clipboard();
-            });
+
+            let copied = Zeroizing::new(text.to_string());
+            match held {
+                Some(held) if self.wait => {
+                    println!("Keeping passman running until then; the clipboard is lost if it is stopped");
+                    wait_then_clear(held, &copied, self.timeout)?;
+                }
+                held => {
+                    // Spawn a thread to clear clipboard after timeout
+                    let timeout = self.timeout;
+                    std::thread::spawn(move || {
+                        std::thread::sleep(timeout);
+                        // Leave anything the user copied in the meantime alone
+                        let _ = Self::clear_if_still(held, &copied);
+                    });
+                }
+            }
         } else {
             println!("Password copied to clipboard");
         }
@@ -60,51 +170,123 @@ impl ClipboardManager {
         Ok(())
     }
 
+    /// Copy text that is left on the clipboard, serving it until the timeout
+    ///
+    /// Nothing is cleared, but the copying handle is held like in
+    /// `copy_with_timeout` so the text can still be pasted on X11 after a
+    /// one-shot command is done.
+    pub fn copy_held(&self, text: &str) -> Result<()> {
+        let Some(held) = self.copy_holding(text)? else {
+            return Ok(());
+        };
+
+        if self.timeout.as_secs() > 0 {
+            if self.wait {
+                println!("Keeping passman running for {} seconds so it can be pasted",
+                         self.timeout.as_secs());
+                hold_for(held, self.timeout);
+            } else {
+                let timeout = self.timeout;
+                std::thread::spawn(move || hold_for(held, timeout));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Get current clipboard contents
     pub fn get(&self) -> Result<String> {
+        Self::read_clipboard(&mut None)
+    }
+
+    /// Clear clipboard
+    pub fn clear(&self) -> Result<()> {
+        self.copy("")
+    }
+
+    /// Clear the clipboard if it still holds `copied` (static method for thread use)
+    fn clear_if_still(mut held: Option<SystemClipboard>, copied: &str) -> Result<bool> {
+        let current = Self::read_clipboard(&mut held);
+        clear_if_unchanged(copied, current, || Self::clear_clipboard(&mut held))
+    }
+
+    /// Read the clipboard through `held`, or a new handle if there is none
+    fn read_clipboard(held: &mut Option<SystemClipboard>) -> Result<String> {
         #[cfg(feature = "clipboard-support")]
         {
-            let mut ctx: ClipboardContext = ClipboardProvider::new()
-                .map_err(|e| Error::Clipboard(format!("Failed to access clipboard: {}", e)))?;
+            let clipboard = match held {
+                Some(clipboard) => clipboard,
+                None => held.insert(open_system_clipboard()?),
+            };
             
-            ctx.get_contents()
-                .map_err(|e| Error::Clipboard(format!("Failed to read from clipboard: {}", e)))
+            clipboard_text(clipboard.get_text())
         }
         #[cfg(not(feature = "clipboard-support"))]
         {
-            Err(Error::Clipboard("Clipboard support not available".to_string()))
+            let _ = held;
+            Err(Error::ClipboardUnavailable("clipboard support is not compiled in".to_string()))
         }
     }
 
-    /// Clear clipboard
-    pub fn clear(&self) -> Result<()> {
-        self.copy("")
-    }
-
-    /// Clear clipboard (static method for thread use)
-    fn clear_clipboard() -> Result<()> {
+    /// Clear the clipboard through `held`, or a new handle if there is none
+    fn clear_clipboard(held: &mut Option<SystemClipboard>) -> Result<()> {
         #[cfg(feature = "clipboard-support")]
         {
-            let mut ctx: ClipboardContext = ClipboardProvider::new()
-                .map_err(|e| Error::Clipboard(format!("Failed to access clipboard: {}", e)))?;
-            ctx.set_contents(String::new())
-                .map_err(|e| Error::Clipboard(format!("Failed to clear clipboard: {}", e)))?;
-            Ok(())
+            let clipboard = match held {
+                Some(clipboard) => clipboard,
+                None => held.insert(open_system_clipboard()?),
+            };
+            clipboard.clear()
+                .map_err(|e| Error::Clipboard(format!("Failed to clear clipboard: {}", e)))
         }
         #[cfg(not(feature = "clipboard-support"))]
         {
+            let _ = held;
             println!("Clipboard cleared (simulated)");
             Ok(())
         }
     }
 }
 
+impl ClipboardSink for ClipboardManager {
+    fn copy_secret(&self, text: &str) -> Result<()> {
+        self.copy_with_timeout(text)
+    }
+}
+
 impl Default for ClipboardManager {
     fn default() -> Self {
         Self::new(30) // 30 seconds default timeout
     }
 }
 
+/// Serve `copied` through `held` for `timeout`, then clear it if it's still there
+///
+/// Returns whether it was cleared.
+fn wait_then_clear(held: SystemClipboard, copied: &str, timeout: Duration) -> Result<bool> {
+    std::thread::sleep(timeout);
+    ClipboardManager::clear_if_still(Some(held), copied)
+}
+
+/// Serve text through `held` for `timeout`, then let it go
+fn hold_for(_held: SystemClipboard, timeout: Duration) {
+    std::thread::sleep(timeout);
+}
+
+/// Clear the clipboard only if its `current` contents are still `copied`
+///
+/// Returns whether it was cleared. A clipboard that can't be read is cleared
+/// anyway, since it may still hold the secret.
+fn clear_if_unchanged<C>(copied: &str, current: Result<String>, clear: C) -> Result<bool>
+where
+    C: FnOnce() -> Result<()>,
+{
+    match current.map(Zeroizing::new) {
+        Ok(current) if current.as_str() != copied => Ok(false),
+        _ => clear().map(|_| true),
+    }
+}
+
 /// Copy password to clipboard with default settings
 pub fn copy_password(password: &str) -> Result<()> {
     let manager = ClipboardManager::default();
@@ -116,3 +298,235 @@ pub fn copy_text(text: &str) -> Result<()> {
     let manager = ClipboardManager::new(0);
     manager.copy(text)
 }
+
+/// Refuse to copy or reveal more than `limit` secrets at once unless `confirm` agrees
+///
+/// `confirm` is only asked, with the number of secrets, when the limit is exceeded.
+pub fn guard_bulk_reveal<F>(count: usize, limit: usize, confirm: F) -> Result<()>
+where
+    F: FnOnce(usize) -> Result<bool>,
+{
+    if count <= limit || confirm(count)? {
+        return Ok(());
+    }
+
+    Err(Error::InvalidInput(format!(
+        "Refusing to reveal {} secrets at once (limit is {}, see security.max_secrets_per_operation)",
+        count, limit
+    )))
+}
+
+/// Classify a failure to connect to the system clipboard as unavailability
+///
+/// Failing to open the clipboard means there is none to use (no display
+/// server, CI), as opposed to a copy that failed on a working clipboard.
+#[cfg_attr(not(feature = "clipboard-support"), allow(dead_code))]
+fn open_clipboard<C, E: std::fmt::Display>(opened: std::result::Result<C, E>) -> Result<C> {
+    opened.map_err(|e| Error::ClipboardUnavailable(e.to_string()))
+}
+
+/// Connect to the system clipboard
+///
+/// On Linux, builds with `clipboard-wayland` use the Wayland data-control
+/// protocol when `WAYLAND_DISPLAY` is set and fall back to X11 otherwise.
+#[cfg(feature = "clipboard-support")]
+fn open_system_clipboard() -> Result<SystemClipboard> {
+    open_clipboard(arboard::Clipboard::new())
+}
+
+/// The clipboard's text, treating an empty or non-text clipboard as ""
+#[cfg(feature = "clipboard-support")]
+fn clipboard_text(read: std::result::Result<String, arboard::Error>) -> Result<String> {
+    match read {
+        Ok(text) => Ok(text),
+        Err(arboard::Error::ContentNotAvailable) => Ok(String::new()),
+        Err(e) => Err(Error::Clipboard(format!("Failed to read from clipboard: {}", e))),
+    }
+}
+
+/// Build the OSC 52 escape sequences that set the clipboard to `text`
+pub fn osc52_sequences(text: &str, mode: Osc52Mode) -> Vec<String> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+
+    match mode {
+        Osc52Mode::Single => vec![format!("\x1b]52;c;{}\x07", encoded)],
+        Osc52Mode::Chunked(chunk_size) => {
+            let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(chunk_size.max(1)).collect();
+            let last = chunks.len().saturating_sub(1);
+
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+                    let prefix = if i == 0 { "\x1b]52;c;" } else { "" };
+                    let suffix = if i == last { "\x07" } else { "" };
+                    // Wrap each piece in a DCS passthrough so multiplexers forward it
+                    format!("\x1bP{}{}{}\x1b\\", prefix, chunk, suffix)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Copy a login block for an entry, rendered from a template
+///
+/// The template may reference `{title}`, `{username}`, `{password}` and `{url}`.
+/// The entry's password field must already be decrypted.
+pub fn copy_login(sink: &dyn ClipboardSink, template: &str, entry: &PasswordEntry) -> Result<()> {
+    let block = zeroize::Zeroizing::new(template::render(
+        template,
+        &[
+            ("title", &entry.title),
+            ("username", &entry.username),
+            ("password", entry.password.as_str()),
+            ("url", entry.url.as_deref().unwrap_or("")),
+        ],
+    )?);
+
+    sink.copy_secret(&block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SecureString;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct MockClipboard {
+        contents: RefCell<Option<String>>,
+    }
+
+    impl ClipboardSink for MockClipboard {
+        fn copy_secret(&self, text: &str) -> Result<()> {
+            *self.contents.borrow_mut() = Some(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_unavailable_backend_is_distinct_from_copy_failure() {
+        struct NoDisplay;
+        let opened = open_clipboard::<NoDisplay, _>(Err("no X11 display"));
+        assert!(matches!(opened, Err(Error::ClipboardUnavailable(_))));
+
+        let manager = ClipboardManager::new(0).with_max_payload(1);
+        assert!(matches!(manager.copy("too long"), Err(Error::Clipboard(_))));
+    }
+
+    #[test]
+    fn test_payload_limit() {
+        let manager = ClipboardManager::new(0).with_max_payload(8);
+        assert!(manager.check_payload("12345678").is_ok());
+
+        let err = manager.copy("123456789").unwrap_err();
+        assert!(err.to_string().contains("exceeds the clipboard limit of 8 bytes"));
+    }
+
+    #[test]
+    fn test_osc52_chunking() {
+        let single = osc52_sequences("hello", Osc52Mode::Single);
+        assert_eq!(single, vec!["\x1b]52;c;aGVsbG8=\x07".to_string()]);
+
+        // 300 bytes encode to 400 base64 characters
+        let text = "x".repeat(300);
+        let chunks = osc52_sequences(&text, Osc52Mode::Chunked(100));
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks[0].starts_with("\x1bP\x1b]52;c;"));
+        assert!(chunks[3].ends_with("\x07\x1b\\"));
+
+        let payload: String = chunks
+            .iter()
+            .map(|c| {
+                c.trim_start_matches("\x1bP")
+                    .trim_start_matches("\x1b]52;c;")
+                    .trim_end_matches("\x1b\\")
+                    .trim_end_matches('\x07')
+            })
+            .collect();
+        let decoded = base64::engine::general_purpose::STANDARD.decode(payload).unwrap();
+        assert_eq!(decoded, text.as_bytes());
+    }
+
+    #[test]
+    fn test_copy_login_uses_template() {
+        let entry = PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::from("hunter2"),
+            Some("https://github.com".to_string()),
+            None,
+        );
+        let clipboard = MockClipboard::default();
+
+        copy_login(&clipboard, "{username}\t{password}", &entry).unwrap();
+        assert_eq!(clipboard.contents.borrow().as_deref(), Some("octocat\thunter2"));
+
+        copy_login(&clipboard, "{url} {username} {password}", &entry).unwrap();
+        assert_eq!(
+            clipboard.contents.borrow().as_deref(),
+            Some("https://github.com octocat hunter2")
+        );
+    }
+
+    #[test]
+    fn test_bulk_reveal_guard() {
+        assert!(guard_bulk_reveal(3, 3, |_| panic!("no confirmation needed")).is_ok());
+
+        let refused = guard_bulk_reveal(4, 3, |count| {
+            assert_eq!(count, 4);
+            Ok(false)
+        });
+        assert!(matches!(refused, Err(Error::InvalidInput(_))));
+
+        assert!(guard_bulk_reveal(4, 3, |_| Ok(true)).is_ok());
+    }
+
+    #[test]
+    fn test_auto_clear_leaves_newer_contents_alone() {
+        let cleared = RefCell::new(false);
+        let clear = || {
+            *cleared.borrow_mut() = true;
+            Ok(())
+        };
+
+        assert!(!clear_if_unchanged("hunter2", Ok("meeting notes".to_string()), clear).unwrap());
+        assert!(!*cleared.borrow());
+
+        assert!(clear_if_unchanged("hunter2", Ok("hunter2".to_string()), clear).unwrap());
+        assert!(*cleared.borrow());
+
+        let unreadable = Err(Error::ClipboardUnavailable("no display".to_string()));
+        assert!(clear_if_unchanged("hunter2", unreadable, || Ok(())).unwrap());
+    }
+
+    #[cfg(feature = "clipboard-support")]
+    #[test]
+    fn test_empty_clipboard_reads_as_empty_text() {
+        assert_eq!(clipboard_text(Ok("hunter2".to_string())).unwrap(), "hunter2");
+        assert_eq!(clipboard_text(Err(arboard::Error::ContentNotAvailable)).unwrap(), "");
+
+        let err = clipboard_text(Err(arboard::Error::ClipboardOccupied)).unwrap_err();
+        assert!(matches!(err, Error::Clipboard(_)));
+    }
+
+    #[cfg(not(feature = "clipboard-support"))]
+    #[test]
+    fn test_waiting_copy_clears_before_returning() {
+        let start = std::time::Instant::now();
+        assert!(wait_then_clear((), "hunter2", Duration::from_millis(50)).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[cfg(not(feature = "clipboard-support"))]
+    #[test]
+    fn test_without_a_backend_the_secret_is_still_cleared() {
+        let manager = ClipboardManager::new(0);
+        assert!(matches!(manager.get(), Err(Error::ClipboardUnavailable(_))));
+
+        // Nothing to hold on to, and an unreadable clipboard is cleared anyway
+        assert!(manager.copy_holding("hunter2").unwrap().is_none());
+        assert!(ClipboardManager::clear_if_still(None, "hunter2").unwrap());
+    }
+}
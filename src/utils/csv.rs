@@ -0,0 +1,137 @@
+//! Minimal RFC 4180 CSV support for list output and spreadsheet imports
+
+/// Quote a field if it contains a delimiter, quote or line break
+pub fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Format one CSV record, terminated by CRLF as RFC 4180 specifies
+pub fn format_record<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields.iter().map(|field| escape_field(field.as_ref())).collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+/// One parsed record and the line it starts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRecord {
+    /// 1-based line number of the record's first line
+    pub line: usize,
+    /// The record's fields, or why it couldn't be parsed
+    pub fields: std::result::Result<Vec<String>, String>,
+}
+
+/// Split CSV text into records, accepting both CRLF and LF line endings
+///
+/// Quoted fields may contain delimiters, doubled quotes and line breaks. A
+/// malformed record is reported in place so the rest can still be used; blank
+/// lines are skipped.
+pub fn parse_records(text: &str) -> Vec<CsvRecord> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut error = None;
+        let mut in_quotes = false;
+        let mut quoted = false;
+
+        while let Some(c) = chars.next() {
+            if c == '\n' {
+                line += 1;
+            }
+            if in_quotes {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    '"' => in_quotes = false,
+                    c => field.push(c),
+                }
+                continue;
+            }
+
+            match c {
+                ',' => {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                }
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => break,
+                '"' if field.is_empty() && !quoted => {
+                    in_quotes = true;
+                    quoted = true;
+                }
+                c if quoted => {
+                    error.get_or_insert(format!("unexpected '{}' after a closing quote", c));
+                }
+                '"' => {
+                    error.get_or_insert("unexpected quote inside an unquoted field".to_string());
+                }
+                c => field.push(c),
+            }
+        }
+
+        if in_quotes {
+            error = Some("quoted field is never closed".to_string());
+        }
+        fields.push(field);
+
+        if error.is_none() && fields.len() == 1 && fields[0].is_empty() && !quoted {
+            continue;
+        }
+        records.push(CsvRecord {
+            line: start,
+            fields: match error {
+                Some(error) => Err(error),
+                None => Ok(fields),
+            },
+        });
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records_round_trips_and_reports_errors() {
+        let text = format!(
+            "{}\n{}{}bad\"field,x\r\n\"open",
+            "a,b c,",
+            format_record(&["say \"hi\"", "x,y", "two\nlines"]),
+            "\r\n"
+        );
+        let records = parse_records(&text);
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0], CsvRecord { line: 1, fields: Ok(vec!["a".into(), "b c".into(), "".into()]) });
+        assert_eq!(records[1].line, 2);
+        assert_eq!(
+            records[1].fields,
+            Ok(vec!["say \"hi\"".to_string(), "x,y".to_string(), "two\nlines".to_string()])
+        );
+        assert_eq!(records[2].line, 5);
+        assert!(records[2].fields.is_err());
+        assert_eq!(records[3].line, 6);
+        assert!(records[3].fields.is_err());
+    }
+
+    #[test]
+    fn test_format_record_quotes_when_needed() {
+        assert_eq!(format_record(&["a", "b c", ""]), "a,b c,\r\n");
+        assert_eq!(
+            format_record(&["say \"hi\"", "x,y", "two\nlines"]),
+            "\"say \"\"hi\"\"\",\"x,y\",\"two\nlines\"\r\n"
+        );
+    }
+}
@@ -2,6 +2,73 @@ use crate::{Error, Result};
 use rand::thread_rng;
 use rand::seq::SliceRandom;
 
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NUMBERS: &str = "0123456789";
+
+/// Characters that need quoting or escaping in POSIX shells
+pub const SHELL_UNSAFE_CHARS: &str = "`$\\\"'!&|;<>()*?[]{}~# ";
+
+/// ASCII punctuation outside the unreserved URL set (`-`, `.`, `_`, `~`)
+pub const URL_UNSAFE_CHARS: &str = "!\"#$%&'()*+,/:;<=>?@[\\]^`{|}";
+
+/// Characters that must be escaped inside JSON strings
+pub const JSON_UNSAFE_CHARS: &str = "\"\\";
+
+/// Characters easily mistaken for one another when read or typed
+pub const AMBIGUOUS_CHARS: &str = "Il1|O0o";
+
+/// Mask placeholders: `L` letter, `l` lowercase, `U` uppercase, `d` digit, `s` symbol
+///
+/// Any other character in a mask is copied into the password unchanged.
+pub const MASK_PLACEHOLDERS: &str = "LlUds";
+
+/// Contexts a generated password can be made safe for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SafeContext {
+    /// Usable unquoted in POSIX shells
+    #[value(name = "shell-safe")]
+    Shell,
+    /// Usable in URLs without percent-encoding
+    #[value(name = "url-safe")]
+    Url,
+    /// Usable in JSON strings without escaping
+    #[value(name = "json-safe")]
+    Json,
+}
+
+impl SafeContext {
+    /// Characters excluded for this context
+    pub fn excluded_chars(self) -> &'static str {
+        match self {
+            SafeContext::Shell => SHELL_UNSAFE_CHARS,
+            SafeContext::Url => URL_UNSAFE_CHARS,
+            SafeContext::Json => JSON_UNSAFE_CHARS,
+        }
+    }
+}
+
+/// Check that a custom symbol set is usable
+///
+/// Whitespace and control characters are rejected since they are easy to lose
+/// when a password is copied or typed, and duplicates would skew the odds.
+pub fn validate_symbol_set(symbols: &str) -> Result<()> {
+    let mut seen = Vec::new();
+    for c in symbols.chars() {
+        if c.is_whitespace() || c.is_control() {
+            return Err(Error::InvalidInput(format!(
+                "Symbol set contains whitespace or control character {:?}",
+                c
+            )));
+        }
+        if seen.contains(&c) {
+            return Err(Error::InvalidInput(format!("Symbol set contains '{}' more than once", c)));
+        }
+        seen.push(c);
+    }
+    Ok(())
+}
+
 /// Password generation configuration
 #[derive(Debug, Clone)]
 pub struct GeneratorConfig {
@@ -11,6 +78,26 @@ pub struct GeneratorConfig {
     pub include_numbers: bool,
     pub include_symbols: bool,
     pub symbol_set: String,
+    /// Characters never used, whichever sets are enabled
+    pub exclude_chars: String,
+    /// Leave out `AMBIGUOUS_CHARS` from every set
+    pub exclude_ambiguous: bool,
+    /// Minimum number of characters from each class
+    pub min_lowercase: u32,
+    pub min_uppercase: u32,
+    pub min_numbers: u32,
+    pub min_symbols: u32,
+    /// Draw only from these characters, ignoring the character classes and minimums
+    pub custom_charset: Option<String>,
+    /// Fixed structure to fill in, see `MASK_PLACEHOLDERS`; overrides `length`
+    pub mask: Option<String>,
+}
+
+impl GeneratorConfig {
+    /// Exclude the characters that are problematic in the given context
+    pub fn exclude_for(&mut self, context: SafeContext) {
+        self.exclude_chars.push_str(context.excluded_chars());
+    }
 }
 
 impl Default for GeneratorConfig {
@@ -22,6 +109,14 @@ impl Default for GeneratorConfig {
             include_numbers: true,
             include_symbols: true,
             symbol_set: "!@#$%^&*()-_=+[]{}|;:,.<>?".to_string(),
+            exclude_chars: String::new(),
+            exclude_ambiguous: false,
+            min_lowercase: 0,
+            min_uppercase: 0,
+            min_numbers: 0,
+            min_symbols: 0,
+            custom_charset: None,
+            mask: None,
         }
     }
 }
@@ -46,68 +141,188 @@ impl PasswordGenerator {
 
     /// Generate a password
     pub fn generate(&self) -> Result<String> {
+        if let Some(mask) = &self.config.mask {
+            return self.generate_masked(mask);
+        }
+
         if self.config.length == 0 {
             return Err(Error::PasswordGeneration("Password length cannot be zero".to_string()));
         }
 
-        let mut charset = String::new();
-        
-        if self.config.include_lowercase {
-            charset.push_str("abcdefghijklmnopqrstuvwxyz");
+        let sets = self.character_sets()?;
+        if sets.is_empty() {
+            return Err(Error::PasswordGeneration("No character sets selected".to_string()));
         }
-        
-        if self.config.include_uppercase {
-            charset.push_str("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+
+        let required: u32 = sets.iter().map(|(_, minimum)| minimum).sum();
+        if required > self.config.length {
+            return Err(Error::PasswordGeneration(format!(
+                "Minimum character counts add up to {}, more than the password length of {}",
+                required, self.config.length
+            )));
         }
-        
-        if self.config.include_numbers {
-            charset.push_str("0123456789");
+
+        let charset: Vec<char> = sets.iter().flat_map(|(set, _)| set).copied().collect();
+        let mut rng = thread_rng();
+
+        // Seed the required characters, at least one from each enabled set;
+        // a custom charset is a single set drawn from uniformly instead
+        let mut password = Vec::new();
+        if self.config.custom_charset.is_none() {
+            for (set, minimum) in &sets {
+                for _ in 0..(*minimum).max(1) {
+                    password.push(*set.choose(&mut rng).unwrap());
+                }
+            }
         }
-        
-        if self.config.include_symbols {
-            charset.push_str(&self.config.symbol_set);
+
+        // Fill the rest randomly
+        while password.len() < self.config.length as usize {
+            password.push(*charset.choose(&mut rng).unwrap());
         }
 
-        if charset.is_empty() {
-            return Err(Error::PasswordGeneration("No character sets selected".to_string()));
+        // Shuffle the password to avoid predictable patterns
+        password.shuffle(&mut rng);
+
+        Ok(password.into_iter().collect())
+    }
+
+    /// Fill in the placeholders of `mask`, keeping literal characters in place
+    fn generate_masked(&self, mask: &str) -> Result<String> {
+        if mask.is_empty() {
+            return Err(Error::PasswordGeneration("Mask is empty".to_string()));
         }
 
-        let charset_chars: Vec<char> = charset.chars().collect();
         let mut rng = thread_rng();
-        let mut password = String::new();
+        let mut password = String::with_capacity(mask.len());
+        for c in mask.chars() {
+            match self.mask_set(c)? {
+                Some(set) => password.push(*set.choose(&mut rng).unwrap()),
+                None => password.push(c),
+            }
+        }
+
+        Ok(password)
+    }
 
-        // Ensure at least one character from each enabled set
-        if self.config.include_lowercase {
-            let lowercase: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
-            password.push(*lowercase.choose(&mut rng).unwrap());
+    /// Characters a mask placeholder draws from, or `None` for a literal
+    fn mask_set(&self, placeholder: char) -> Result<Option<Vec<char>>> {
+        let (name, chars) = match placeholder {
+            'L' => ("letter", format!("{}{}", LOWERCASE, UPPERCASE)),
+            'l' => ("lowercase", LOWERCASE.to_string()),
+            'U' => ("uppercase", UPPERCASE.to_string()),
+            'd' => ("digit", NUMBERS.to_string()),
+            's' => {
+                validate_symbol_set(&self.config.symbol_set)?;
+                ("symbol", self.config.symbol_set.clone())
+            }
+            _ => return Ok(None),
+        };
+
+        let set: Vec<char> = chars.chars().filter(|c| !self.is_excluded(*c)).collect();
+        if set.is_empty() {
+            return Err(Error::PasswordGeneration(format!(
+                "No {} characters left after exclusions for mask placeholder '{}'",
+                name, placeholder
+            )));
         }
-        
-        if self.config.include_uppercase {
-            let uppercase: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
-            password.push(*uppercase.choose(&mut rng).unwrap());
+        Ok(Some(set))
+    }
+
+    /// Estimated entropy in bits of a password produced by this generator
+    ///
+    /// For a mask, each placeholder contributes the size of its own set and
+    /// literals contribute nothing.
+    pub fn estimated_entropy(&self, password: &str) -> Result<f64> {
+        match &self.config.mask {
+            Some(mask) => {
+                let mut bits = 0.0;
+                for c in mask.chars() {
+                    if let Some(set) = self.mask_set(c)? {
+                        bits += (set.len() as f64).log2();
+                    }
+                }
+                Ok(bits)
+            }
+            None => Ok(estimate_entropy(password, self.effective_charset()?.chars().count())),
         }
-        
-        if self.config.include_numbers {
-            let numbers: Vec<char> = "0123456789".chars().collect();
-            password.push(*numbers.choose(&mut rng).unwrap());
+    }
+
+    /// Every character a generated password may contain
+    pub fn effective_charset(&self) -> Result<String> {
+        Ok(self.character_sets()?.into_iter().flat_map(|(set, _)| set).collect())
+    }
+
+    /// Enabled character sets with excluded characters removed, with their minimum counts
+    fn character_sets(&self) -> Result<Vec<(Vec<char>, u32)>> {
+        if let Some(custom) = &self.config.custom_charset {
+            return Ok(vec![(self.custom_set(custom)?, 0)]);
         }
-        
+
         if self.config.include_symbols {
-            let symbols: Vec<char> = self.config.symbol_set.chars().collect();
-            password.push(*symbols.choose(&mut rng).unwrap());
+            validate_symbol_set(&self.config.symbol_set)?;
         }
 
-        // Fill the rest randomly
-        while password.len() < self.config.length as usize {
-            let random_char = charset_chars.choose(&mut rng).unwrap();
-            password.push(*random_char);
+        let classes = [
+            (self.config.include_lowercase, "lowercase", LOWERCASE, self.config.min_lowercase),
+            (self.config.include_uppercase, "uppercase", UPPERCASE, self.config.min_uppercase),
+            (self.config.include_numbers, "number", NUMBERS, self.config.min_numbers),
+            (self.config.include_symbols, "symbol", self.config.symbol_set.as_str(), self.config.min_symbols),
+        ];
+
+        let mut sets = Vec::new();
+        for (enabled, name, chars, minimum) in classes {
+            if !enabled {
+                if minimum > 0 {
+                    return Err(Error::PasswordGeneration(format!(
+                        "At least {} {} characters requested but {} characters are disabled",
+                        minimum, name, name
+                    )));
+                }
+                continue;
+            }
+
+            let set: Vec<char> = chars
+                .chars()
+                .filter(|c| !self.is_excluded(*c))
+                .collect();
+            if set.is_empty() {
+                return Err(Error::PasswordGeneration(format!(
+                    "No {} characters left after exclusions",
+                    name
+                )));
+            }
+            sets.push((set, minimum));
         }
 
-        // Shuffle the password to avoid predictable patterns
-        let mut password_chars: Vec<char> = password.chars().collect();
-        password_chars.shuffle(&mut rng);
-        
-        Ok(password_chars.into_iter().collect())
+        Ok(sets)
+    }
+
+    /// Distinct characters of a custom charset, minus exclusions
+    fn custom_set(&self, custom: &str) -> Result<Vec<char>> {
+        if custom.is_empty() {
+            return Err(Error::PasswordGeneration("Custom charset is empty".to_string()));
+        }
+
+        // Duplicates would skew the odds towards the repeated characters
+        let mut set = Vec::new();
+        for c in custom.chars().filter(|c| !self.is_excluded(*c)) {
+            if !set.contains(&c) {
+                set.push(c);
+            }
+        }
+        if set.len() < 2 {
+            return Err(Error::PasswordGeneration(format!(
+                "Custom charset needs at least 2 distinct characters, got {}",
+                set.len()
+            )));
+        }
+
+        Ok(set)
+    }
+
+    fn is_excluded(&self, c: char) -> bool {
+        self.config.exclude_chars.contains(c) || (self.config.exclude_ambiguous && AMBIGUOUS_CHARS.contains(c))
     }
 
     /// Generate multiple passwords
@@ -155,9 +370,11 @@ impl Default for PasswordGenerator {
 
 /// Generate a simple password with default settings
 pub fn generate_password(length: u32) -> Result<String> {
-    let mut config = GeneratorConfig::default();
-    config.length = length;
-    
+    let config = GeneratorConfig {
+        length,
+        ..Default::default()
+    };
+
     let generator = PasswordGenerator::with_config(config);
     generator.generate()
 }
@@ -171,12 +388,130 @@ pub fn generate_alphanumeric_password(length: u32) -> Result<String> {
         include_numbers: true,
         include_symbols: false,
         symbol_set: String::new(),
+        exclude_chars: String::new(),
+        ..Default::default()
     };
     
     let generator = PasswordGenerator::with_config(config);
     generator.generate()
 }
 
+/// Estimate the entropy in bits of a password drawn uniformly from a charset
+pub fn estimate_entropy(password: &str, charset_size: usize) -> f64 {
+    if charset_size == 0 {
+        return 0.0;
+    }
+    password.chars().count() as f64 * (charset_size as f64).log2()
+}
+
+/// Coarse strength rating for an entropy estimate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strength {
+    Weak,
+    Fair,
+    Strong,
+    VeryStrong,
+}
+
+impl Strength {
+    /// Rate an entropy estimate in bits
+    pub fn from_bits(bits: f64) -> Self {
+        if bits < 40.0 {
+            Strength::Weak
+        } else if bits < 60.0 {
+            Strength::Fair
+        } else if bits < 80.0 {
+            Strength::Strong
+        } else {
+            Strength::VeryStrong
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Strength::Weak => "Weak",
+            Strength::Fair => "Fair",
+            Strength::Strong => "Strong",
+            Strength::VeryStrong => "Very Strong",
+        }
+    }
+}
+
+/// Embedded diceware-style wordlist, one lowercase word per line
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+/// Words a passphrase is drawn from
+pub fn wordlist() -> Vec<&'static str> {
+    WORDLIST.lines().filter(|line| !line.is_empty()).collect()
+}
+
+/// Passphrase generation configuration
+#[derive(Debug, Clone)]
+pub struct PassphraseConfig {
+    pub words: u32,
+    pub separator: String,
+    /// Capitalize the first letter of each word
+    pub capitalize: bool,
+    /// Append a random digit to the passphrase
+    pub append_digit: bool,
+}
+
+impl Default for PassphraseConfig {
+    fn default() -> Self {
+        Self {
+            words: 5,
+            separator: "-".to_string(),
+            capitalize: false,
+            append_digit: false,
+        }
+    }
+}
+
+/// Diceware-style passphrase generator
+pub struct PassphraseGenerator {
+    config: PassphraseConfig,
+}
+
+impl PassphraseGenerator {
+    /// Create a passphrase generator with custom config
+    pub fn with_config(config: PassphraseConfig) -> Self {
+        Self { config }
+    }
+
+    /// Generate a passphrase
+    pub fn generate(&self) -> Result<String> {
+        if self.config.words == 0 {
+            return Err(Error::PasswordGeneration("Passphrase word count cannot be zero".to_string()));
+        }
+
+        let words = wordlist();
+        let mut rng = thread_rng();
+
+        let chosen: Vec<String> = (0..self.config.words)
+            .map(|_| {
+                let word = *words.choose(&mut rng).unwrap();
+                if self.config.capitalize {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                        .unwrap_or_default()
+                } else {
+                    word.to_string()
+                }
+            })
+            .collect();
+
+        let mut passphrase = chosen.join(&self.config.separator);
+        if self.config.append_digit {
+            let digits: Vec<char> = NUMBERS.chars().collect();
+            passphrase.push(*digits.choose(&mut rng).unwrap());
+        }
+
+        Ok(passphrase)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,9 +525,11 @@ mod tests {
 
     #[test]
     fn test_custom_length() {
-        let mut config = GeneratorConfig::default();
-        config.length = 32;
-        
+        let config = GeneratorConfig {
+            length: 32,
+            ..Default::default()
+        };
+
         let generator = PasswordGenerator::with_config(config);
         let password = generator.generate().unwrap();
         assert_eq!(password.len(), 32);
@@ -204,4 +541,245 @@ mod tests {
         assert_eq!(password.len(), 20);
         assert!(password.chars().all(|c| c.is_alphanumeric()));
     }
+
+    #[test]
+    fn test_url_safe_preset() {
+        let mut config = GeneratorConfig {
+            length: 64,
+            ..Default::default()
+        };
+        config.exclude_for(SafeContext::Url);
+
+        let generator = PasswordGenerator::with_config(config);
+        for _ in 0..20 {
+            let password = generator.generate().unwrap();
+            assert!(password
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_symbol_set_rejected() {
+        assert!(validate_symbol_set("!@#").is_ok());
+        assert!(matches!(validate_symbol_set("! @"), Err(Error::InvalidInput(_))));
+        assert!(matches!(validate_symbol_set("!\t"), Err(Error::InvalidInput(_))));
+        assert!(matches!(validate_symbol_set("!@!"), Err(Error::InvalidInput(_))));
+
+        let config = GeneratorConfig {
+            symbol_set: "$$".to_string(),
+            ..Default::default()
+        };
+        assert!(PasswordGenerator::with_config(config).generate().is_err());
+    }
+
+    #[test]
+    fn test_preview_reflects_disabled_classes() {
+        let config = GeneratorConfig {
+            include_uppercase: false,
+            include_numbers: false,
+            symbol_set: "!?".to_string(),
+            exclude_chars: "xyz".to_string(),
+            ..Default::default()
+        };
+
+        let charset = PasswordGenerator::with_config(config).effective_charset().unwrap();
+        assert_eq!(charset, "abcdefghijklmnopqrstuvw!?");
+    }
+
+    #[test]
+    fn test_exclusions_emptying_a_set() {
+        let config = GeneratorConfig {
+            include_symbols: true,
+            symbol_set: "\"\\".to_string(),
+            exclude_chars: JSON_UNSAFE_CHARS.to_string(),
+            ..Default::default()
+        };
+
+        let result = PasswordGenerator::with_config(config).generate();
+        assert!(matches!(result, Err(Error::PasswordGeneration(_))));
+    }
+
+    #[test]
+    fn test_passphrase_uses_wordlist_and_separator() {
+        let words = wordlist();
+        let config = PassphraseConfig {
+            words: 6,
+            separator: "_".to_string(),
+            ..Default::default()
+        };
+
+        let passphrase = PassphraseGenerator::with_config(config).generate().unwrap();
+        let parts: Vec<&str> = passphrase.split('_').collect();
+        assert_eq!(parts.len(), 6);
+        assert!(parts.iter().all(|part| words.contains(part)));
+    }
+
+    #[test]
+    fn test_passphrase_capitalize_and_digit() {
+        let config = PassphraseConfig {
+            words: 3,
+            capitalize: true,
+            append_digit: true,
+            ..Default::default()
+        };
+
+        let passphrase = PassphraseGenerator::with_config(config).generate().unwrap();
+        assert!(passphrase.ends_with(|c: char| c.is_ascii_digit()));
+        assert!(passphrase.split('-').all(|word| word.starts_with(|c: char| c.is_ascii_uppercase())));
+    }
+
+    #[test]
+    fn test_passphrase_zero_words_rejected() {
+        let config = PassphraseConfig {
+            words: 0,
+            ..Default::default()
+        };
+
+        let result = PassphraseGenerator::with_config(config).generate();
+        assert!(matches!(result, Err(Error::PasswordGeneration(_))));
+    }
+
+    #[test]
+    fn test_estimate_entropy_uses_enabled_sets() {
+        assert_eq!(estimate_entropy("abcd", 0), 0.0);
+        assert_eq!(estimate_entropy("abcdefgh", 16), 32.0);
+
+        let config = GeneratorConfig {
+            length: 20,
+            include_uppercase: false,
+            include_numbers: false,
+            symbol_set: "!?".to_string(),
+            ..Default::default()
+        };
+        let generator = PasswordGenerator::with_config(config);
+        let charset_size = generator.effective_charset().unwrap().chars().count();
+        assert_eq!(charset_size, 28);
+
+        let bits = estimate_entropy(&generator.generate().unwrap(), charset_size);
+        assert!((bits - 20.0 * 28f64.log2()).abs() < 1e-9);
+        assert_eq!(Strength::from_bits(bits), Strength::VeryStrong);
+        assert_eq!(Strength::from_bits(39.9).label(), "Weak");
+        assert_eq!(Strength::from_bits(128.0).label(), "Very Strong");
+    }
+
+    #[test]
+    fn test_no_ambiguous_characters() {
+        let config = GeneratorConfig {
+            length: 64,
+            exclude_ambiguous: true,
+            ..Default::default()
+        };
+        let generator = PasswordGenerator::with_config(config);
+
+        assert!(!generator.effective_charset().unwrap().contains(|c| AMBIGUOUS_CHARS.contains(c)));
+        for _ in 0..20 {
+            let password = generator.generate().unwrap();
+            assert!(!password.contains(|c| AMBIGUOUS_CHARS.contains(c)));
+            assert!(password.chars().any(|c| c.is_ascii_digit()));
+        }
+
+        let config = GeneratorConfig {
+            symbol_set: "|".to_string(),
+            exclude_ambiguous: true,
+            ..Default::default()
+        };
+        let result = PasswordGenerator::with_config(config).generate();
+        assert!(matches!(result, Err(Error::PasswordGeneration(msg)) if msg.contains("symbol")));
+    }
+
+    #[test]
+    fn test_minimum_class_counts() {
+        let config = GeneratorConfig {
+            length: 8,
+            min_numbers: 3,
+            min_symbols: 2,
+            ..Default::default()
+        };
+        let generator = PasswordGenerator::with_config(config);
+
+        for _ in 0..20 {
+            let password = generator.generate().unwrap();
+            assert_eq!(password.len(), 8);
+            assert!(password.chars().filter(|c| c.is_ascii_digit()).count() >= 3);
+            assert!(password.chars().filter(|c| !c.is_ascii_alphanumeric()).count() >= 2);
+        }
+
+        let config = GeneratorConfig {
+            length: 4,
+            min_numbers: 3,
+            min_symbols: 2,
+            ..Default::default()
+        };
+        let result = PasswordGenerator::with_config(config).generate();
+        assert!(matches!(result, Err(Error::PasswordGeneration(_))));
+
+        let config = GeneratorConfig {
+            include_symbols: false,
+            min_symbols: 1,
+            ..Default::default()
+        };
+        let result = PasswordGenerator::with_config(config).generate();
+        assert!(matches!(result, Err(Error::PasswordGeneration(_))));
+    }
+
+    #[test]
+    fn test_custom_charset() {
+        let config = GeneratorConfig {
+            length: 32,
+            include_symbols: false,
+            custom_charset: Some("abcabc!".to_string()),
+            ..Default::default()
+        };
+        let generator = PasswordGenerator::with_config(config);
+        assert_eq!(generator.effective_charset().unwrap(), "abc!");
+
+        for _ in 0..20 {
+            let password = generator.generate().unwrap();
+            assert_eq!(password.len(), 32);
+            assert!(password.chars().all(|c| "abc!".contains(c)));
+        }
+
+        for charset in ["", "aaaa"] {
+            let config = GeneratorConfig {
+                custom_charset: Some(charset.to_string()),
+                ..Default::default()
+            };
+            let result = PasswordGenerator::with_config(config).generate();
+            assert!(matches!(result, Err(Error::PasswordGeneration(_))));
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        let config = GeneratorConfig {
+            length: 40,
+            symbol_set: "!?".to_string(),
+            mask: Some("UUdd-ssll".to_string()),
+            ..Default::default()
+        };
+        let generator = PasswordGenerator::with_config(config);
+
+        for _ in 0..20 {
+            let password: Vec<char> = generator.generate().unwrap().chars().collect();
+            assert_eq!(password.len(), 9);
+            assert!(password[..2].iter().all(|c| c.is_ascii_uppercase()));
+            assert!(password[2..4].iter().all(|c| c.is_ascii_digit()));
+            assert_eq!(password[4], '-');
+            assert!(password[5..7].iter().all(|c| "!?".contains(*c)));
+            assert!(password[7..].iter().all(|c| c.is_ascii_lowercase()));
+        }
+
+        let bits = generator.estimated_entropy("").unwrap();
+        let expected = 2.0 * 26f64.log2() + 2.0 * 10f64.log2() + 2.0 + 2.0 * 26f64.log2();
+        assert!((bits - expected).abs() < 1e-9);
+
+        let config = GeneratorConfig {
+            exclude_chars: NUMBERS.to_string(),
+            mask: Some("Ld".to_string()),
+            ..Default::default()
+        };
+        let result = PasswordGenerator::with_config(config).generate();
+        assert!(matches!(result, Err(Error::PasswordGeneration(msg)) if msg.contains("digit")));
+    }
 }
@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Source of the current time, injectable so idle timeouts can be tested
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when advanced; clones share the same time
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Tracks time since the last activity against a timeout
+pub struct IdleTimer {
+    clock: Arc<dyn Clock>,
+    timeout: Duration,
+    last_activity: Instant,
+}
+
+impl IdleTimer {
+    /// Start a timer; a zero timeout never expires
+    pub fn new(timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            last_activity: clock.now(),
+            clock,
+            timeout,
+        }
+    }
+
+    /// Record activity, restarting the idle period
+    pub fn touch(&mut self) {
+        self.last_activity = self.clock.now();
+    }
+
+    /// Time since the last activity
+    pub fn idle_for(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.last_activity)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Whether the idle period has exceeded the timeout
+    pub fn is_expired(&self) -> bool {
+        !self.timeout.is_zero() && self.idle_for() > self.timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_timer_with_manual_clock() {
+        let clock = ManualClock::new();
+        let mut timer = IdleTimer::new(Duration::from_secs(60), Arc::new(clock.clone()));
+
+        clock.advance(Duration::from_secs(45));
+        assert_eq!(timer.idle_for(), Duration::from_secs(45));
+        timer.touch();
+        clock.advance(Duration::from_secs(45));
+        assert!(!timer.is_expired());
+        clock.advance(Duration::from_secs(16));
+        assert!(timer.is_expired());
+
+        let never = IdleTimer::new(Duration::ZERO, Arc::new(clock.clone()));
+        clock.advance(Duration::from_secs(86_400));
+        assert!(!never.is_expired());
+    }
+}
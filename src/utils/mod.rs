@@ -1,5 +1,14 @@
+pub mod audit;
+pub mod browser;
 pub mod generator;
+pub mod idle;
+pub mod output;
 pub mod clipboard;
+pub mod csv;
+pub mod policy;
+pub mod pwned;
+pub mod template;
+pub mod time;
 
 pub use generator::*;
 pub use clipboard::*;
@@ -0,0 +1,44 @@
+use crate::Result;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// Write `data` to `path`, readable and writable only by the current user
+///
+/// An existing file is truncated. On Unix the file is created with mode 0600;
+/// an existing file keeps its permissions.
+pub fn write_private_file(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_private_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("generated.txt");
+
+        write_private_file(&path, b"Xk9#mP2$vL7@qR4!\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"Xk9#mP2$vL7@qR4!\n");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::utils::audit::password_entropy;
+
+type ClassMember = fn(&char) -> bool;
+
+/// Requirements a password must satisfy
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    /// Minimum estimated entropy in bits, see `audit::password_entropy`
+    pub min_entropy: f64,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 12,
+            min_lowercase: 0,
+            min_uppercase: 0,
+            min_digits: 0,
+            min_symbols: 0,
+            min_entropy: 0.0,
+        }
+    }
+}
+
+/// Outcome of checking a password against a policy
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolicyResult {
+    /// One human-readable reason per unmet requirement
+    pub failures: Vec<String>,
+}
+
+impl PolicyResult {
+    /// Whether every requirement was met
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Check a password against a policy, collecting every unmet requirement
+pub fn check(password: &str, policy: &PasswordPolicy) -> PolicyResult {
+    let mut failures = Vec::new();
+
+    let length = password.chars().count();
+    if length < policy.min_length {
+        failures.push(format!("too short: {} characters, need at least {}", length, policy.min_length));
+    }
+
+    let classes: [(&str, usize, ClassMember); 4] = [
+        ("lowercase letters", policy.min_lowercase, char::is_ascii_lowercase),
+        ("uppercase letters", policy.min_uppercase, char::is_ascii_uppercase),
+        ("digits", policy.min_digits, char::is_ascii_digit),
+        ("symbols", policy.min_symbols, |c| !c.is_ascii_alphanumeric()),
+    ];
+    for (name, min, is_member) in classes {
+        let count = password.chars().filter(is_member).count();
+        if count < min {
+            failures.push(format!("too few {}: {}, need at least {}", name, count, min));
+        }
+    }
+
+    let entropy = password_entropy(password);
+    if entropy < policy.min_entropy {
+        failures.push(format!(
+            "entropy too low: {:.1} bits, need at least {:.1}",
+            entropy, policy.min_entropy
+        ));
+    }
+
+    PolicyResult { failures }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_check() {
+        let policy = PasswordPolicy {
+            min_length: 12,
+            min_lowercase: 1,
+            min_uppercase: 1,
+            min_digits: 1,
+            min_symbols: 2,
+            min_entropy: 60.0,
+        };
+
+        let result = check("Correct1horse!battery", &policy);
+        assert!(!result.passed());
+        assert_eq!(result.failures, vec!["too few symbols: 1, need at least 2".to_string()]);
+
+        assert!(check("Xk9#mP2$vL7@qR4!", &policy).passed());
+    }
+}
@@ -0,0 +1,72 @@
+//! Have I Been Pwned breach check using the k-anonymity range API
+//!
+//! Only the first five hex characters of a password's SHA-1 hash leave the
+//! machine; the match against the returned suffixes happens locally.
+
+/// Endpoint taking the five-character hash prefix
+pub const RANGE_API_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+/// Seconds to wait for the range API before giving up on a request
+pub const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// Split a password's uppercase hex SHA-1 hash into the prefix that is sent
+/// and the suffix that is looked up in the response
+pub fn hash_prefix_suffix(password: &str) -> (String, String) {
+    let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes());
+    let hex: String = digest.as_ref().iter().map(|byte| format!("{:02X}", byte)).collect();
+    let (prefix, suffix) = hex.split_at(5);
+    (prefix.to_string(), suffix.to_string())
+}
+
+/// How often the suffix appears in a range response, 0 if not at all
+///
+/// Each response line reads `SUFFIX:COUNT`. Padding entries with a count of 0
+/// are reported as not found.
+pub fn breach_count(response: &str, suffix: &str) -> u64 {
+    response
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Fetch the suffixes for a hash prefix from the range API
+///
+/// Rate limiting and timeouts come back as `Error::Network` so callers can
+/// report them and move on.
+#[cfg(feature = "network")]
+pub fn fetch_range(prefix: &str) -> crate::Result<String> {
+    use std::time::Duration;
+
+    let response = ureq::get(&format!("{}{}", RANGE_API_URL, prefix))
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .set("User-Agent", concat!("passman-cli/", env!("CARGO_PKG_VERSION")))
+        .set("Add-Padding", "true")
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(429, _) => crate::Error::Network("rate limited by the HIBP API".to_string()),
+            ureq::Error::Status(code, _) => crate::Error::Network(format!("HIBP API returned HTTP {}", code)),
+            ureq::Error::Transport(e) => crate::Error::Network(e.to_string()),
+        })?;
+
+    response.into_string().map_err(crate::Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_split_and_lookup() {
+        // SHA-1("password") = 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        let (prefix, suffix) = hash_prefix_suffix("password");
+        assert_eq!(prefix, "5BAA6");
+        assert_eq!(suffix, "1E4C9B93F3F0682250B6CF8331B7EE68FD8");
+
+        let response = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n1e4c9b93f3f0682250b6cf8331b7ee68fd8:3861493\r\nFFFFF00000000000000000000000000000F:0\r\n";
+        assert_eq!(breach_count(response, &suffix), 3861493);
+        assert_eq!(breach_count(response, "FFFFF00000000000000000000000000000F"), 0);
+        assert_eq!(breach_count(response, "0000000000000000000000000000000000A"), 0);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{Error, Result};
+
+/// Render a template by substituting `{name}` placeholders with field values
+///
+/// Use `{{` and `}}` for literal braces. Unknown placeholders are rejected so
+/// that typos in a configured template surface as errors.
+pub fn render(template: &str, fields: &[(&str, &str)]) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => {
+                            return Err(Error::InvalidInput(format!(
+                                "Unterminated placeholder in template: {}",
+                                template
+                            )))
+                        }
+                    }
+                }
+
+                let value = fields
+                    .iter()
+                    .find(|(field, _)| *field == name)
+                    .map(|(_, value)| *value)
+                    .ok_or_else(|| {
+                        Error::InvalidInput(format!("Unknown template field: {}", name))
+                    })?;
+                output.push_str(value);
+            }
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_substitutes_fields() {
+        let rendered = render("{username}\t{password} {{x}}", &[
+            ("username", "octocat"),
+            ("password", "hunter2"),
+        ])
+        .unwrap();
+        assert_eq!(rendered, "octocat\thunter2 {x}");
+    }
+
+    #[test]
+    fn test_render_rejects_unknown_field() {
+        assert!(render("{user}", &[("username", "octocat")]).is_err());
+        assert!(render("{username", &[("username", "octocat")]).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use std::fmt::Display;
+
+/// Format used when showing timestamps to the user
+const DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M:%S %:z";
+
+/// Format a stored UTC timestamp for display, optionally in the system's local timezone
+pub fn format_timestamp(timestamp: &DateTime<Utc>, local_time: bool) -> String {
+    if local_time {
+        format_in_timezone(timestamp, &Local)
+    } else {
+        format_in_timezone(timestamp, &Utc)
+    }
+}
+
+/// Format a stored UTC timestamp in the given timezone
+pub fn format_in_timezone<Tz>(timestamp: &DateTime<Utc>, timezone: &Tz) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    timestamp
+        .with_timezone(timezone)
+        .format(DISPLAY_FORMAT)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn test_render_in_offset() {
+        let stored = "2024-03-01T22:30:00+00:00";
+        let timestamp = DateTime::parse_from_rfc3339(stored)
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let offset = FixedOffset::east_opt(7 * 3600).unwrap();
+        assert_eq!(
+            format_in_timezone(&timestamp, &offset),
+            "2024-03-02 05:30:00 +07:00"
+        );
+        assert_eq!(format_timestamp(&timestamp, false), "2024-03-01 22:30:00 +00:00");
+
+        // Display conversion never alters the canonical UTC value
+        assert_eq!(timestamp.to_rfc3339(), stored);
+    }
+}
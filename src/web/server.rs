@@ -1,35 +1,105 @@
-use crate::{Result, utils::PasswordGenerator};
+use crate::crypto::{EncryptionManager, LockedKey};
+use crate::database::{models, PasswordRepository, SecureString, SharedRepository};
+use crate::{Error, Result, utils::PasswordGenerator};
 use axum::{
-    extract::Query,
-    http::StatusCode,
-    response::{Html, Json},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
 
 /// Web server for PassMan-CLI
 pub struct WebServer {
+    bind: IpAddr,
+    /// Whether `bind` may be reachable from other machines
+    allow_remote: bool,
     port: u16,
+    state: AppState,
+    /// Random per-launch secret the API requires as a bearer token
+    token: String,
+}
+
+/// The unlocked vault, shared by every request
+#[derive(Clone)]
+struct AppState {
+    repo: SharedRepository,
+    key: Arc<LockedKey>,
 }
 
 impl WebServer {
-    pub fn new(port: u16) -> Self {
-        Self { port }
+    /// Serve an unlocked vault; the key is dropped when the server stops
+    pub fn new(port: u16, repo: PasswordRepository, key: LockedKey) -> Result<Self> {
+        let token = URL_SAFE_NO_PAD.encode(EncryptionManager::new().generate_random(32)?);
+        Ok(Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            allow_remote: false,
+            port,
+            state: AppState {
+                repo: SharedRepository::new(repo),
+                key: Arc::new(key),
+            },
+            token,
+        })
+    }
+
+    /// Listen on `bind` instead of the loopback address
+    pub fn with_bind(mut self, bind: IpAddr) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    /// Accept a non-loopback `bind`; see `check_bind`
+    pub fn with_allow_remote(mut self, allow_remote: bool) -> Self {
+        self.allow_remote = allow_remote;
+        self
+    }
+
+    /// Refuse addresses reachable from other machines unless `allow_remote` is set
+    ///
+    /// The token is still required, but without TLS it and the vault data
+    /// cross the network in plain text. A wildcard address is never allowed:
+    /// the `Host` check needs the address clients actually connect to.
+    pub fn check_bind(bind: IpAddr, allow_remote: bool) -> Result<()> {
+        if bind.is_loopback() {
+            return Ok(());
+        }
+        if bind.is_unspecified() {
+            return Err(Error::InvalidInput(format!(
+                "refusing to serve the web UI on {}: give the address clients will connect to",
+                bind
+            )));
+        }
+        if !allow_remote {
+            return Err(Error::InvalidInput(format!(
+                "refusing to serve the web UI on {}: it has no TLS; pass --allow-remote to do so anyway",
+                bind
+            )));
+        }
+        Ok(())
     }
 
     /// Start the web server
     pub async fn serve(self) -> Result<()> {
-        let app = self.create_app();
-
-        let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", self.port))
+        Self::check_bind(self.bind, self.allow_remote)?;
+        if !self.bind.is_loopback() {
+            eprintln!("⚠️  Serving on {} without TLS: anyone on the network path can read the token and your passwords", self.bind);
+        }
+        let listener = tokio::net::TcpListener::bind(SocketAddr::new(self.bind, self.port))
             .await
-            .map_err(|e| crate::Error::Io(e))?;
+            .map_err(crate::Error::Io)?;
+        // The real port when 0 was asked for, so the Host check matches it
+        let address = listener.local_addr().map_err(crate::Error::Io)?;
+        let app = self.create_app(address);
 
-        println!("🚀 PassMan-CLI Web UI running at http://127.0.0.1:{}", self.port);
-        println!("📝 Open your browser and go to the URL above to use the web interface");
+        // The token goes in the fragment, which the browser never sends
+        println!("🚀 PassMan-CLI Web UI running at http://{}/#token={}", address, self.token);
+        println!("📝 Open the URL above, token included; it changes every time the server starts");
 
         axum::serve(listener, app)
             .await
@@ -38,13 +108,119 @@ impl WebServer {
         Ok(())
     }
 
-    fn create_app(&self) -> Router {
+    /// Routes for the server listening on `address`
+    ///
+    /// No CORS layer is added, so browsers only let the UI's own page read
+    /// the responses.
+    fn create_app(&self, address: SocketAddr) -> Router {
+        let guard = Arc::new(Guard::new(address, self.token.clone()));
         Router::new()
             .route("/", get(home_handler))
             .route("/api/generate", post(generate_password_handler))
             .route("/api/passwords", get(list_passwords_handler))
             .route("/api/passwords", post(add_password_handler))
-            .layer(CorsLayer::permissive())
+            .route("/api/passwords/:id/password", get(reveal_password_handler))
+            .layer(middleware::from_fn_with_state(guard, guard_middleware))
+            .with_state(self.state.clone())
+    }
+}
+
+/// What a request must carry to reach the vault
+#[derive(Debug)]
+struct Guard {
+    /// `Host` values naming the listening address, e.g. `127.0.0.1:8080`
+    hosts: Vec<String>,
+    token: String,
+}
+
+impl Guard {
+    fn new(address: SocketAddr, token: String) -> Self {
+        Self {
+            hosts: vec![address.to_string(), format!("localhost:{}", address.port())],
+            token,
+        }
+    }
+
+    /// Reject requests from other websites
+    ///
+    /// A `Host` other than the listening address means a DNS rebinding
+    /// attempt; an `Origin` other than the UI's own is a cross-site request.
+    /// The API also needs the launch token as `Authorization: Bearer`.
+    fn check(&self, path: &str, headers: &HeaderMap) -> std::result::Result<(), ApiError> {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if !header(header::HOST).is_some_and(|host| self.hosts.iter().any(|h| h == host)) {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, "Unexpected Host header"));
+        }
+        if let Some(origin) = header(header::ORIGIN) {
+            let same_origin = origin
+                .strip_prefix("http://")
+                .is_some_and(|host| self.hosts.iter().any(|h| h == host));
+            if !same_origin {
+                return Err(ApiError::new(StatusCode::FORBIDDEN, "Cross-origin requests are not allowed"));
+            }
+        }
+
+        if path.starts_with("/api/") {
+            let token = header(header::AUTHORIZATION).and_then(|value| value.strip_prefix("Bearer "));
+            if !token.is_some_and(|token| tokens_match(token, &self.token)) {
+                return Err(ApiError::new(StatusCode::UNAUTHORIZED, "Missing or wrong access token"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare without stopping at the first differing byte
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn guard_middleware(State(guard): State<Arc<Guard>>, request: Request, next: Next) -> Response {
+    match guard.check(request.uri().path(), request.headers()) {
+        Ok(()) => next.run(request).await,
+        Err(err) => err.into_response(),
+    }
+}
+
+/// An error response: the status code plus `{"error": message}`
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let status = match err {
+            Error::InvalidInput(_) | Error::Serialization(_) => StatusCode::BAD_REQUEST,
+            Error::EntryNotFound(_) => StatusCode::NOT_FOUND,
+            Error::Authentication(_) => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        // Database and crypto failures stay in the server log
+        if status == StatusCode::INTERNAL_SERVER_ERROR {
+            log::error!("Web request failed: {}", err);
+            return Self::new(status, "Internal server error");
+        }
+        Self::new(status, err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
     }
 }
 
@@ -63,14 +239,35 @@ struct GeneratePasswordResponse {
     length: usize,
 }
 
-#[derive(Serialize)]
-struct PasswordEntry {
+/// Password entry as exposed by the web API (never includes the password)
+#[derive(Debug, Serialize)]
+struct PasswordEntryDto {
     id: String,
     title: String,
     username: String,
     url: Option<String>,
     notes: Option<String>,
     created_at: String,
+    updated_at: String,
+}
+
+impl From<models::PasswordEntry> for PasswordEntryDto {
+    fn from(entry: models::PasswordEntry) -> Self {
+        Self {
+            id: entry.id.to_string(),
+            title: entry.title,
+            username: entry.username,
+            url: entry.url,
+            notes: entry.notes,
+            created_at: entry.created_at.to_rfc3339(),
+            updated_at: entry.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RevealPasswordResponse {
+    password: String,
 }
 
 #[derive(Deserialize)]
@@ -120,43 +317,226 @@ async fn generate_password_handler(
 }
 
 /// List passwords API endpoint
-async fn list_passwords_handler() -> Json<Vec<PasswordEntry>> {
-    // Mock data for now
-    let passwords = vec![
-        PasswordEntry {
-            id: "1".to_string(),
-            title: "GitHub".to_string(),
-            username: "user@example.com".to_string(),
-            url: Some("https://github.com".to_string()),
-            notes: Some("Work account".to_string()),
-            created_at: "2024-01-01T00:00:00Z".to_string(),
-        },
-        PasswordEntry {
-            id: "2".to_string(),
-            title: "Gmail".to_string(),
-            username: "personal@gmail.com".to_string(),
-            url: Some("https://gmail.com".to_string()),
-            notes: None,
-            created_at: "2024-01-02T00:00:00Z".to_string(),
-        },
-    ];
-    
-    Json(passwords)
+async fn list_passwords_handler(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<Vec<PasswordEntryDto>>, ApiError> {
+    let entries = state.repo.run(|repo| repo.list_entries()).await?;
+    Ok(Json(entries.into_iter().map(PasswordEntryDto::from).collect()))
+}
+
+/// Decrypt one entry's password, for the UI's copy button
+async fn reveal_password_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<([(header::HeaderName, &'static str); 1], Json<RevealPasswordResponse>), ApiError> {
+    let id = uuid::Uuid::parse_str(&id)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid entry ID: {}", id)))?;
+    let key = Arc::clone(&state.key);
+    let entry = state.repo.run(move |repo| repo.get_entry_decrypted_by_id(&id, &key)).await?;
+
+    let password = entry.password.as_str().to_string();
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(RevealPasswordResponse { password })))
 }
 
 /// Add password API endpoint
 async fn add_password_handler(
+    State(state): State<AppState>,
     Json(req): Json<AddPasswordRequest>,
-) -> std::result::Result<Json<PasswordEntry>, StatusCode> {
-    // Mock implementation - in real app, this would save to database
-    let entry = PasswordEntry {
-        id: "new".to_string(),
-        title: req.title,
-        username: req.username,
-        url: req.url,
-        notes: req.notes,
-        created_at: chrono::Utc::now().to_rfc3339(),
-    };
-    
-    Ok(Json(entry))
+) -> std::result::Result<(StatusCode, Json<PasswordEntryDto>), ApiError> {
+    let password = SecureString::new(req.password);
+    let title = req.title.trim().to_string();
+    if title.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "Title must not be empty"));
+    }
+
+    let entry = models::PasswordEntry::new(title, req.username, SecureString::default(), req.url, req.notes);
+    let key = Arc::clone(&state.key);
+    let entry = state
+        .repo
+        .run(move |repo| {
+            if repo.title_exists(&entry.title)? {
+                return Ok(Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    format!("Entry '{}' already exists", entry.title),
+                )));
+            }
+            let encrypted = repo.encryption_manager()?.encrypt(&key, password.as_str().as_bytes())?;
+            repo.add_entry(&entry, &encrypted)?;
+            Ok(Ok(entry))
+        })
+        .await??;
+
+    Ok((StatusCode::CREATED, Json(entry.into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::PasswordManager;
+    use axum::http::HeaderValue;
+
+    fn state() -> AppState {
+        let repo = PasswordRepository::new(":memory:").unwrap();
+        let password_manager = PasswordManager::new();
+        let (hash, _) = password_manager.hash_password("master").unwrap();
+        let salt = password_manager.generate_salt().unwrap();
+        repo.initialize_vault(salt, hash.into_bytes()).unwrap();
+
+        let key = EncryptionManager::new().generate_key().unwrap();
+        WebServer::new(0, repo, LockedKey::new(key, false)).unwrap().state
+    }
+
+    fn add_request(title: &str) -> AddPasswordRequest {
+        AddPasswordRequest {
+            title: title.to_string(),
+            username: "octocat".to_string(),
+            password: "hunter2".to_string(),
+            url: Some("https://github.com".to_string()),
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn test_remote_binds_need_allow_remote() {
+        for allow_remote in [false, true] {
+            assert!(WebServer::check_bind("127.0.0.1".parse().unwrap(), allow_remote).is_ok());
+            assert!(WebServer::check_bind("::1".parse().unwrap(), allow_remote).is_ok());
+        }
+
+        for address in ["192.168.1.20", "fd00::20"] {
+            let err = WebServer::check_bind(address.parse().unwrap(), false).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput(_)), "{}", address);
+            assert!(WebServer::check_bind(address.parse().unwrap(), true).is_ok(), "{}", address);
+        }
+
+        for address in ["0.0.0.0", "::"] {
+            let err = WebServer::check_bind(address.parse().unwrap(), true).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput(_)), "{}", address);
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in pairs {
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_guard_only_lets_the_ui_reach_the_api() {
+        let guard = Guard::new("127.0.0.1:8080".parse().unwrap(), "s3cret".to_string());
+        let status = |path, pairs: &[(&'static str, &str)]| {
+            guard.check(path, &headers(pairs)).err().map(|err| err.status)
+        };
+        let host = ("host", "127.0.0.1:8080");
+        let auth = ("authorization", "Bearer s3cret");
+
+        assert_eq!(status("/", &[host]), None);
+        assert_eq!(status("/", &[("host", "localhost:8080")]), None);
+        assert_eq!(status("/api/passwords", &[host, auth]), None);
+        assert_eq!(
+            status("/api/passwords", &[host, auth, ("origin", "http://127.0.0.1:8080")]),
+            None
+        );
+
+        // DNS rebinding: right address, someone else's name
+        assert_eq!(status("/api/passwords", &[("host", "evil.example:8080"), auth]), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status("/", &[]), Some(StatusCode::FORBIDDEN));
+        assert_eq!(
+            status("/api/passwords", &[host, auth, ("origin", "https://evil.example")]),
+            Some(StatusCode::FORBIDDEN)
+        );
+
+        assert_eq!(status("/api/passwords", &[host]), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(status("/api/generate", &[host, ("authorization", "Bearer s3cre")]), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(status("/api/generate", &[host, ("authorization", "s3cret")]), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn test_each_server_gets_its_own_token() {
+        let first = WebServer::new(0, PasswordRepository::new(":memory:").unwrap(), LockedKey::new(vec![0; 32], false)).unwrap();
+        let second = WebServer::new(0, PasswordRepository::new(":memory:").unwrap(), LockedKey::new(vec![0; 32], false)).unwrap();
+        assert_eq!(first.token.len(), 43);
+        assert_ne!(first.token, second.token);
+    }
+
+    #[tokio::test]
+    async fn test_add_then_list_uses_the_vault() {
+        let state = state();
+
+        let (status, Json(added)) = add_password_handler(State(state.clone()), Json(add_request("GitHub"))).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let Json(listed) = list_passwords_handler(State(state.clone())).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, added.id);
+        assert_eq!(listed[0].title, "GitHub");
+
+        let key = Arc::clone(&state.key);
+        let password = state
+            .repo
+            .run(move |repo| repo.get_entry_decrypted("GitHub", &key))
+            .await
+            .unwrap()
+            .password;
+        assert_eq!(password.as_str(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn test_reveal_returns_the_stored_password() {
+        let state = state();
+        let (_, Json(added)) = add_password_handler(State(state.clone()), Json(add_request("GitHub"))).await.unwrap();
+
+        let (headers, Json(revealed)) = reveal_password_handler(State(state.clone()), Path(added.id)).await.unwrap();
+        assert_eq!(revealed.password, "hunter2");
+        assert_eq!(headers[0].1, "no-store");
+
+        let err = reveal_password_handler(State(state.clone()), Path("not-an-id".to_string())).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        let err = reveal_password_handler(State(state), Path(uuid::Uuid::new_v4().to_string())).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_add_errors_map_to_status_codes() {
+        let state = state();
+        let _ = add_password_handler(State(state.clone()), Json(add_request("GitHub"))).await.unwrap();
+
+        let err = add_password_handler(State(state.clone()), Json(add_request("GitHub"))).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::CONFLICT);
+
+        let err = add_password_handler(State(state.clone()), Json(add_request("  "))).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        assert_eq!(ApiError::from(Error::EntryNotFound("x".into())).status, StatusCode::NOT_FOUND);
+        let err = ApiError::from(Error::Crypto("bad tag".into()));
+        assert_eq!(err.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.message, "Internal server error");
+    }
+
+    #[test]
+    fn test_model_to_dto_mapping() {
+        let entry = models::PasswordEntry::new(
+            "GitHub".to_string(),
+            "octocat".to_string(),
+            SecureString::from("hunter2"),
+            Some("https://github.com".to_string()),
+            Some("Work account".to_string()),
+        );
+        let id = entry.id.to_string();
+        let created_at = entry.created_at.to_rfc3339();
+
+        let dto = PasswordEntryDto::from(entry);
+        assert_eq!(dto.id, id);
+        assert_eq!(dto.title, "GitHub");
+        assert_eq!(dto.username, "octocat");
+        assert_eq!(dto.url.as_deref(), Some("https://github.com"));
+        assert_eq!(dto.notes.as_deref(), Some("Work account"));
+        assert_eq!(dto.created_at, created_at);
+        assert_eq!(dto.updated_at, created_at);
+
+        let json = serde_json::to_value(&dto).unwrap();
+        assert!(json.get("password").is_none());
+    }
 }
@@ -0,0 +1,94 @@
+use passman_cli::cli::session;
+use passman_cli::crypto::{Argon2Params, Cipher, PasswordManager};
+use passman_cli::database::{PasswordEntry, SecureString};
+use passman_cli::Error;
+
+#[test]
+fn init_creates_a_vault_that_unlocks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("passman.db");
+
+    session::init_vault(&path, "correct horse", false).unwrap();
+
+    let repo = session::open_initialized(&path, "correct horse").unwrap();
+    let key = session::unlock_with_password(&repo, "correct horse").unwrap();
+    assert_eq!(key.len(), 32);
+    assert!(matches!(
+        session::unlock_with_password(&repo, "wrong"),
+        Err(Error::Authentication(_))
+    ));
+    assert!(matches!(
+        session::open_initialized(&path, "wrong"),
+        Err(Error::Authentication(_))
+    ));
+}
+
+#[test]
+fn init_refuses_existing_vault_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("passman.db");
+    session::init_vault(&path, "first", false).unwrap();
+
+    assert!(matches!(
+        session::init_vault(&path, "second", false),
+        Err(Error::VaultAlreadyExists)
+    ));
+    let repo = session::open_initialized(&path, "first").unwrap();
+    assert!(session::unlock_with_password(&repo, "first").is_ok());
+}
+
+#[test]
+fn init_with_force_wipes_the_old_vault() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("passman.db");
+    session::init_vault(&path, "first", false).unwrap();
+
+    let repo = session::open_initialized(&path, "first").unwrap();
+    let key = session::unlock_with_password(&repo, "first").unwrap();
+    let entry = PasswordEntry::new("GitHub".to_string(), "me".to_string(), SecureString::default(), None, None);
+    repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap())
+        .unwrap();
+    drop(repo);
+
+    session::init_vault(&path, "second", true).unwrap();
+
+    assert!(session::open_initialized(&path, "first").is_err());
+    let repo = session::open_initialized(&path, "second").unwrap();
+    assert_eq!(repo.count_entries().unwrap(), 0);
+    assert!(session::unlock_with_password(&repo, "second").is_ok());
+    assert!(session::unlock_with_password(&repo, "first").is_err());
+}
+
+#[test]
+fn init_with_aes_records_the_cipher() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("passman.db");
+    let options = session::VaultOptions { cipher: Cipher::Aes256Gcm, ..Default::default() };
+    session::init_vault_with(&path, "master", false, &options).unwrap();
+
+    let repo = session::open_initialized(&path, "master").unwrap();
+    assert_eq!(repo.cipher().unwrap(), Cipher::Aes256Gcm);
+
+    let key = session::unlock_with_password(&repo, "master").unwrap();
+    let entry = PasswordEntry::new("GitHub".to_string(), "me".to_string(), SecureString::default(), None, None);
+    repo.add_entry(&entry, &repo.encryption_manager().unwrap().encrypt(&key, b"hunter2").unwrap())
+        .unwrap();
+    assert_eq!(repo.get_entry_decrypted("GitHub", &key).unwrap().password.as_str(), "hunter2");
+    assert!(repo.set_cipher(Cipher::ChaCha20Poly1305).is_err());
+}
+
+#[test]
+fn init_with_custom_kdf_params_unlocks_with_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("passman.db");
+    let kdf = Argon2Params { m_cost: 8 * 1024, t_cost: 3, p_cost: 1 };
+    let options = session::VaultOptions { kdf, ..Default::default() };
+    session::init_vault_with(&path, "master", false, &options).unwrap();
+
+    let repo = session::open_initialized(&path, "master").unwrap();
+    assert_eq!(repo.kdf_params().unwrap(), kdf);
+    let key = session::unlock_with_password(&repo, "master").unwrap();
+    let salt = repo.get_vault_metadata().unwrap().salt;
+    assert_eq!(key, PasswordManager::with_params(kdf).unwrap().derive_key("master", &salt).unwrap());
+    assert_ne!(key, PasswordManager::new().derive_key("master", &salt).unwrap());
+}